@@ -0,0 +1,2 @@
+DROP TABLE static_files;
+DROP TABLE static_blobs;
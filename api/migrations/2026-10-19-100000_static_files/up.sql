@@ -0,0 +1,22 @@
+CREATE TABLE "static_blobs" (
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    hash CHAR(64) NOT NULL,
+    size BIGINT NOT NULL,
+    content_type TEXT NOT NULL,
+    reference_count INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    uploaded BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Blobs uploaded ahead of a manifest are kept until it has had time to be committed
+    leased_until TIMESTAMP,
+    PRIMARY KEY (project_id, hash)
+);
+
+CREATE TABLE "static_files" (
+    id UUID PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    deployment_id UUID NOT NULL REFERENCES deployments(id) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    hash CHAR(64) NOT NULL,
+    size BIGINT NOT NULL,
+    content_type TEXT NOT NULL,
+    UNIQUE (deployment_id, path)
+);
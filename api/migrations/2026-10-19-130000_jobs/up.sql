@@ -0,0 +1,21 @@
+CREATE TABLE "jobs" (
+    id UUID PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind VARCHAR(64) NOT NULL,
+    payload JSONB NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'queued',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL,
+    idempotency_key VARCHAR(255) NULL,
+    result JSONB NULL,
+    error TEXT NULL,
+    run_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    locked_until TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    started_at TIMESTAMP NULL,
+    finished_at TIMESTAMP NULL
+);
+
+CREATE UNIQUE INDEX jobs_idempotency_key ON jobs (user_id, kind, idempotency_key) WHERE idempotency_key IS NOT NULL;
+CREATE INDEX jobs_due ON jobs (run_at) WHERE status = 'queued';
+CREATE INDEX jobs_history ON jobs (user_id, created_at);
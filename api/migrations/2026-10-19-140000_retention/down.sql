@@ -0,0 +1,3 @@
+ALTER TABLE deployments DROP COLUMN pinned;
+ALTER TABLE projects DROP COLUMN retention_days;
+ALTER TABLE projects DROP COLUMN retention_count;
@@ -0,0 +1,3 @@
+ALTER TABLE projects ADD COLUMN retention_count INTEGER NULL;
+ALTER TABLE projects ADD COLUMN retention_days INTEGER NULL;
+ALTER TABLE deployments ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT false;
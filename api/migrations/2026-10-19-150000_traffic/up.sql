@@ -0,0 +1,8 @@
+CREATE TABLE "traffic" (
+    project_id UUID PRIMARY KEY NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    stable_id UUID NOT NULL REFERENCES deployments(id) ON DELETE CASCADE,
+    candidate_id UUID NULL REFERENCES deployments(id) ON DELETE SET NULL,
+    weight INTEGER NOT NULL DEFAULT 0 CHECK (weight >= 0 AND weight <= 100),
+    sticky_header VARCHAR(64) NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
@@ -0,0 +1,11 @@
+CREATE TABLE "aliases" (
+    id UUID PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    name VARCHAR(63) NOT NULL,
+    deployment_id UUID NOT NULL REFERENCES deployments(id),
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    updated_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    UNIQUE (project_id, name)
+);
+
+CREATE INDEX aliases_deployment ON aliases (deployment_id);
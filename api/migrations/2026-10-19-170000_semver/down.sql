@@ -0,0 +1,3 @@
+DROP INDEX deployments_normalized_version;
+ALTER TABLE deployments DROP COLUMN normalized_version;
+ALTER TABLE deployments ALTER COLUMN version TYPE VARCHAR(12);
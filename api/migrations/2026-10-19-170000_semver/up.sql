@@ -0,0 +1,19 @@
+ALTER TABLE deployments ALTER COLUMN version TYPE VARCHAR(128);
+
+-- Versions which differ only by a leading 'v' or build metadata are the same,
+-- so uniqueness is enforced on the version without them
+ALTER TABLE deployments ADD COLUMN normalized_version VARCHAR(128);
+
+-- Deployments from before versions were enforced are left unnormalized, as
+-- are any later duplicates of a version
+UPDATE deployments SET normalized_version = normalized.version
+FROM (
+    SELECT DISTINCT ON (project_id, regexp_replace(version, '^v|\+.*$', '', 'g'))
+        id, regexp_replace(version, '^v|\+.*$', '', 'g') AS version
+    FROM deployments
+    WHERE version ~ '^v?(0|[1-9][0-9]*)\.(0|[1-9][0-9]*)\.(0|[1-9][0-9]*)(-[0-9A-Za-z.-]+)?(\+[0-9A-Za-z.-]+)?$'
+    ORDER BY project_id, regexp_replace(version, '^v|\+.*$', '', 'g'), published_at
+) AS normalized
+WHERE deployments.id = normalized.id;
+
+CREATE UNIQUE INDEX deployments_normalized_version ON deployments (project_id, normalized_version);
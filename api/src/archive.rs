@@ -0,0 +1,473 @@
+use crate::{config::CFG, errors::ApiError, static_files};
+use actix_web::web::{self, Bytes};
+use flate2::read::GzDecoder;
+use futures::{Stream, StreamExt};
+use mime::Mime;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+use tar::EntryType;
+use tempfile::TempDir;
+use zip::ZipArchive;
+
+// File type bits of a unix mode
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+// Space allowed for the header and padding of each tar entry
+const TAR_ENTRY_OVERHEAD: u64 = 4096;
+
+/// Archive formats accepted for static files
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+    Zip,
+    Tar,
+    TarGzip,
+    TarZstd,
+}
+
+impl Format {
+    /// Determine the archive format from a content type
+    pub fn from_mime(mime: &Mime) -> Option<Self> {
+        if mime.type_() != mime::APPLICATION {
+            return None;
+        }
+
+        match mime.subtype().as_str() {
+            "zip" | "x-zip" | "x-zip-compressed" => Some(Format::Zip),
+            "x-tar" => Some(Format::Tar),
+            "gzip" | "x-gzip" | "x-gtar" => Some(Format::TarGzip),
+            "zstd" | "x-zstd" => Some(Format::TarZstd),
+            _ => None,
+        }
+    }
+}
+
+/// The contents of an archive extracted to a temporary directory which
+/// is removed when dropped
+pub struct Extracted {
+    _directory: TempDir,
+    pub entries: Vec<Entry>,
+}
+
+/// A file extracted from an archive
+pub struct Entry {
+    /// Normalized path of the file within the archive
+    pub path: String,
+    /// Location of the extracted contents on disk
+    pub file: PathBuf,
+}
+
+/// Limits on the contents of an archive
+#[derive(Clone, Copy, Debug)]
+struct Limits {
+    entries: usize,
+    depth: usize,
+    extracted: u64,
+}
+
+impl Limits {
+    fn configured() -> Self {
+        Limits {
+            entries: CFG.uploads.entries,
+            depth: CFG.uploads.depth,
+            extracted: CFG.uploads.extracted,
+        }
+    }
+}
+
+/// Stream an upload to a temporary file, failing if it exceeds the limit
+pub async fn receive<S, E>(mut stream: S, limit: u64) -> Result<File, ApiError>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: fmt::Display,
+{
+    let mut file = web::block(tempfile::tempfile).await?;
+
+    let mut size = 0;
+    while let Some(chunk) = stream.next().await {
+        let chunk =
+            chunk.map_err(|e| ApiError::new(400, format!("failed to read upload: {}", e)))?;
+
+        size += chunk.len() as u64;
+        if size > limit {
+            return Err(ApiError::new(
+                413,
+                format!("upload exceeds maximum size of {} bytes", limit),
+            ));
+        }
+
+        file = web::block(move || file.write_all(&chunk).map(|_| file)).await?;
+    }
+
+    file.seek(SeekFrom::Start(0))?;
+    Ok(file)
+}
+
+/// Extract an archive, rejecting links and enforcing the configured
+/// limits on entries, path depth and extracted size. This is blocking and
+/// should be run using `web::block`.
+pub fn extract(format: Format, file: File) -> Result<Extracted, ApiError> {
+    let limits = Limits::configured();
+    match format {
+        Format::Zip => extract_zip(file, limits),
+        Format::Tar => extract_tar(file, limits),
+        Format::TarGzip => extract_tar(GzDecoder::new(file), limits),
+        Format::TarZstd => extract_tar(zstd::stream::read::Decoder::new(file)?, limits),
+    }
+}
+
+fn extract_zip<R: Read + Seek>(reader: R, limits: Limits) -> Result<Extracted, ApiError> {
+    let mut archive = ZipArchive::new(reader)?;
+    if archive.len() > limits.entries {
+        return Err(too_many_entries(limits));
+    }
+
+    let mut extractor = Extractor::new(limits)?;
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+        if entry.name().ends_with('/') {
+            continue;
+        }
+        if let Some(mode) = entry.unix_mode() {
+            if mode & S_IFMT == S_IFLNK {
+                return Err(link(entry.name()));
+            }
+        }
+
+        let name = entry.name().to_string();
+        extractor.add(&name, entry)?;
+    }
+
+    Ok(extractor.finish())
+}
+
+fn extract_tar<R: Read>(reader: R, limits: Limits) -> Result<Extracted, ApiError> {
+    // Bound the decompressed stream so entries which are skipped, such as
+    // directories and extension headers, can't be used to inflate it
+    let limit = limits.extracted + (limits.entries as u64 + 1) * TAR_ENTRY_OVERHEAD;
+    let exceeded = Rc::new(Cell::new(false));
+    let reader = Bounded {
+        inner: reader,
+        remaining: limit,
+        exceeded: exceeded.clone(),
+    };
+
+    let result = extract_tar_entries(reader, limits);
+    if exceeded.get() {
+        return Err(contents_too_large(limits));
+    }
+    result
+}
+
+fn extract_tar_entries<R: Read>(reader: R, limits: Limits) -> Result<Extracted, ApiError> {
+    let mut archive = tar::Archive::new(reader);
+    let mut extractor = Extractor::new(limits)?;
+
+    let mut seen = 0;
+    for entry in archive.entries().map_err(invalid_tar)? {
+        let entry = entry.map_err(invalid_tar)?;
+
+        seen += 1;
+        if seen > limits.entries {
+            return Err(too_many_entries(limits));
+        }
+
+        let name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+        match entry.header().entry_type() {
+            EntryType::Regular | EntryType::Continuous => extractor.add(&name, entry)?,
+            EntryType::Directory | EntryType::XGlobalHeader => {}
+            EntryType::Symlink | EntryType::Link => return Err(link(&name)),
+            _ => {
+                return Err(ApiError::new(
+                    400,
+                    format!("archive entry '{}' is not a regular file", name),
+                ))
+            }
+        }
+    }
+
+    Ok(extractor.finish())
+}
+
+/// Writes archive entries to a temporary directory while tracking the limits
+struct Extractor {
+    directory: TempDir,
+    entries: Vec<Entry>,
+    paths: HashSet<String>,
+    remaining: u64,
+    limits: Limits,
+}
+
+impl Extractor {
+    fn new(limits: Limits) -> Result<Self, ApiError> {
+        Ok(Extractor {
+            directory: tempfile::tempdir()?,
+            entries: Vec::new(),
+            paths: HashSet::new(),
+            remaining: limits.extracted,
+            limits,
+        })
+    }
+
+    fn add<R: Read>(&mut self, name: &str, reader: R) -> Result<(), ApiError> {
+        if self.entries.len() >= self.limits.entries {
+            return Err(too_many_entries(self.limits));
+        }
+
+        let path = static_files::normalize_path(name)
+            .ok_or_else(|| ApiError::new(400, format!("invalid archive entry path '{}'", name)))?;
+        if path.matches('/').count() > self.limits.depth {
+            return Err(ApiError::new(
+                400,
+                format!(
+                    "archive entry '{}' is nested more than {} directories deep",
+                    path, self.limits.depth
+                ),
+            ));
+        }
+        if !self.paths.insert(path.clone()) {
+            return Err(ApiError::new(
+                400,
+                format!("duplicate archive entry '{}'", path),
+            ));
+        }
+
+        // Entries are stored by index so their paths never touch the filesystem.
+        // The declared size of an entry can't be trusted, so stop reading one byte
+        // past the remaining allowance.
+        let file = self.directory.path().join(self.entries.len().to_string());
+        let mut output = File::create(&file)?;
+        let mut reader = reader.take(self.remaining + 1);
+        let mut buffer = [0; 8192];
+        let mut written = 0;
+        loop {
+            let n = reader.read(&mut buffer).map_err(|e| {
+                ApiError::new(
+                    400,
+                    format!("failed to read archive entry '{}': {}", path, e),
+                )
+            })?;
+            if n == 0 {
+                break;
+            }
+
+            written += n as u64;
+            if written > self.remaining {
+                return Err(contents_too_large(self.limits));
+            }
+            output.write_all(&buffer[..n])?;
+        }
+        self.remaining -= written;
+
+        self.entries.push(Entry { path, file });
+        Ok(())
+    }
+
+    fn finish(self) -> Extracted {
+        Extracted {
+            _directory: self.directory,
+            entries: self.entries,
+        }
+    }
+}
+
+/// Reader which fails once more than a fixed number of bytes are read
+struct Bounded<R> {
+    inner: R,
+    remaining: u64,
+    exceeded: Rc<Cell<bool>>,
+}
+
+impl<R: Read> Read for Bounded<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n as u64 > self.remaining {
+            self.exceeded.set(true);
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "archive exceeds maximum size",
+            ));
+        }
+
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+fn invalid_tar(error: io::Error) -> ApiError {
+    ApiError::new(400, format!("invalid tar archive: {}", error))
+}
+
+fn link(name: &str) -> ApiError {
+    ApiError::new(400, format!("archive entry '{}' is a link", name))
+}
+
+fn contents_too_large(limits: Limits) -> ApiError {
+    ApiError::new(
+        413,
+        format!(
+            "archive contents exceed maximum size of {} bytes",
+            limits.extracted
+        ),
+    )
+}
+
+fn too_many_entries(limits: Limits) -> ApiError {
+    ApiError::new(
+        413,
+        format!("archive contains more than {} entries", limits.entries),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tar::{Builder, Header};
+    use zip::{write::FileOptions, ZipWriter};
+
+    fn limits(entries: usize, depth: usize, extracted: u64) -> Limits {
+        Limits {
+            entries,
+            depth,
+            extracted,
+        }
+    }
+
+    fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = Builder::new(Vec::new());
+        for (path, data) in files {
+            let mut header = Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, path, *data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn zip(files: &[(&str, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, data) in files {
+            writer.start_file(*path, FileOptions::default()).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn extract_tar_files(files: &[(&str, &[u8])], limits: Limits) -> Result<Extracted, ApiError> {
+        extract_tar(Cursor::new(tar(files)), limits)
+    }
+
+    #[test]
+    fn extracts_tar_archives() {
+        let files: &[(&str, &[u8])] = &[("index.html", b"<html>"), ("./css/site.css", b"body {}")];
+        let extracted = extract_tar_files(files, limits(10, 4, 1024)).unwrap();
+
+        let paths: Vec<&str> = extracted.entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["index.html", "css/site.css"]);
+        assert_eq!(
+            std::fs::read(&extracted.entries[1].file).unwrap(),
+            b"body {}"
+        );
+    }
+
+    #[test]
+    fn extracts_zip_archives() {
+        let archive = zip(&[("a/b.txt", b"b"), ("c.txt", b"c")]);
+        let extracted = extract_zip(archive, limits(10, 4, 1024)).unwrap();
+
+        let paths: Vec<&str> = extracted.entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["a/b.txt", "c.txt"]);
+        assert_eq!(std::fs::read(&extracted.entries[1].file).unwrap(), b"c");
+    }
+
+    #[test]
+    fn rejects_too_many_entries() {
+        let files: &[(&str, &[u8])] = &[("a", b""), ("b", b""), ("c", b"")];
+        let error = extract_tar_files(files, limits(2, 4, 1024)).unwrap_err();
+        assert_eq!(error.status_code, 413);
+
+        let error = extract_zip(zip(files), limits(2, 4, 1024)).unwrap_err();
+        assert_eq!(error.status_code, 413);
+    }
+
+    #[test]
+    fn rejects_deeply_nested_entries() {
+        let files: &[(&str, &[u8])] = &[("a/b/c.txt", b"")];
+        for error in vec![
+            extract_tar_files(files, limits(10, 1, 1024)).unwrap_err(),
+            extract_zip(zip(files), limits(10, 1, 1024)).unwrap_err(),
+        ] {
+            assert_eq!(
+                error.message,
+                "archive entry 'a/b/c.txt' is nested more than 1 directories deep"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_contents_larger_than_limit() {
+        let files: &[(&str, &[u8])] = &[("a", b"123456"), ("b", b"123456")];
+        let error = extract_tar_files(files, limits(10, 4, 10)).unwrap_err();
+        assert_eq!(error.status_code, 413);
+
+        let error = extract_zip(zip(files), limits(10, 4, 10)).unwrap_err();
+        assert_eq!(error.status_code, 413);
+    }
+
+    #[test]
+    fn rejects_streams_larger_than_limit() {
+        // Long names are stored in extension headers which aren't entries
+        // themselves, but still count towards the size of the stream
+        let name = "a".repeat(3 * TAR_ENTRY_OVERHEAD as usize);
+        let files: &[(&str, &[u8])] = &[(&name, b"")];
+        let error = extract_tar_files(files, limits(1, 4, 0)).unwrap_err();
+        assert_eq!(error.status_code, 413);
+    }
+
+    #[test]
+    fn rejects_links() {
+        let mut builder = Builder::new(Vec::new());
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Symlink);
+        header.set_link_name("/etc/passwd").unwrap();
+        header.set_size(0);
+        builder
+            .append_data(&mut header, "passwd", io::empty())
+            .unwrap();
+        let archive = builder.into_inner().unwrap();
+
+        let error = extract_tar(Cursor::new(archive), limits(10, 4, 1024)).unwrap_err();
+        assert_eq!(error.message, "archive entry 'passwd' is a link");
+    }
+
+    #[test]
+    fn rejects_duplicate_and_invalid_paths() {
+        let files: &[(&str, &[u8])] = &[("a.txt", b""), ("./a.txt", b"")];
+        let error = extract_tar_files(files, limits(10, 4, 1024)).unwrap_err();
+        assert_eq!(error.message, "duplicate archive entry 'a.txt'");
+        let error = extract_zip(zip(files), limits(10, 4, 1024)).unwrap_err();
+        assert_eq!(error.message, "duplicate archive entry 'a.txt'");
+
+        let error = extract_zip(zip(&[("../a.txt", b"")]), limits(10, 4, 1024)).unwrap_err();
+        assert_eq!(error.message, "invalid archive entry path '../a.txt'");
+    }
+
+    #[test]
+    fn detects_formats() {
+        let format = |mime: &str| Format::from_mime(&mime.parse().unwrap());
+        assert!(matches!(format("application/zip"), Some(Format::Zip)));
+        assert!(matches!(
+            format("application/x-gzip"),
+            Some(Format::TarGzip)
+        ));
+        assert!(matches!(format("application/zstd"), Some(Format::TarZstd)));
+        assert!(format("text/zip").is_none());
+        assert!(format("application/json").is_none());
+    }
+}
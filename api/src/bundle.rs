@@ -0,0 +1,94 @@
+use crate::{
+    archive::{Entry, Extracted},
+    errors::ApiError,
+    project_format::ProjectFormat,
+    references, static_files,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+// Names the project file may have at the root of a bundle
+static PROJECT_FILES: &[&str] = &["project.yaml", "project.yml", "project.json"];
+
+/// A project directory extracted from an archive
+pub struct Bundle {
+    pub format: ProjectFormat,
+    /// Static files as their path within the static directory and location on disk
+    pub static_files: Vec<(String, PathBuf)>,
+}
+
+/// Load the project configuration and static files from an extracted
+/// project directory. The directory may be the root of the archive or a
+/// single directory within it. This is blocking and should be run using
+/// `web::block`.
+pub fn load(extracted: &Extracted) -> Result<Bundle, ApiError> {
+    let root = find_root(&extracted.entries)?;
+    let entries: HashMap<&str, &Entry> = extracted
+        .entries
+        .iter()
+        .filter(|e| e.path.starts_with(&root))
+        .map(|e| (&e.path[root.len()..], e))
+        .collect();
+
+    let (project_path, project_entry) = PROJECT_FILES
+        .iter()
+        .find_map(|name| entries.get(name).map(|e| (*name, *e)))
+        .ok_or_else(|| ApiError::new(400, "archive does not contain a project.yaml".to_string()))?;
+    let project = parse(project_path, project_entry)?;
+    let project = references::resolve(project_path, project, |path| {
+        let entry = entries.get(path).ok_or_else(|| {
+            ApiError::new(400, format!("referenced file '{}' does not exist", path))
+        })?;
+        parse(path, entry)
+    })?;
+
+    let format: ProjectFormat = serde_json::from_value(project)
+        .map_err(|e| ApiError::new(400, format!("invalid project configuration: {}", e)))?;
+
+    let directory = static_files::normalize_path(&format.static_directory).ok_or_else(|| {
+        ApiError::new(
+            400,
+            format!("invalid static directory '{}'", format.static_directory),
+        )
+    })?;
+    let prefix = format!("{}/", directory);
+    let static_files = entries
+        .iter()
+        .filter(|(path, _)| path.starts_with(&prefix))
+        .map(|(path, entry)| (path[prefix.len()..].to_string(), entry.file.clone()))
+        .collect();
+
+    Ok(Bundle {
+        format,
+        static_files,
+    })
+}
+
+/// Find the directory containing the project file, either the root of
+/// the archive or its only top level directory
+fn find_root(entries: &[Entry]) -> Result<String, ApiError> {
+    if entries
+        .iter()
+        .any(|e| PROJECT_FILES.contains(&e.path.as_str()))
+    {
+        return Ok(String::new());
+    }
+
+    let mut directories = entries.iter().map(|e| e.path.split('/').next());
+    let first = directories.next().flatten();
+    match first {
+        Some(d) if directories.all(|o| o == Some(d)) => Ok(format!("{}/", d)),
+        _ => Err(ApiError::new(
+            400,
+            "archive does not contain a project.yaml".to_string(),
+        )),
+    }
+}
+
+/// Parse a YAML or JSON file from the bundle
+fn parse(path: &str, entry: &Entry) -> Result<Value, ApiError> {
+    let data = std::fs::read(&entry.file)?;
+    serde_yaml::from_slice(&data)
+        .map_err(|e| ApiError::new(400, format!("failed to parse '{}': {}", path, e)))
+}
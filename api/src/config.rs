@@ -16,7 +16,7 @@ pub struct External {
 }
 
 #[derive(Clone, Debug, Deserialize)]
-pub struct Gcp {
+pub struct Gcs {
     pub bucket: String,
 }
 
@@ -27,6 +27,17 @@ pub struct Http {
     pub session: Session,
 }
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct Jobs {
+    pub workers: usize,
+    pub shutdown: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Local {
+    pub directory: String,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct Logger {
     pub pretty: bool,
@@ -38,6 +49,26 @@ pub struct Redis {
     pub address: String,
 }
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct Retention {
+    pub interval: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Runtimes {
+    pub token: String,
+    pub expiry: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct S3 {
+    pub bucket: String,
+    pub region: String,
+    pub endpoint: Option<String>,
+    pub key: String,
+    pub secret: String,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct Session {
     pub age: i64,
@@ -50,10 +81,38 @@ pub struct Session {
 pub struct Settings {
     pub database: Database,
     pub external: External,
-    pub gcp: Gcp,
     pub http: Http,
+    pub jobs: Jobs,
     pub logger: Logger,
     pub redis: Redis,
+    pub retention: Retention,
+    pub runtimes: Runtimes,
+    pub storage: Storage,
+    pub uploads: Uploads,
+    pub webhooks: Webhooks,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Storage {
+    pub backend: String,
+    pub gcs: Option<Gcs>,
+    pub local: Option<Local>,
+    pub s3: Option<S3>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Uploads {
+    pub directory: String,
+    pub archive: u64,
+    pub extracted: u64,
+    pub entries: usize,
+    pub depth: usize,
+    pub blob: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Webhooks {
+    pub allow_private_destinations: bool,
 }
 
 impl Settings {
@@ -0,0 +1,384 @@
+use crate::models::{Handler, Route};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The set of changes between two deployments
+#[derive(Debug, Serialize)]
+pub struct DeploymentDiff {
+    pub routes: Changes<RouteChange>,
+    pub handlers: Changes<HandlerChange>,
+    /// `None` when either deployment's static files predate content hashing
+    #[serde(rename = "static")]
+    pub static_files: Option<Changes<StaticChange>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Changes<T> {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<T>,
+}
+
+/// A route whose path and method now point at a different handler
+#[derive(Debug, Serialize)]
+pub struct RouteChange {
+    pub route: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// A handler with a JSON patch for each field which changed
+#[derive(Debug, Serialize)]
+pub struct HandlerChange {
+    pub name: String,
+    pub changes: BTreeMap<&'static str, Vec<Value>>,
+}
+
+/// A static file whose contents changed
+#[derive(Debug, Serialize)]
+pub struct StaticChange {
+    pub path: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// Compare two deployments given their routes, handlers, and the content
+/// hashes of their static files keyed by path
+pub fn deployments(
+    from: (&[Route], &[Handler], Option<&HashMap<String, String>>),
+    to: (&[Route], &[Handler], Option<&HashMap<String, String>>),
+) -> DeploymentDiff {
+    let static_files = match (from.2, to.2) {
+        (Some(from), Some(to)) => Some(static_files(from, to)),
+        _ => None,
+    };
+
+    DeploymentDiff {
+        routes: routes(from.0, to.0),
+        handlers: handlers(from.1, to.1),
+        static_files,
+    }
+}
+
+fn routes(from: &[Route], to: &[Route]) -> Changes<RouteChange> {
+    let from = route_map(from);
+    let to = route_map(to);
+
+    let mut changes = Changes::new(&from, &to);
+    for (route, old) in &from {
+        if let Some(new) = to.get(route) {
+            if old != new {
+                changes.changed.push(RouteChange {
+                    route: route.clone(),
+                    from: old.to_string(),
+                    to: new.to_string(),
+                });
+            }
+        }
+    }
+    changes
+}
+
+/// Key each route by method and path so routes with multiple methods compare individually
+fn route_map(routes: &[Route]) -> BTreeMap<String, &str> {
+    let mut map = BTreeMap::new();
+    for route in routes {
+        for method in &route.methods {
+            map.insert(
+                format!("{} {}", method.to_uppercase(), route.path),
+                route.handler.as_str(),
+            );
+        }
+    }
+    map
+}
+
+fn handlers(from: &[Handler], to: &[Handler]) -> Changes<HandlerChange> {
+    let from: BTreeMap<&str, &Handler> = from.iter().map(|h| (h.name.as_str(), h)).collect();
+    let to: BTreeMap<&str, &Handler> = to.iter().map(|h| (h.name.as_str(), h)).collect();
+
+    let mut changes = Changes::new(&from, &to);
+    for (name, old) in &from {
+        let new = match to.get(name) {
+            Some(n) => n,
+            None => continue,
+        };
+
+        let mut fields = BTreeMap::new();
+        for (field, before, after) in vec![
+            (
+                "query_parameters",
+                json!(old.query_parameters),
+                json!(new.query_parameters),
+            ),
+            ("headers", json!(old.headers), json!(new.headers)),
+            (
+                "path_parameters",
+                json!(old.path_parameters),
+                json!(new.path_parameters),
+            ),
+            ("body", json!(old.body), json!(new.body)),
+            ("logic", old.logic.clone(), new.logic.clone()),
+        ] {
+            let patch = json_patch(&before, &after);
+            if !patch.is_empty() {
+                fields.insert(field, patch);
+            }
+        }
+
+        if !fields.is_empty() {
+            changes.changed.push(HandlerChange {
+                name: name.to_string(),
+                changes: fields,
+            });
+        }
+    }
+    changes
+}
+
+fn static_files(
+    from: &HashMap<String, String>,
+    to: &HashMap<String, String>,
+) -> Changes<StaticChange> {
+    let from: BTreeMap<&String, &String> = from.iter().collect();
+    let to: BTreeMap<&String, &String> = to.iter().collect();
+
+    let mut changes = Changes::new(&from, &to);
+    for (path, old) in &from {
+        if let Some(new) = to.get(path) {
+            if old != new {
+                changes.changed.push(StaticChange {
+                    path: path.to_string(),
+                    from: old.to_string(),
+                    to: new.to_string(),
+                });
+            }
+        }
+    }
+    changes
+}
+
+impl<T> Changes<T> {
+    /// Find the keys which were added and removed between two maps
+    fn new<K: Ord + ToString, A, B>(from: &BTreeMap<K, A>, to: &BTreeMap<K, B>) -> Self {
+        let from_keys: BTreeSet<&K> = from.keys().collect();
+        let to_keys: BTreeSet<&K> = to.keys().collect();
+
+        Changes {
+            added: to_keys
+                .difference(&from_keys)
+                .map(|k| k.to_string())
+                .collect(),
+            removed: from_keys
+                .difference(&to_keys)
+                .map(|k| k.to_string())
+                .collect(),
+            changed: vec![],
+        }
+    }
+}
+
+/// Generate the RFC 6902 JSON patch operations to transform one value into another
+pub fn json_patch(from: &Value, to: &Value) -> Vec<Value> {
+    let mut operations = Vec::new();
+    diff_values("", from, to, &mut operations);
+    operations
+}
+
+fn diff_values(path: &str, from: &Value, to: &Value, operations: &mut Vec<Value>) {
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            for (key, old) in from {
+                let child = format!("{}/{}", path, escape(key));
+                match to.get(key) {
+                    Some(new) => diff_values(&child, old, new, operations),
+                    None => operations.push(json!({ "op": "remove", "path": child })),
+                }
+            }
+            for (key, new) in to {
+                if !from.contains_key(key) {
+                    let child = format!("{}/{}", path, escape(key));
+                    operations.push(json!({ "op": "add", "path": child, "value": new }));
+                }
+            }
+        }
+        (Value::Array(from), Value::Array(to)) => {
+            let common = from.len().min(to.len());
+            for (i, (old, new)) in from.iter().zip(to.iter()).enumerate() {
+                diff_values(&format!("{}/{}", path, i), old, new, operations);
+            }
+            for (i, new) in to.iter().enumerate().skip(common) {
+                let child = format!("{}/{}", path, i);
+                operations.push(json!({ "op": "add", "path": child, "value": new }));
+            }
+            // Remove from the end so earlier indices stay valid
+            for i in (common..from.len()).rev() {
+                let child = format!("{}/{}", path, i);
+                operations.push(json!({ "op": "remove", "path": child }));
+            }
+        }
+        (from, to) if from != to => {
+            operations.push(json!({ "op": "replace", "path": path, "value": to }));
+        }
+        _ => {}
+    }
+}
+
+/// Escape a key for use in a JSON pointer
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn route(path: &str, methods: &[&str], handler: &str) -> Route {
+        Route {
+            id: Uuid::nil(),
+            deployment_id: Uuid::nil(),
+            path: path.to_string(),
+            methods: methods.iter().map(|m| m.to_string()).collect(),
+            handler: handler.to_string(),
+        }
+    }
+
+    fn handler(name: &str, logic: Value) -> Handler {
+        Handler {
+            id: Uuid::nil(),
+            deployment_id: Uuid::nil(),
+            name: name.to_string(),
+            query_parameters: None,
+            headers: None,
+            path_parameters: None,
+            body: None,
+            logic,
+        }
+    }
+
+    #[test]
+    fn patches_object_fields() {
+        let from = json!({ "a": 1, "b": { "c": true }, "d": "x" });
+        let to = json!({ "a": 2, "b": {}, "e": null });
+        assert_eq!(
+            json_patch(&from, &to),
+            vec![
+                json!({ "op": "replace", "path": "/a", "value": 2 }),
+                json!({ "op": "remove", "path": "/b/c" }),
+                json!({ "op": "remove", "path": "/d" }),
+                json!({ "op": "add", "path": "/e", "value": null }),
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes_keys() {
+        let from = json!({ "a/b": 1, "c~d": 1 });
+        let to = json!({ "a/b": 2, "c~d": 2 });
+        assert_eq!(
+            json_patch(&from, &to),
+            vec![
+                json!({ "op": "replace", "path": "/a~1b", "value": 2 }),
+                json!({ "op": "replace", "path": "/c~0d", "value": 2 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn patches_growing_arrays() {
+        let from = json!([1, 2]);
+        let to = json!([1, 3, 4, 5]);
+        assert_eq!(
+            json_patch(&from, &to),
+            vec![
+                json!({ "op": "replace", "path": "/1", "value": 3 }),
+                json!({ "op": "add", "path": "/2", "value": 4 }),
+                json!({ "op": "add", "path": "/3", "value": 5 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn removes_from_the_end_of_shrinking_arrays() {
+        let from = json!({ "list": [1, 2, 3, 4] });
+        let to = json!({ "list": [0] });
+        assert_eq!(
+            json_patch(&from, &to),
+            vec![
+                json!({ "op": "replace", "path": "/list/0", "value": 0 }),
+                json!({ "op": "remove", "path": "/list/3" }),
+                json!({ "op": "remove", "path": "/list/2" }),
+                json!({ "op": "remove", "path": "/list/1" }),
+            ]
+        );
+    }
+
+    #[test]
+    fn replaces_values_of_different_types() {
+        assert_eq!(
+            json_patch(&json!({ "a": [1] }), &json!({ "a": { "0": 1 } })),
+            vec![json!({ "op": "replace", "path": "/a", "value": { "0": 1 } })]
+        );
+        assert_eq!(
+            json_patch(&json!(null), &json!([])),
+            vec![json!({ "op": "replace", "path": "", "value": [] })]
+        );
+    }
+
+    #[test]
+    fn produces_no_operations_for_equal_values() {
+        let value = json!({ "a": [1, { "b": "c" }], "d": null });
+        assert!(json_patch(&value, &value).is_empty());
+    }
+
+    #[test]
+    fn compares_deployments() {
+        let from_routes = vec![
+            route("/a", &["GET", "POST"], "a"),
+            route("/b", &["GET"], "b"),
+        ];
+        let to_routes = vec![route("/a", &["GET"], "a"), route("/a", &["POST"], "b")];
+        let from_handlers = vec![handler("a", json!([])), handler("b", json!([]))];
+        let to_handlers = vec![
+            handler("b", json!([{ "action": "return" }])),
+            handler("c", json!([])),
+        ];
+
+        let mut from_static = HashMap::new();
+        from_static.insert("index.html".to_string(), "1".to_string());
+        from_static.insert("site.css".to_string(), "2".to_string());
+        let mut to_static = HashMap::new();
+        to_static.insert("index.html".to_string(), "3".to_string());
+
+        let diff = deployments(
+            (&from_routes, &from_handlers, Some(&from_static)),
+            (&to_routes, &to_handlers, Some(&to_static)),
+        );
+
+        assert!(diff.routes.added.is_empty());
+        assert_eq!(diff.routes.removed, vec!["GET /b"]);
+        assert_eq!(diff.routes.changed.len(), 1);
+        assert_eq!(diff.routes.changed[0].route, "POST /a");
+        assert_eq!(diff.routes.changed[0].to, "b");
+
+        assert_eq!(diff.handlers.added, vec!["c"]);
+        assert_eq!(diff.handlers.removed, vec!["a"]);
+        assert_eq!(diff.handlers.changed[0].name, "b");
+        assert_eq!(
+            diff.handlers.changed[0].changes["logic"],
+            vec![json!({ "op": "add", "path": "/0", "value": { "action": "return" } })]
+        );
+
+        let static_files = diff.static_files.unwrap();
+        assert_eq!(static_files.removed, vec!["site.css"]);
+        assert_eq!(static_files.changed[0].path, "index.html");
+    }
+
+    #[test]
+    fn skips_static_files_without_hashes() {
+        let diff = deployments((&[], &[], None), (&[], &[], Some(&HashMap::new())));
+        assert!(diff.static_files.is_none());
+    }
+}
@@ -1,11 +1,13 @@
+use crate::storage::StorageError;
 use actix_threadpool::BlockingError;
 use actix_web::error::{Error as ActixError, ParseError};
 use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
-use cloud_storage::Error as CloudStorageError;
 use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use redis::RedisError;
 use serde::Deserialize;
 use std::{fmt, io::Error as IoError};
+use zip::result::ZipError;
 
 #[derive(Debug, Deserialize)]
 pub struct ApiError {
@@ -39,8 +41,9 @@ impl From<DieselError> for ApiError {
         match error {
             DieselError::DatabaseError(kind, err) => match kind {
                 DatabaseErrorKind::UniqueViolation => ApiError::new(409, err.message().to_string()),
+                // The referenced record was removed or is still referenced by another
                 DatabaseErrorKind::ForeignKeyViolation => ApiError::new(
-                    500,
+                    409,
                     format!("Foreign key constraint violated: {}", err.message()),
                 ),
                 DatabaseErrorKind::UnableToSendCommand => {
@@ -91,11 +94,56 @@ impl From<BlockingError<IoError>> for ApiError {
     }
 }
 
-impl From<BlockingError<CloudStorageError>> for ApiError {
-    fn from(error: BlockingError<CloudStorageError>) -> ApiError {
+impl From<RedisError> for ApiError {
+    fn from(error: RedisError) -> ApiError {
+        ApiError::new(500, format!("redis error: {}", error))
+    }
+}
+
+impl From<StorageError> for ApiError {
+    fn from(error: StorageError) -> ApiError {
         match error {
-            BlockingError::Canceled => ApiError::new(500, "upload operation was cancelled".to_string()),
-            BlockingError::Error(e) => ApiError::new(500, format!("failed to upload object to google cloud storage: {}", e))
+            StorageError::NotFound(key) => {
+                ApiError::new(404, format!("file '{}' does not exist", key))
+            }
+            e => ApiError::new(500, format!("storage operation failed: {}", e)),
+        }
+    }
+}
+
+impl From<BlockingError<StorageError>> for ApiError {
+    fn from(error: BlockingError<StorageError>) -> ApiError {
+        match error {
+            BlockingError::Canceled => {
+                ApiError::new(500, "storage operation was cancelled".to_string())
+            }
+            BlockingError::Error(e) => ApiError::from(e),
+        }
+    }
+}
+
+impl From<BlockingError<ApiError>> for ApiError {
+    fn from(error: BlockingError<ApiError>) -> ApiError {
+        match error {
+            BlockingError::Canceled => ApiError::new(500, "operation was cancelled".to_string()),
+            BlockingError::Error(e) => e,
+        }
+    }
+}
+
+impl From<ZipError> for ApiError {
+    fn from(error: ZipError) -> ApiError {
+        match error {
+            ZipError::Io(e) => ApiError::new(500, format!("failed to process zip archive: {}", e)),
+            ZipError::InvalidArchive(e) => {
+                ApiError::new(400, format!("invalid zip archive: {}", e))
+            }
+            ZipError::UnsupportedArchive(e) => {
+                ApiError::new(400, format!("unsupported zip archive: {}", e))
+            }
+            ZipError::FileNotFound => {
+                ApiError::new(404, "file not found in zip archive".to_string())
+            }
         }
     }
 }
@@ -0,0 +1,303 @@
+//! Events published over redis to notify runtimes of deployment changes.
+//!
+//! Events are appended to the `deployments:events` stream as a JSON object
+//! in the `event` field of each entry:
+//!
+//! ```json
+//! {
+//!   "schema_version": 1,
+//!   "type": "deployment.published",
+//!   "project_id": "0b5cc8a4-0b4e-4d3a-9a43-3f5c4f2f3c7e",
+//!   "deployment_id": "5e0f1c3a-7d0c-4a55-8f0a-bd0c2ad0f0a1",
+//!   "version": "v1.0.0",
+//!   "timestamp": "2026-10-19T12:00:00.000000Z",
+//!   "correlation_id": "a7f3c3d2-2c3b-4b8e-9d7c-1c2f0e9b8a6d"
+//! }
+//! ```
+//!
+//! | Type                   | Meaning                                                   |
+//! |------------------------|-----------------------------------------------------------|
+//! | `deployment.published` | The deployment is complete and can be served              |
+//! | `deployment.deleted`   | The deployment was removed and must be unloaded           |
+//! | `deployment.promoted`  | The deployment receives all of the project's traffic      |
+//! | `traffic.updated`      | The split of the project's unversioned traffic changed    |
+//! | `alias.updated`        | An alias was created or now points at the deployment      |
+//! | `alias.deleted`        | An alias pointing at the deployment was removed           |
+//!
+//! `deployment.promoted` and `traffic.updated` events have a `traffic` field
+//! with the project's new split, see `models::Traffic`. Alias events have an
+//! `alias` field with the alias, see `models::Alias`.
+//!
+//! `schema_version` is incremented whenever a field is removed or its meaning
+//! changes. Fields may be added without a new version, so consumers should
+//! ignore fields they don't recognize and skip events with a newer version.
+//!
+//! Each runtime process reads the stream through its own consumer group,
+//! named by its runtime id, and acknowledges an event once it has been
+//! applied. An event is redelivered until it is acknowledged, so consumers
+//! must be able to process the same event more than once. A runtime's group
+//! is removed when it deregisters or its heartbeats expire.
+
+use crate::{
+    errors::ApiError,
+    models::{Alias, Deployment, Traffic},
+    progress::{self, Progress, State},
+    webhooks,
+};
+use chrono::{DateTime, Utc};
+use redis::{
+    streams::{StreamInfoGroupsReply, StreamMaxlen, StreamPendingCountReply, StreamRangeReply},
+    Commands,
+};
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Version of the event schema
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Stream events are appended to
+pub const STREAM: &str = "deployments:events";
+
+// Approximate number of events kept in the stream
+const MAX_LENGTH: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub enum EventType {
+    #[serde(rename = "deployment.published")]
+    DeploymentPublished,
+    #[serde(rename = "deployment.deleted")]
+    DeploymentDeleted,
+    #[serde(rename = "deployment.promoted")]
+    DeploymentPromoted,
+    #[serde(rename = "traffic.updated")]
+    TrafficUpdated,
+    #[serde(rename = "alias.updated")]
+    AliasUpdated,
+    #[serde(rename = "alias.deleted")]
+    AliasDeleted,
+}
+
+impl EventType {
+    /// The webhook event delivered for events of this type
+    fn webhook(self) -> Option<&'static str> {
+        match self {
+            EventType::DeploymentPublished => Some("deployment.ready"),
+            EventType::DeploymentDeleted => Some("deployment.deleted"),
+            EventType::DeploymentPromoted => Some("deployment.promoted"),
+            EventType::TrafficUpdated | EventType::AliasUpdated | EventType::AliasDeleted => None,
+        }
+    }
+
+    /// The state a deployment is in after events of this type
+    fn state(self) -> Option<State> {
+        match self {
+            EventType::DeploymentPublished => Some(State::Ready),
+            EventType::DeploymentDeleted => Some(State::Deleted),
+            EventType::DeploymentPromoted
+            | EventType::TrafficUpdated
+            | EventType::AliasUpdated
+            | EventType::AliasDeleted => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Event {
+    pub schema_version: u32,
+    #[serde(rename = "type")]
+    pub kind: EventType,
+    pub project_id: Uuid,
+    pub deployment_id: Uuid,
+    pub version: String,
+    pub timestamp: DateTime<Utc>,
+    pub correlation_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub traffic: Option<Traffic>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<Alias>,
+}
+
+impl Event {
+    /// Create an event about a deployment
+    pub fn new(kind: EventType, deployment: &Deployment, correlation_id: String) -> Self {
+        Event {
+            schema_version: SCHEMA_VERSION,
+            kind,
+            project_id: deployment.project_id,
+            deployment_id: deployment.id,
+            version: deployment.version.clone(),
+            timestamp: Utc::now(),
+            correlation_id,
+            traffic: None,
+            alias: None,
+        }
+    }
+
+    /// Create an event about a change to a project's traffic split
+    pub fn traffic(
+        kind: EventType,
+        deployment: &Deployment,
+        traffic: Traffic,
+        correlation_id: String,
+    ) -> Self {
+        Event {
+            traffic: Some(traffic),
+            ..Event::new(kind, deployment, correlation_id)
+        }
+    }
+
+    /// Create an event about a change to one of a project's aliases
+    pub fn alias(
+        kind: EventType,
+        deployment: &Deployment,
+        alias: Alias,
+        correlation_id: String,
+    ) -> Self {
+        Event {
+            alias: Some(alias),
+            ..Event::new(kind, deployment, correlation_id)
+        }
+    }
+
+    /// Append the event to the stream, record the deployment's new state, and
+    /// queue webhook deliveries for it
+    pub fn publish(&self) -> Result<(), ApiError> {
+        let payload = serde_json::to_string(self)
+            .map_err(|e| ApiError::new(500, format!("failed to encode event: {}", e)))?;
+
+        let mut connection = crate::redis::connection()?;
+        let _id: String = connection.xadd_maxlen(
+            STREAM,
+            StreamMaxlen::Approx(MAX_LENGTH),
+            "*",
+            &[("event", payload)],
+        )?;
+        if let Some(state) = self.kind.state() {
+            progress::emit(self.deployment_id, Progress::state(state));
+        }
+
+        match self.kind.webhook() {
+            Some(event) => webhooks::enqueue(
+                self.project_id,
+                event,
+                json!({
+                    "deployment_id": self.deployment_id,
+                    "version": self.version,
+                    "correlation_id": self.correlation_id,
+                }),
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The delivery of the events for a deployment to each runtime
+#[derive(Debug, Serialize)]
+pub struct Delivery {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub timestamp: Value,
+    pub runtimes: Vec<RuntimeDelivery>,
+}
+
+/// The delivery of an event to a single runtime
+#[derive(Debug, Serialize)]
+pub struct RuntimeDelivery {
+    pub runtime: String,
+    pub status: DeliveryStatus,
+    /// Number of times the event was delivered without being acknowledged
+    pub deliveries: usize,
+    /// Milliseconds since the event was published if it is unacknowledged
+    pub lag: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// The runtime applied the event
+    Acknowledged,
+    /// The runtime received the event but has not applied it
+    Pending,
+    /// The runtime has not read the event yet
+    Undelivered,
+}
+
+/// Remove a runtime's consumer group so its undelivered events are no
+/// longer reported
+pub fn remove_group(connection: &mut redis::Connection, runtime_id: &str) -> Result<(), ApiError> {
+    let exists: bool = connection.exists(STREAM)?;
+    if exists {
+        connection.xgroup_destroy::<_, _, ()>(STREAM, runtime_id)?;
+    }
+    Ok(())
+}
+
+/// Get the delivery status of the events still in the stream for a
+/// deployment, newest first
+pub fn deliveries(deployment_id: Uuid) -> Result<Vec<Delivery>, ApiError> {
+    let mut connection = crate::redis::connection()?;
+
+    let exists: bool = connection.exists(STREAM)?;
+    if !exists {
+        return Ok(Vec::new());
+    }
+
+    let groups: StreamInfoGroupsReply = connection.xinfo_groups(STREAM)?;
+    let entries: StreamRangeReply = connection.xrevrange_count(STREAM, "+", "-", MAX_LENGTH)?;
+    let now = Utc::now().timestamp_millis() as u64;
+
+    let mut deliveries = Vec::new();
+    for entry in entries.ids {
+        let event = entry
+            .get::<String>("event")
+            .and_then(|e| serde_json::from_str::<Value>(&e).ok())
+            .unwrap_or(Value::Null);
+        if event["deployment_id"].as_str() != Some(&deployment_id.to_string()) {
+            continue;
+        }
+
+        let mut runtimes = Vec::with_capacity(groups.groups.len());
+        for group in &groups.groups {
+            let pending: StreamPendingCountReply =
+                connection.xpending_count(STREAM, &group.name, &entry.id, &entry.id, 1)?;
+
+            let (status, deliveries) = match pending.ids.first() {
+                Some(p) => (DeliveryStatus::Pending, p.times_delivered),
+                None if parse_id(&entry.id) <= parse_id(&group.last_delivered_id) => {
+                    (DeliveryStatus::Acknowledged, 0)
+                }
+                None => (DeliveryStatus::Undelivered, 0),
+            };
+            let lag = match status {
+                DeliveryStatus::Acknowledged => None,
+                _ => Some(now.saturating_sub(parse_id(&entry.id).0)),
+            };
+
+            runtimes.push(RuntimeDelivery {
+                runtime: group.name.clone(),
+                status,
+                deliveries,
+                lag,
+            });
+        }
+
+        deliveries.push(Delivery {
+            id: entry.id.clone(),
+            kind: event["type"].as_str().unwrap_or_default().to_string(),
+            timestamp: event["timestamp"].clone(),
+            runtimes,
+        });
+    }
+
+    Ok(deliveries)
+}
+
+/// Split a stream entry id into its millisecond timestamp and sequence number
+fn parse_id(id: &str) -> (u64, u64) {
+    let mut parts = id.splitn(2, '-');
+    let millis = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+    let sequence = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+    (millis, sequence)
+}
@@ -0,0 +1,291 @@
+use crate::{
+    errors::ApiError,
+    models::{Deployment, Handler, Project, Route},
+    project_format::{Handler as HandlerFormat, ProjectFormat, Route as RouteFormat},
+};
+use std::collections::HashSet;
+use std::io::{Cursor, Write};
+use zip::{write::FileOptions, ZipWriter};
+
+/// Recover the configuration a deployment was created from. Deployments
+/// created before the configuration was stored are rebuilt from their rows,
+/// which is only possible if the rows hold the configuration unchanged.
+pub fn project_format(
+    project: &Project,
+    deployment: &Deployment,
+    routes: Vec<Route>,
+    handlers: Vec<Handler>,
+) -> Result<ProjectFormat, ApiError> {
+    if let Some(source) = &deployment.source {
+        return serde_json::from_value(source.clone()).map_err(|e| {
+            ApiError::new(
+                500,
+                format!("failed to decode stored project configuration: {}", e),
+            )
+        });
+    }
+
+    let format = ProjectFormat {
+        name: project.name.clone(),
+        version: deployment.version.clone(),
+        static_directory: "./static".to_string(),
+        routes: routes
+            .into_iter()
+            .map(|r| RouteFormat {
+                path: r.path,
+                methods: r.methods,
+                handler: r.handler,
+            })
+            .collect(),
+        handlers: handlers
+            .into_iter()
+            .map(|h| HandlerFormat {
+                name: h.name,
+                query_parameters: h.query_parameters,
+                headers: h.headers,
+                path_parameters: h.path_parameters,
+                body: h.body,
+                logic: h.logic,
+            })
+            .collect(),
+    };
+    if format.hash()? != deployment.hash {
+        return Err(ApiError::new(
+            409,
+            "deployment was created before its configuration was stored and cannot be recovered"
+                .to_string(),
+        ));
+    }
+
+    Ok(format)
+}
+
+/// Build a project archive containing `project.yaml`, a file for each
+/// handler referenced with `$ref`, and the deployment's static files. The
+/// configuration is also included as `project.json` without references so
+/// it can be sent to `create` as-is.
+pub fn build_archive(
+    format: &ProjectFormat,
+    static_files: Vec<(String, Vec<u8>)>,
+) -> Result<Vec<u8>, ApiError> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+    // Write each handler to its own file
+    let mut used = HashSet::new();
+    let mut references = Vec::with_capacity(format.handlers.len());
+    for handler in &format.handlers {
+        let mut file_name = sanitize(&handler.name);
+        let mut i = 1;
+        while !used.insert(file_name.clone()) {
+            i += 1;
+            file_name = format!("{}-{}", sanitize(&handler.name), i);
+        }
+
+        let path = format!("handlers/{}.yaml", file_name);
+        writer.start_file(path.as_str(), FileOptions::default())?;
+        writer.write_all(to_yaml(handler)?.as_bytes())?;
+
+        let mut reference = serde_yaml::Mapping::new();
+        reference.insert("$ref".into(), format!("./{}", path).into());
+        references.push(serde_yaml::Value::Mapping(reference));
+    }
+
+    // Replace the inline handlers with references
+    let mut project = serde_yaml::to_value(format).map_err(yaml_error)?;
+    project["handlers"] = serde_yaml::Value::Sequence(references);
+    writer.start_file("project.yaml", FileOptions::default())?;
+    writer.write_all(to_yaml(&project)?.as_bytes())?;
+
+    let inline = serde_json::to_vec_pretty(format)
+        .map_err(|e| ApiError::new(500, format!("failed to encode project as json: {}", e)))?;
+    writer.start_file("project.json", FileOptions::default())?;
+    writer.write_all(&inline)?;
+
+    let directory = static_directory(&format.static_directory);
+    for (name, data) in static_files {
+        writer.start_file(format!("{}/{}", directory, name), FileOptions::default())?;
+        writer.write_all(&data)?;
+    }
+
+    Ok(writer.finish()?.into_inner())
+}
+
+/// Normalize the static directory to a path within the archive
+fn static_directory(directory: &str) -> String {
+    let parts: Vec<&str> = directory
+        .split('/')
+        .filter(|p| !p.is_empty() && *p != ".")
+        .collect();
+    if parts.is_empty() || parts.contains(&"..") {
+        "static".to_string()
+    } else {
+        parts.join("/")
+    }
+}
+
+/// Make a handler name safe to use as a file name
+fn sanitize(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if sanitized.is_empty() {
+        "handler".to_string()
+    } else {
+        sanitized
+    }
+}
+
+fn to_yaml<T: serde::Serialize>(value: &T) -> Result<String, ApiError> {
+    serde_yaml::to_string(value).map_err(yaml_error)
+}
+
+fn yaml_error(error: serde_yaml::Error) -> ApiError {
+    ApiError::new(500, format!("failed to encode project as yaml: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        archive::{self, Format},
+        bundle,
+    };
+    use chrono::Utc;
+    use std::io::{Read, Seek, SeekFrom};
+    use uuid::Uuid;
+    use zip::ZipArchive;
+
+    fn project() -> Project {
+        Project {
+            id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            name: "shop".to_string(),
+            description: String::new(),
+            created_at: Utc::now().naive_utc(),
+            updated_at: None,
+            retention_count: None,
+            retention_days: None,
+        }
+    }
+
+    fn format() -> ProjectFormat {
+        ProjectFormat {
+            name: "shop".to_string(),
+            version: "1.0.0".to_string(),
+            static_directory: "./public".to_string(),
+            routes: vec![RouteFormat {
+                path: "/items/{int:id}".to_string(),
+                methods: vec!["GET".to_string(), "PUT".to_string()],
+                handler: "item".to_string(),
+            }],
+            handlers: vec![HandlerFormat {
+                name: "item".to_string(),
+                query_parameters: Some(vec![]),
+                headers: None,
+                path_parameters: Some(vec!["id".to_string()]),
+                body: Some(json!({ "fields": { "price": { "type": "number" } } })),
+                logic: json!([{ "action": "return", "status": 200, "value": 1.5 }]),
+            }],
+        }
+    }
+
+    fn deployment(format: &ProjectFormat, source: Option<serde_json::Value>) -> Deployment {
+        Deployment {
+            id: Uuid::new_v4(),
+            project_id: Uuid::nil(),
+            version: format.version.clone(),
+            hash: format.hash().unwrap(),
+            has_static: true,
+            published_at: Utc::now().naive_utc(),
+            source,
+            pinned: false,
+            normalized_version: None,
+        }
+    }
+
+    fn export(deployment: &Deployment, handlers: Vec<Handler>) -> Vec<u8> {
+        let format = project_format(&project(), deployment, vec![], handlers).unwrap();
+        build_archive(
+            &format,
+            vec![("css/site.css".to_string(), b"body {}".to_vec())],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn round_trips_through_create() {
+        let format = format();
+        let deployment = deployment(&format, Some(json!(format)));
+        let archive = export(&deployment, vec![]);
+
+        let mut archive = ZipArchive::new(Cursor::new(archive)).unwrap();
+        let mut json = String::new();
+        archive
+            .by_name("project.json")
+            .unwrap()
+            .read_to_string(&mut json)
+            .unwrap();
+        assert!(!json.contains("$ref"));
+
+        let imported: ProjectFormat = serde_json::from_str(&json).unwrap();
+        assert_eq!(imported.hash().unwrap(), deployment.hash);
+    }
+
+    #[test]
+    fn round_trips_through_deploy() {
+        let format = format();
+        let deployment = deployment(&format, Some(json!(format)));
+        let archive = export(&deployment, vec![]);
+
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&archive).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let extracted = archive::extract(Format::Zip, file).unwrap();
+        let bundle = bundle::load(&extracted).unwrap();
+
+        assert_eq!(bundle.format.hash().unwrap(), deployment.hash);
+        assert_eq!(bundle.static_files.len(), 1);
+        assert_eq!(bundle.static_files[0].0, "css/site.css");
+    }
+
+    #[test]
+    fn rebuilds_unchanged_configurations() {
+        let mut format = format();
+        format.static_directory = "./static".to_string();
+        format.routes.clear();
+        format.handlers[0].query_parameters = None;
+        let deployment = deployment(&format, None);
+        let handlers = format
+            .handlers
+            .into_iter()
+            .map(|h| Handler::new(h, deployment.id))
+            .collect();
+
+        let rebuilt = project_format(&project(), &deployment, vec![], handlers).unwrap();
+        assert_eq!(rebuilt.hash().unwrap(), deployment.hash);
+    }
+
+    #[test]
+    fn rejects_configurations_which_cannot_be_rebuilt() {
+        // Empty lists are stored as null, so the original can't be recovered
+        let mut format = format();
+        format.static_directory = "./static".to_string();
+        format.routes.clear();
+        let deployment = deployment(&format, None);
+        let handlers = format
+            .handlers
+            .into_iter()
+            .map(|h| Handler::new(h, deployment.id))
+            .collect();
+
+        let error = project_format(&project(), &deployment, vec![], handlers).unwrap_err();
+        assert_eq!(error.status_code, 409);
+    }
+}
@@ -0,0 +1,423 @@
+//! Durable background jobs for work which is too slow to run within a
+//! request, such as ingesting static file archives and purging storage.
+//!
+//! Jobs are stored in Postgres and run by workers within the API process.
+//! A worker leases each job while running it and renews the lease until
+//! the job finishes, so a job left behind by a worker which stopped is
+//! picked up again once its lease expires. Only the worker holding the
+//! lease records the result. Because jobs can be picked up again, and
+//! because failed jobs are retried, every job must be safe to run more
+//! than once.
+
+use crate::{
+    archive::Format,
+    config::CFG,
+    errors::ApiError,
+    events::{Event, EventType},
+    models::{Deployment, Job, Project, StaticFile, Upload},
+    retention::{self, CollectGarbage},
+    static_files, storage,
+};
+use actix_web::web;
+use chrono::{Duration, NaiveDateTime, Utc};
+use futures::future::{self, Either};
+use mime::Mime;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::File;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use uuid::Uuid;
+
+/// Extract an uploaded archive into a deployment's static files
+pub static INGEST_STATIC: &str = "static.ingest";
+/// Delete all of a removed project's stored files
+pub static PURGE_PROJECT: &str = "project.purge";
+/// Remove a project's deployments which have expired under its retention policy
+pub static COLLECT_GARBAGE: &str = "deployments.gc";
+/// Move a deployment's static files stored before manifests into its manifest
+pub static MIGRATE_STATIC: &str = "static.migrate";
+
+// Attempts made before a job is abandoned
+const MAX_ATTEMPTS: i32 = 5;
+
+// Delay before the first retry in seconds, doubled after each attempt
+const RETRY_DELAY: i64 = 10;
+
+// How long a worker has to finish a job before another may take it over
+const LEASE: i64 = 15 * 60;
+
+// Seconds between renewals of a running job's lease
+const RENEW_INTERVAL: u64 = LEASE as u64 / 3;
+
+// Milliseconds between checks for due jobs when the queue is empty
+const POLL_INTERVAL: u64 = 1000;
+
+// Set once the server starts shutting down so no new jobs are started
+static STOPPING: AtomicBool = AtomicBool::new(false);
+
+// Number of jobs currently being run
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Deserialize, Serialize)]
+pub struct IngestStatic {
+    pub upload_id: Uuid,
+    pub correlation_id: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct PurgeProject {
+    pub project_id: Uuid,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct MigrateStatic {
+    pub deployment_id: Uuid,
+}
+
+/// Queue a job for a user, returning the existing job if one of the same
+/// kind was already queued with the idempotency key
+pub fn enqueue<T: Serialize>(
+    user_id: Uuid,
+    kind: &str,
+    payload: &T,
+    idempotency_key: Option<String>,
+) -> Result<Job, ApiError> {
+    let payload = serde_json::to_value(payload)
+        .map_err(|e| ApiError::new(500, format!("failed to encode job: {}", e)))?;
+    Job::create(
+        user_id,
+        kind.to_string(),
+        payload,
+        idempotency_key,
+        MAX_ATTEMPTS,
+    )
+}
+
+/// Queue ingesting an upload into its deployment's static files. The
+/// deployment is locked while checking that it has no static files and
+/// isn't already ingesting another upload, so concurrent requests can't
+/// both queue an ingest.
+pub fn enqueue_ingest(
+    user_id: Uuid,
+    payload: &IngestStatic,
+    idempotency_key: Option<String>,
+) -> Result<Job, ApiError> {
+    let upload = Upload::find(payload.upload_id)?;
+    let payload = serde_json::to_value(payload)
+        .map_err(|e| ApiError::new(500, format!("failed to encode job: {}", e)))?;
+
+    Deployment::lock(upload.deployment_id, |conn, deployment| {
+        if let Some(job) = find_existing(user_id, INGEST_STATIC, idempotency_key.as_deref())? {
+            return Ok(job);
+        }
+
+        if deployment.has_static {
+            return Err(ApiError::new(
+                403,
+                "static files already registered for deployment".to_string(),
+            ));
+        } else if is_ingesting(deployment.id)? {
+            return Err(ApiError::new(
+                409,
+                "static files are already being added to deployment".to_string(),
+            ));
+        }
+
+        Job::create_with(
+            conn,
+            user_id,
+            INGEST_STATIC.to_string(),
+            payload,
+            idempotency_key,
+            MAX_ATTEMPTS,
+        )
+    })
+}
+
+/// Whether a queued or running job is ingesting an upload into a deployment
+pub fn is_ingesting(deployment_id: Uuid) -> Result<bool, ApiError> {
+    let pending = pending_uploads()?;
+    let ingesting = Upload::find_all(deployment_id)?
+        .iter()
+        .any(|u| pending.contains(&u.id));
+    Ok(ingesting)
+}
+
+/// Find a job previously queued with an idempotency key, allowing a
+/// request to return it before doing any other work
+pub fn find_existing(
+    user_id: Uuid,
+    kind: &str,
+    idempotency_key: Option<&str>,
+) -> Result<Option<Job>, ApiError> {
+    match idempotency_key {
+        Some(key) => Job::find_by_key(user_id, kind, key),
+        None => Ok(None),
+    }
+}
+
+/// Queue a migration for each deployment whose static files are stored
+/// without a manifest. Each deployment is only ever migrated once.
+pub fn enqueue_migrations() -> Result<(), ApiError> {
+    for deployment in Deployment::find_without_manifest()? {
+        let project = Project::find(deployment.project_id)?;
+        enqueue(
+            project.user_id,
+            MIGRATE_STATIC,
+            &MigrateStatic {
+                deployment_id: deployment.id,
+            },
+            Some(format!("migrate:{}", deployment.id)),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Find the uploads which queued or running jobs are going to ingest
+pub fn pending_uploads() -> Result<HashSet<Uuid>, ApiError> {
+    let uploads = Job::find_active(INGEST_STATIC)?
+        .iter()
+        .filter_map(|j| payload::<IngestStatic>(j).ok())
+        .map(|p| p.upload_id)
+        .collect();
+    Ok(uploads)
+}
+
+/// Start the configured number of workers
+pub fn start() {
+    for _ in 0..CFG.jobs.workers {
+        actix_rt::spawn(work());
+    }
+}
+
+/// Stop starting new jobs and wait for running jobs to finish, up to the
+/// configured timeout. Jobs still running afterwards are retried once
+/// their lease expires.
+pub async fn shutdown() {
+    STOPPING.store(true, Ordering::SeqCst);
+
+    let deadline = Utc::now() + Duration::seconds(CFG.jobs.shutdown as i64);
+    while RUNNING.load(Ordering::SeqCst) > 0 {
+        if Utc::now() > deadline {
+            warn!(
+                "stopping with {} job(s) still running",
+                RUNNING.load(Ordering::SeqCst)
+            );
+            return;
+        }
+        actix_rt::time::delay_for(std::time::Duration::from_millis(100)).await;
+    }
+}
+
+/// Run jobs until the server shuts down
+async fn work() {
+    while !STOPPING.load(Ordering::SeqCst) {
+        // Count the job as running before it is claimed so shutdown waits for it
+        RUNNING.fetch_add(1, Ordering::SeqCst);
+        let claimed = web::block(|| Job::claim(Duration::seconds(LEASE))).await;
+
+        let idle = match claimed {
+            Ok(Some(job)) => {
+                run(job).await;
+                false
+            }
+            Ok(None) => true,
+            Err(e) => {
+                error!("failed to claim job: {}", ApiError::from(e));
+                true
+            }
+        };
+        RUNNING.fetch_sub(1, Ordering::SeqCst);
+
+        if idle {
+            actix_rt::time::delay_for(std::time::Duration::from_millis(POLL_INTERVAL)).await;
+        }
+    }
+}
+
+/// Run a job and record its result
+async fn run(job: Job) {
+    let result = if job.attempts > job.max_attempts {
+        // The job's lease expired on its final attempt
+        Err(ApiError::new(
+            500,
+            "job did not finish before its lease expired".to_string(),
+        ))
+    } else {
+        // Keep the lease while the job runs, stopping if another worker takes it over
+        match future::select(Box::pin(execute(&job)), Box::pin(keep_leased(&job))).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => {
+                warn!("job {} was taken over by another worker", job.id);
+                return;
+            }
+        }
+    };
+
+    let recorded = match result {
+        Ok(value) => job.succeed(value),
+        Err(e) => {
+            let retry_at = retry_at(&job, &e);
+            job.fail(e.message, retry_at)
+        }
+    };
+    match recorded {
+        Ok(Some(_)) => {}
+        Ok(None) => warn!(
+            "job {} was taken over before its result was recorded",
+            job.id
+        ),
+        Err(e) => error!("failed to record result of job {}: {}", job.id, e),
+    }
+}
+
+/// Renew a job's lease at a regular interval, returning once the lease
+/// has been lost to another worker
+async fn keep_leased(job: &Job) {
+    loop {
+        actix_rt::time::delay_for(std::time::Duration::from_secs(RENEW_INTERVAL)).await;
+
+        let leased = job.clone();
+        match web::block(move || leased.renew(Duration::seconds(LEASE))).await {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => error!(
+                "failed to renew lease of job {}: {}",
+                job.id,
+                ApiError::from(e)
+            ),
+        }
+    }
+}
+
+async fn execute(job: &Job) -> Result<serde_json::Value, ApiError> {
+    if job.kind == INGEST_STATIC {
+        ingest_static(payload(job)?).await
+    } else if job.kind == PURGE_PROJECT {
+        purge_project(payload(job)?).await
+    } else if job.kind == COLLECT_GARBAGE {
+        collect_garbage(payload(job)?).await
+    } else if job.kind == MIGRATE_STATIC {
+        migrate_static(payload(job)?).await
+    } else {
+        Err(ApiError::new(
+            500,
+            format!("unknown job kind '{}'", job.kind),
+        ))
+    }
+}
+
+/// When to retry a failed job, if at all. Errors caused by the request
+/// itself will not succeed when retried.
+fn retry_at(job: &Job, error: &ApiError) -> Option<NaiveDateTime> {
+    if job.attempts >= job.max_attempts || error.status_code < 500 {
+        return None;
+    }
+
+    let delay = RETRY_DELAY << (job.attempts - 1).max(0);
+    Some(Utc::now().naive_utc() + Duration::seconds(delay))
+}
+
+/// Decode the payload of a job
+fn payload<T: DeserializeOwned>(job: &Job) -> Result<T, ApiError> {
+    serde_json::from_value(job.payload.clone())
+        .map_err(|e| ApiError::new(400, format!("invalid job payload: {}", e)))
+}
+
+async fn ingest_static(payload: IngestStatic) -> Result<serde_json::Value, ApiError> {
+    let upload = Upload::find(payload.upload_id)?;
+    let deployment = Deployment::find(upload.deployment_id)?;
+    let result = json!({ "deployment_id": deployment.id });
+
+    // A previous attempt stored the files but failed to publish the
+    // deployment or remove the upload, and publishing again is harmless
+    if deployment.has_static {
+        Event::new(
+            EventType::DeploymentPublished,
+            &deployment,
+            payload.correlation_id,
+        )
+        .publish()?;
+        static_files::remove_upload(upload.id).await?;
+        return Ok(result);
+    }
+
+    // Discard anything stored by a previous attempt which failed part way
+    StaticFile::release_all(deployment.project_id, deployment.id)?;
+
+    let format = upload
+        .content_type
+        .parse::<Mime>()
+        .ok()
+        .and_then(|m| Format::from_mime(&m))
+        .ok_or_else(|| {
+            ApiError::new(
+                400,
+                "upload must be a zip, tar, tar.gz, or tar.zst archive".to_string(),
+            )
+        })?;
+    let path = static_files::upload_path(upload.id);
+    let file = web::block(move || File::open(path)).await?;
+
+    static_files::ingest(deployment.project_id, deployment.id, format, file).await?;
+    deployment.mark_has_static()?;
+
+    // The upload is kept until the deployment is published so a retry can publish it
+    Event::new(
+        EventType::DeploymentPublished,
+        &deployment,
+        payload.correlation_id,
+    )
+    .publish()?;
+    static_files::remove_upload(upload.id).await?;
+
+    Ok(result)
+}
+
+async fn purge_project(payload: PurgeProject) -> Result<serde_json::Value, ApiError> {
+    // The job is queued before the project is deleted, so retry until it has been
+    match Project::find(payload.project_id) {
+        Ok(_) => {
+            return Err(ApiError::new(
+                500,
+                "project has not been deleted".to_string(),
+            ))
+        }
+        Err(e) if e.status_code == 404 => {}
+        Err(e) => return Err(e),
+    }
+
+    let prefix = storage::project_prefix(payload.project_id);
+    let files = web::block(move || storage::backend().list(&prefix)).await?;
+
+    let count = files.len();
+    for file in files {
+        web::block(move || storage::backend().delete(&file.key)).await?;
+    }
+
+    Ok(json!({ "deleted": count }))
+}
+
+async fn collect_garbage(payload: CollectGarbage) -> Result<serde_json::Value, ApiError> {
+    let report = retention::collect(payload.project_id, payload.correlation_id).await?;
+    serde_json::to_value(report)
+        .map_err(|e| ApiError::new(500, format!("failed to encode report: {}", e)))
+}
+
+async fn migrate_static(payload: MigrateStatic) -> Result<serde_json::Value, ApiError> {
+    let deployment = Deployment::find(payload.deployment_id)?;
+    let migrated = static_files::migrate(&deployment).await?;
+
+    // Runtimes only serve files in the deployment's manifest
+    if migrated > 0 {
+        Event::new(
+            EventType::DeploymentPublished,
+            &deployment,
+            Uuid::new_v4().to_string(),
+        )
+        .publish()?;
+    }
+
+    Ok(json!({ "migrated": migrated }))
+}
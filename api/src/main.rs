@@ -15,14 +15,30 @@ use actix_redis::RedisSession;
 use actix_web::{middleware, App, HttpServer};
 use std::str::FromStr;
 
+mod archive;
+mod bundle;
 mod config;
 mod database;
+mod diff;
 mod errors;
+mod events;
+mod export;
+mod jobs;
 mod models;
+mod openapi;
+mod progress;
 mod project_format;
 mod redis;
+mod references;
+mod retention;
 mod routes;
+mod runtimes;
 mod schema;
+mod static_files;
+mod storage;
+mod typescript;
+mod versions;
+mod webhooks;
 
 // Log format string
 static LOG_FORMAT: &str = "%a \"%r\" %s %b \"%{Referer}i\" \"%{User-Agent}i\" %D";
@@ -35,6 +51,7 @@ async fn main() -> std::io::Result<()> {
     // Connect to dependent services
     database::connect();
     redis::connect();
+    storage::connect();
 
     // Build server and bind
     let server = HttpServer::new(|| {
@@ -52,6 +69,13 @@ async fn main() -> std::io::Result<()> {
             .configure(routes::users)
             .configure(routes::projects)
             .configure(routes::deployments)
+            .configure(routes::static_files)
+            .configure(routes::traffic)
+            .configure(routes::aliases)
+            .configure(routes::uploads)
+            .configure(routes::runtimes)
+            .configure(routes::webhooks)
+            .configure(routes::jobs)
     })
     .server_hostname(&CFG.http.domain)
     .bind(&CFG.http.address)?;
@@ -59,8 +83,28 @@ async fn main() -> std::io::Result<()> {
     // Initialize Sentry
     initialize_sentry();
 
-    // Run server
-    server.run().await
+    // Send queued webhook deliveries in the background
+    actix_rt::spawn(webhooks::run());
+
+    // Collect expired deployments of projects with a retention policy
+    actix_rt::spawn(retention::schedule());
+
+    // Discard resumable uploads which were abandoned
+    actix_rt::spawn(static_files::schedule_upload_expiry());
+
+    // Forget runtimes which stopped without deregistering
+    actix_rt::spawn(runtimes::schedule_expiry());
+
+    // Run background jobs, including moving static files from older layouts
+    if let Err(e) = jobs::enqueue_migrations() {
+        error!("failed to queue static file migrations: {}", e);
+    }
+    jobs::start();
+
+    // Run server, then let running jobs finish once it stops accepting requests
+    server.run().await?;
+    jobs::shutdown().await;
+    Ok(())
 }
 
 /// Initialize sentry configuration
@@ -0,0 +1,146 @@
+use crate::{
+    database,
+    errors::ApiError,
+    models::{Deployment, Project},
+    schema::aliases,
+};
+use chrono::{NaiveDateTime, Utc};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize)]
+pub struct AliasMessage {
+    pub deployment_id: Uuid,
+    /// Deployment the alias must currently point at for it to be changed
+    pub previous: Option<Uuid>,
+}
+
+/// A mutable name pointing at one of a project's deployments
+#[derive(
+    Clone, Debug, Serialize, Deserialize, Queryable, Insertable, Associations, Identifiable,
+)]
+#[belongs_to(Project)]
+#[belongs_to(Deployment)]
+#[table_name = "aliases"]
+pub struct Alias {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub name: String,
+    pub deployment_id: Uuid,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl Alias {
+    /// Retrieve all aliases for a project
+    pub fn find_all(project_id: Uuid) -> Result<Vec<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let results = aliases::table
+            .filter(aliases::project_id.eq(project_id))
+            .order(aliases::name.asc())
+            .load::<Alias>(&conn)?;
+        Ok(results)
+    }
+
+    /// Find an alias in a project by name
+    pub fn find(project_id: Uuid, name: &str) -> Result<Option<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let alias = aliases::table
+            .filter(aliases::project_id.eq(project_id))
+            .filter(aliases::name.eq(name))
+            .first(&conn)
+            .optional()?;
+        Ok(alias)
+    }
+
+    /// Retrieve all aliases pointing at a deployment using a connection,
+    /// such as one within a transaction
+    pub fn find_for_deployment(
+        conn: &PgConnection,
+        deployment_id: Uuid,
+    ) -> Result<Vec<Self>, ApiError> {
+        let results = aliases::table
+            .filter(aliases::deployment_id.eq(deployment_id))
+            .load::<Alias>(conn)?;
+        Ok(results)
+    }
+
+    /// Point an alias at a deployment, creating it if it does not exist. If
+    /// `previous` is given, the alias is only changed if it already points
+    /// at that deployment.
+    pub fn set(
+        project_id: Uuid,
+        name: String,
+        deployment_id: Uuid,
+        previous: Option<Uuid>,
+    ) -> Result<Self, ApiError> {
+        let conn = database::connection()?;
+
+        conn.transaction(|| {
+            let existing = aliases::table
+                .filter(aliases::project_id.eq(project_id))
+                .filter(aliases::name.eq(&name))
+                .for_update()
+                .first::<Alias>(&conn)
+                .optional()?;
+
+            if let Some(previous) = previous {
+                if existing.as_ref().map(|a| a.deployment_id) != Some(previous) {
+                    return Err(ApiError::new(
+                        409,
+                        "alias does not point at the previous deployment".to_string(),
+                    ));
+                }
+            }
+
+            let now = Utc::now().naive_utc();
+            let alias = match existing {
+                Some(alias) => diesel::update(aliases::table.filter(aliases::id.eq(alias.id)))
+                    .set((
+                        aliases::deployment_id.eq(deployment_id),
+                        aliases::updated_at.eq(now),
+                    ))
+                    .get_result(&conn)?,
+                None => {
+                    let result = diesel::insert_into(aliases::table)
+                        .values(Alias {
+                            id: Uuid::new_v4(),
+                            project_id,
+                            name: name.clone(),
+                            deployment_id,
+                            created_at: now,
+                            updated_at: now,
+                        })
+                        .get_result(&conn);
+
+                    match result {
+                        // Another request created the alias first
+                        Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                            return Err(ApiError::new(
+                                409,
+                                "alias was created by another request".to_string(),
+                            ))
+                        }
+                        result => result?,
+                    }
+                }
+            };
+            Ok(alias)
+        })
+    }
+
+    /// Delete an alias
+    pub fn delete(id: Uuid) -> Result<usize, ApiError> {
+        let conn = database::connection()?;
+
+        let res = diesel::delete(aliases::table)
+            .filter(aliases::id.eq(id))
+            .execute(&conn)?;
+        Ok(res)
+    }
+}
@@ -1,15 +1,19 @@
-use super::{Handler, Route};
+use super::{static_file, Handler, Route};
 use crate::{
     database,
     errors::ApiError,
     models::Project,
-    project_format::{Handler as HandlerFormat, Route as RouteFormat},
-    schema::deployments,
+    project_format::ProjectFormat,
+    schema::{deployments, handlers, routes, static_files},
+    versions,
 };
 use chrono::{NaiveDateTime, Utc};
+use diesel::pg::PgConnection;
 use diesel::prelude::*;
 use diesel::result::Error as DieselError;
+use semver::Version;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
 #[derive(
@@ -24,6 +28,14 @@ pub struct Deployment {
     pub hash: String,
     pub has_static: bool,
     pub published_at: NaiveDateTime,
+    #[serde(skip_serializing)]
+    pub source: Option<serde_json::Value>,
+    /// Pinned deployments are never removed by garbage collection
+    pub pinned: bool,
+    /// The version without a leading `v` or build metadata, which is unique
+    /// within a project. Deployments from before versions were enforced have none.
+    #[serde(skip_serializing)]
+    pub normalized_version: Option<String>,
 }
 
 impl Deployment {
@@ -47,6 +59,19 @@ impl Deployment {
         Ok(deployment)
     }
 
+    /// Retrieve all deployments with static files but no manifest, whose
+    /// files were stored before they were addressed by content
+    pub fn find_without_manifest() -> Result<Vec<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let manifest = static_files::table.filter(static_files::deployment_id.eq(deployments::id));
+        let results = deployments::table
+            .filter(deployments::has_static.eq(true))
+            .filter(diesel::dsl::not(diesel::dsl::exists(manifest)))
+            .load::<Deployment>(&conn)?;
+        Ok(results)
+    }
+
     /// Get a deployment by its hash
     pub fn find_by_hash(hash: &String, project_id: Uuid) -> Result<Option<Self>, ApiError> {
         let conn = database::connection()?;
@@ -62,48 +87,104 @@ impl Deployment {
         }
     }
 
-    /// Create a deployment
-    pub fn create(version: String, hash: String, project_id: Uuid) -> Result<Self, ApiError> {
+    /// Create a deployment with its handlers and routes, keeping the
+    /// configuration it was created from. The version is kept as written in
+    /// the configuration and is unique by its parsed `version`. Deployments
+    /// created with a manifest of already uploaded static files are ready to
+    /// be served.
+    pub fn create(
+        format: ProjectFormat,
+        version: &Version,
+        hash: String,
+        project_id: Uuid,
+        manifest: Option<&BTreeMap<String, String>>,
+    ) -> Result<Self, ApiError> {
         let conn = database::connection()?;
+        let source = json!(format);
 
-        let deployment = diesel::insert_into(deployments::table)
-            .values(Deployment {
-                id: Uuid::new_v4(),
-                project_id,
-                version,
-                hash,
-                has_static: false,
-                published_at: Utc::now().naive_utc(),
-            })
-            .get_result(&conn)?;
-        Ok(deployment)
+        conn.transaction(|| {
+            let deployment: Deployment = diesel::insert_into(deployments::table)
+                .values(Deployment {
+                    id: Uuid::new_v4(),
+                    project_id,
+                    version: format.version,
+                    hash,
+                    has_static: manifest.is_some(),
+                    published_at: Utc::now().naive_utc(),
+                    source: Some(source),
+                    pinned: false,
+                    normalized_version: Some(versions::normalize(version)),
+                })
+                .get_result(&conn)?;
+
+            let handlers: Vec<Handler> = format
+                .handlers
+                .into_iter()
+                .map(|h| Handler::new(h, deployment.id))
+                .collect();
+            diesel::insert_into(handlers::table)
+                .values(&handlers)
+                .execute(&conn)?;
+
+            let routes: Vec<Route> = format
+                .routes
+                .into_iter()
+                .map(|r| Route::new(r, deployment.id))
+                .collect();
+            diesel::insert_into(routes::table)
+                .values(&routes)
+                .execute(&conn)?;
+
+            if let Some(manifest) = manifest {
+                static_file::create_manifest(&conn, project_id, deployment.id, manifest)?;
+            }
+
+            Ok(deployment)
+        })
     }
 
-    /// Delete a project
-    pub fn delete(id: Uuid) -> Result<usize, ApiError> {
-        let conn = database::connection()?;
+    /// Delete the deployment, releasing its references to static files. Its
+    /// handlers, routes and manifest are removed by the database. Must be
+    /// run within a transaction holding a lock on the deployment, see `lock`.
+    pub fn delete_with(&self, conn: &PgConnection) -> Result<usize, ApiError> {
+        static_file::release_references(conn, self.project_id, self.id)?;
 
         let res = diesel::delete(deployments::table)
-            .filter(deployments::id.eq(id))
-            .execute(&conn)?;
+            .filter(deployments::id.eq(self.id))
+            .execute(conn)?;
         Ok(res)
     }
 
-    /// Mark deployment as having static files
-    pub fn mark_has_static(&self) -> Result<usize, ApiError> {
+    /// Run `f` with the deployment locked, so requests which depend on its
+    /// current state are applied one at a time
+    pub fn lock<T, F>(id: Uuid, f: F) -> Result<T, ApiError>
+    where
+        F: FnOnce(&PgConnection, Deployment) -> Result<T, ApiError>,
+    {
         let conn = database::connection()?;
 
-        let res = diesel::update(self).set(deployments::has_static.eq(true)).execute(&conn)?;
-        Ok(res)
+        conn.transaction(|| {
+            let deployment = deployments::table
+                .filter(deployments::id.eq(id))
+                .for_update()
+                .first::<Deployment>(&conn)?;
+            f(&conn, deployment)
+        })
     }
 
-    /// Add a handler to the deployment
-    pub fn add_handler(&self, handler: HandlerFormat) -> Result<Handler, ApiError> {
-        Ok(Handler::create(handler, self.id)?)
+    /// Pin or unpin the deployment
+    pub fn set_pinned(&self, pinned: bool) -> Result<usize, ApiError> {
+        let conn = database::connection()?;
+
+        let res = diesel::update(self).set(deployments::pinned.eq(pinned)).execute(&conn)?;
+        Ok(res)
     }
 
-    /// Add a route to the deployment
-    pub fn add_route(&self, route: RouteFormat) -> Result<Route, ApiError> {
-        Ok(Route::create(route, self.id)?)
+    /// Mark deployment as having static files
+    pub fn mark_has_static(&self) -> Result<usize, ApiError> {
+        let conn = database::connection()?;
+
+        let res = diesel::update(self).set(deployments::has_static.eq(true)).execute(&conn)?;
+        Ok(res)
     }
 }
@@ -31,10 +31,8 @@ impl Handler {
         Ok(results)
     }
 
-    /// Create a handler
-    pub fn create(handler: HandlerFormat, deployment_id: Uuid) -> Result<Self, ApiError> {
-        let conn = database::connection()?;
-
+    /// Build a handler for a deployment from its configuration
+    pub fn new(handler: HandlerFormat, deployment_id: Uuid) -> Self {
         // Set unused values to null rather than empty array/object
         let query_parameters = array_is_empty(handler.query_parameters);
         let headers = array_is_empty(handler.headers);
@@ -49,29 +47,16 @@ impl Handler {
             None
         };
 
-        let handler = diesel::insert_into(handlers::table)
-            .values(Handler {
-                id: Uuid::new_v4(),
-                deployment_id,
-                name: handler.name,
-                query_parameters,
-                headers,
-                path_parameters,
-                body,
-                logic: handler.logic,
-            })
-            .get_result(&conn)?;
-        Ok(handler)
-    }
-
-    /// Delete a handler
-    pub fn delete(id: Uuid) -> Result<usize, ApiError> {
-        let conn = database::connection()?;
-
-        let res = diesel::delete(handlers::table)
-            .filter(handlers::id.eq(id))
-            .execute(&conn)?;
-        Ok(res)
+        Handler {
+            id: Uuid::new_v4(),
+            deployment_id,
+            name: handler.name,
+            query_parameters,
+            headers,
+            path_parameters,
+            body,
+            logic: handler.logic,
+        }
     }
 }
 
@@ -0,0 +1,256 @@
+use crate::{database, errors::ApiError, models::User, schema::jobs};
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// The job is waiting for a worker, either for the first time or to be retried
+pub static QUEUED: &str = "queued";
+/// A worker is running the job
+pub static RUNNING: &str = "running";
+/// The job finished successfully
+pub static SUCCEEDED: &str = "succeeded";
+/// The job was abandoned after too many attempts
+pub static FAILED: &str = "failed";
+
+/// Work run in the background on behalf of a user
+#[derive(
+    Clone, Debug, Serialize, Deserialize, Queryable, Insertable, Associations, Identifiable,
+)]
+#[belongs_to(User)]
+#[table_name = "jobs"]
+pub struct Job {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub idempotency_key: Option<String>,
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub run_at: NaiveDateTime,
+    #[serde(skip_serializing)]
+    pub locked_until: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+impl Job {
+    /// Find a job by id
+    pub fn find(id: Uuid) -> Result<Self, ApiError> {
+        let conn = database::connection()?;
+
+        let job = jobs::table.filter(jobs::id.eq(id)).first(&conn)?;
+        Ok(job)
+    }
+
+    /// Retrieve a user's most recent jobs, newest first
+    pub fn find_all(user_id: Uuid, limit: i64) -> Result<Vec<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let results = jobs::table
+            .filter(jobs::user_id.eq(user_id))
+            .order(jobs::created_at.desc())
+            .limit(limit)
+            .load::<Job>(&conn)?;
+        Ok(results)
+    }
+
+    /// Retrieve all jobs of a kind which are queued or running
+    pub fn find_active(kind: &str) -> Result<Vec<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let results = jobs::table
+            .filter(jobs::kind.eq(kind))
+            .filter(jobs::status.eq_any(vec![QUEUED, RUNNING]))
+            .load::<Job>(&conn)?;
+        Ok(results)
+    }
+
+    /// Find a job previously created with an idempotency key
+    pub fn find_by_key(user_id: Uuid, kind: &str, key: &str) -> Result<Option<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let job = jobs::table
+            .filter(jobs::user_id.eq(user_id))
+            .filter(jobs::kind.eq(kind))
+            .filter(jobs::idempotency_key.eq(key))
+            .first(&conn)
+            .optional()?;
+        Ok(job)
+    }
+
+    /// Queue a job to run immediately. If a job of the same kind was already
+    /// created with the idempotency key, that job is returned instead.
+    pub fn create(
+        user_id: Uuid,
+        kind: String,
+        payload: serde_json::Value,
+        idempotency_key: Option<String>,
+        max_attempts: i32,
+    ) -> Result<Self, ApiError> {
+        let conn = database::connection()?;
+        Job::create_with(&conn, user_id, kind, payload, idempotency_key, max_attempts)
+    }
+
+    /// Queue a job using a connection, such as one within a transaction
+    pub fn create_with(
+        conn: &PgConnection,
+        user_id: Uuid,
+        kind: String,
+        payload: serde_json::Value,
+        idempotency_key: Option<String>,
+        max_attempts: i32,
+    ) -> Result<Self, ApiError> {
+        if let Some(key) = &idempotency_key {
+            if let Some(job) = Job::find_by_key(user_id, &kind, key)? {
+                return Ok(job);
+            }
+        }
+
+        let now = Utc::now().naive_utc();
+        let result = diesel::insert_into(jobs::table)
+            .values(Job {
+                id: Uuid::new_v4(),
+                user_id,
+                kind: kind.clone(),
+                payload,
+                status: QUEUED.to_string(),
+                attempts: 0,
+                max_attempts,
+                idempotency_key: idempotency_key.clone(),
+                result: None,
+                error: None,
+                run_at: now,
+                locked_until: now,
+                created_at: now,
+                started_at: None,
+                finished_at: None,
+            })
+            .get_result(conn);
+
+        match (result, idempotency_key) {
+            // Another request created the job with the same key first
+            (Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)), Some(key)) => {
+                Job::find_by_key(user_id, &kind, &key)?
+                    .ok_or_else(|| ApiError::new(500, "conflicting job does not exist".to_string()))
+            }
+            (result, _) => Ok(result?),
+        }
+    }
+
+    /// Take the next job which is due to run, or which was left running by
+    /// a worker that stopped before its lease expired. The job is leased to
+    /// the caller so no other worker runs it at the same time.
+    pub fn claim(lease: Duration) -> Result<Option<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        conn.transaction(|| {
+            let now = Utc::now().naive_utc();
+            let job = jobs::table
+                .filter(
+                    jobs::status
+                        .eq(QUEUED)
+                        .and(jobs::run_at.le(now))
+                        .or(jobs::status.eq(RUNNING).and(jobs::locked_until.lt(now))),
+                )
+                .order(jobs::run_at.asc())
+                .for_update()
+                .skip_locked()
+                .first::<Job>(&conn)
+                .optional()?;
+
+            let job = match job {
+                Some(j) => j,
+                None => return Ok(None),
+            };
+
+            let job = diesel::update(jobs::table.filter(jobs::id.eq(job.id)))
+                .set((
+                    jobs::status.eq(RUNNING),
+                    jobs::attempts.eq(job.attempts + 1),
+                    jobs::locked_until.eq(now + lease),
+                    jobs::started_at.eq(job.started_at.unwrap_or(now)),
+                ))
+                .get_result(&conn)?;
+            Ok(Some(job))
+        })
+    }
+
+    /// Extend the lease of a running job, returning false if another
+    /// worker has taken it over since it was claimed
+    pub fn renew(&self, lease: Duration) -> Result<bool, ApiError> {
+        let conn = database::connection()?;
+
+        let renewed = diesel::update(
+            jobs::table
+                .filter(jobs::id.eq(self.id))
+                .filter(jobs::status.eq(RUNNING))
+                .filter(jobs::attempts.eq(self.attempts)),
+        )
+        .set(jobs::locked_until.eq(Utc::now().naive_utc() + lease))
+        .execute(&conn)?;
+        Ok(renewed > 0)
+    }
+
+    /// Record that the job finished successfully. Nothing is recorded if
+    /// another worker has taken the job over since it was claimed.
+    pub fn succeed(&self, result: serde_json::Value) -> Result<Option<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let job = diesel::update(
+            jobs::table
+                .filter(jobs::id.eq(self.id))
+                .filter(jobs::status.eq(RUNNING))
+                .filter(jobs::attempts.eq(self.attempts)),
+        )
+        .set((
+            jobs::status.eq(SUCCEEDED),
+            jobs::result.eq(Some(result)),
+            jobs::error.eq(None::<String>),
+            jobs::finished_at.eq(Some(Utc::now().naive_utc())),
+        ))
+        .get_result(&conn)
+        .optional()?;
+        Ok(job)
+    }
+
+    /// Record a failed attempt. The job is queued again to run at
+    /// `retry_at`, or marked as failed if there is no retry. Nothing is
+    /// recorded if another worker has taken the job over since it was claimed.
+    pub fn fail(
+        &self,
+        error: String,
+        retry_at: Option<NaiveDateTime>,
+    ) -> Result<Option<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let now = Utc::now().naive_utc();
+        let (status, run_at, finished_at) = match retry_at {
+            Some(at) => (QUEUED, at, None),
+            None => (FAILED, self.run_at, Some(now)),
+        };
+
+        let job = diesel::update(
+            jobs::table
+                .filter(jobs::id.eq(self.id))
+                .filter(jobs::status.eq(RUNNING))
+                .filter(jobs::attempts.eq(self.attempts)),
+        )
+        .set((
+            jobs::status.eq(status),
+            jobs::error.eq(Some(error)),
+            jobs::run_at.eq(run_at),
+            jobs::locked_until.eq(now),
+            jobs::finished_at.eq(finished_at),
+        ))
+        .get_result(&conn)
+        .optional()?;
+        Ok(job)
+    }
+}
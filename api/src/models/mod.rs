@@ -1,11 +1,27 @@
+mod alias;
 mod deployment;
 mod handler;
+mod job;
 mod project;
 mod route;
+mod static_blob;
+mod static_file;
+mod traffic;
+mod upload;
 mod user;
+mod webhook;
+mod webhook_delivery;
 
+pub use alias::{Alias, AliasMessage};
 pub use deployment::*;
 pub use handler::Handler;
+pub use job::Job;
 pub use project::{Project, ProjectMessage};
 pub use route::Route;
+pub use static_blob::StaticBlob;
+pub use static_file::StaticFile;
+pub use traffic::{Traffic, TrafficMessage};
+pub use upload::Upload;
 pub use user::{User, UserMessage};
+pub use webhook::{Webhook, WebhookMessage};
+pub use webhook_delivery::WebhookDelivery;
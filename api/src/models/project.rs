@@ -22,6 +22,10 @@ pub struct Project {
     pub description: String,
     pub created_at: NaiveDateTime,
     pub updated_at: Option<NaiveDateTime>,
+    /// Number of most recent deployments kept by garbage collection
+    pub retention_count: Option<i32>,
+    /// Number of days deployments are kept by garbage collection
+    pub retention_days: Option<i32>,
 }
 
 impl Project {
@@ -55,6 +59,8 @@ impl Project {
                 description: project.description,
                 created_at: Utc::now().naive_utc(),
                 updated_at: None,
+                retention_count: None,
+                retention_days: None,
             })
             .get_result(&conn)?;
         Ok(project)
@@ -74,6 +80,39 @@ impl Project {
         Ok(project)
     }
 
+    /// Retrieve all projects with a retention policy
+    pub fn find_with_retention() -> Result<Vec<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let results = projects::table
+            .filter(
+                projects::retention_count
+                    .is_not_null()
+                    .or(projects::retention_days.is_not_null()),
+            )
+            .load::<Project>(&conn)?;
+        Ok(results)
+    }
+
+    /// Set how long a project's deployments are kept, removing the policy
+    /// if neither limit is given
+    pub fn set_retention(
+        id: Uuid,
+        count: Option<i32>,
+        days: Option<i32>,
+    ) -> Result<Self, ApiError> {
+        let conn = database::connection()?;
+
+        let project = diesel::update(projects::table)
+            .filter(projects::id.eq(id))
+            .set((
+                projects::retention_count.eq(count),
+                projects::retention_days.eq(days),
+            ))
+            .get_result(&conn)?;
+        Ok(project)
+    }
+
     /// Delete a project
     pub fn delete(id: Uuid) -> Result<usize, ApiError> {
         let conn = database::connection()?;
@@ -28,29 +28,14 @@ impl Route {
         Ok(results)
     }
 
-    /// Create a route
-    pub fn create(route: RouteFormat, deployment_id: Uuid) -> Result<Self, ApiError> {
-        let conn = database::connection()?;
-
-        let route = diesel::insert_into(routes::table)
-            .values(Route {
-                id: Uuid::new_v4(),
-                path: route.path,
-                methods: route.methods,
-                handler: route.handler,
-                deployment_id,
-            })
-            .get_result(&conn)?;
-        Ok(route)
-    }
-
-    /// Delete a route
-    pub fn delete(id: Uuid) -> Result<usize, ApiError> {
-        let conn = database::connection()?;
-
-        let res = diesel::delete(routes::table)
-            .filter(routes::id.eq(id))
-            .execute(&conn)?;
-        Ok(res)
+    /// Build a route for a deployment from its configuration
+    pub fn new(route: RouteFormat, deployment_id: Uuid) -> Self {
+        Route {
+            id: Uuid::new_v4(),
+            path: route.path,
+            methods: route.methods,
+            handler: route.handler,
+            deployment_id,
+        }
     }
 }
@@ -0,0 +1,186 @@
+use crate::{database, errors::ApiError, models::Project, schema::static_blobs};
+use chrono::{NaiveDateTime, Utc};
+use diesel::pg::upsert::excluded;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// The contents of a static file, stored once per project and shared
+/// between all deployments which contain it
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Associations, Identifiable)]
+#[belongs_to(Project)]
+#[primary_key(project_id, hash)]
+#[table_name = "static_blobs"]
+pub struct StaticBlob {
+    pub project_id: Uuid,
+    pub hash: String,
+    pub size: i64,
+    pub content_type: String,
+    pub reference_count: i32,
+    pub created_at: NaiveDateTime,
+    /// Whether the contents have been stored, which may be after the blob is referenced
+    pub uploaded: bool,
+    /// Unreferenced blobs are kept until their lease expires
+    pub leased_until: Option<NaiveDateTime>,
+}
+
+impl StaticBlob {
+    /// Find a blob by its hash
+    pub fn find(project_id: Uuid, hash: &str) -> Result<Option<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let result = static_blobs::table.find((project_id, hash)).first(&conn);
+        match result {
+            Ok(r) => Ok(Some(r)),
+            Err(DieselError::NotFound) => Ok(None),
+            Err(e) => Err(ApiError::from(e)),
+        }
+    }
+
+    /// Retrieve all blobs which are no longer referenced by any deployment
+    /// and whose lease has expired
+    pub fn find_unreferenced(project_id: Uuid) -> Result<Vec<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let now = Utc::now().naive_utc();
+        let results = static_blobs::table
+            .filter(static_blobs::project_id.eq(project_id))
+            .filter(static_blobs::reference_count.le(0))
+            .filter(
+                static_blobs::leased_until
+                    .is_null()
+                    .or(static_blobs::leased_until.lt(now)),
+            )
+            .load::<StaticBlob>(&conn)?;
+        Ok(results)
+    }
+
+    /// Add a reference to a blob, creating it if it does not exist. The
+    /// contents must be uploaded unless the blob is marked as uploaded.
+    pub fn acquire(
+        project_id: Uuid,
+        hash: String,
+        size: i64,
+        content_type: String,
+    ) -> Result<Self, ApiError> {
+        let conn = database::connection()?;
+
+        let blob = diesel::insert_into(static_blobs::table)
+            .values(StaticBlob {
+                project_id,
+                hash,
+                size,
+                content_type,
+                reference_count: 1,
+                created_at: Utc::now().naive_utc(),
+                uploaded: false,
+                leased_until: None,
+            })
+            .on_conflict((static_blobs::project_id, static_blobs::hash))
+            .do_update()
+            .set(static_blobs::reference_count.eq(static_blobs::reference_count + 1))
+            .get_result(&conn)?;
+        Ok(blob)
+    }
+
+    /// Record a blob which is not yet referenced by a deployment, keeping it
+    /// until the lease expires. The contents must be uploaded unless the
+    /// blob is marked as uploaded.
+    pub fn register(
+        project_id: Uuid,
+        hash: String,
+        size: i64,
+        content_type: String,
+        leased_until: NaiveDateTime,
+    ) -> Result<Self, ApiError> {
+        let conn = database::connection()?;
+
+        let blob = diesel::insert_into(static_blobs::table)
+            .values(StaticBlob {
+                project_id,
+                hash,
+                size,
+                content_type,
+                reference_count: 0,
+                created_at: Utc::now().naive_utc(),
+                uploaded: false,
+                leased_until: Some(leased_until),
+            })
+            .on_conflict((static_blobs::project_id, static_blobs::hash))
+            .do_update()
+            .set(static_blobs::leased_until.eq(excluded(static_blobs::leased_until)))
+            .get_result(&conn)?;
+        Ok(blob)
+    }
+
+    /// Mark a blob's contents as stored
+    pub fn mark_uploaded(project_id: Uuid, hash: &str) -> Result<usize, ApiError> {
+        let conn = database::connection()?;
+
+        let res = diesel::update(static_blobs::table.find((project_id, hash)))
+            .set(static_blobs::uploaded.eq(true))
+            .execute(&conn)?;
+        Ok(res)
+    }
+
+    /// Add a reference to a blob which already exists
+    pub fn reference(project_id: Uuid, hash: &str) -> Result<Option<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let result = diesel::update(static_blobs::table.find((project_id, hash)))
+            .set(static_blobs::reference_count.eq(static_blobs::reference_count + 1))
+            .get_result(&conn);
+        match result {
+            Ok(r) => Ok(Some(r)),
+            Err(DieselError::NotFound) => Ok(None),
+            Err(e) => Err(ApiError::from(e)),
+        }
+    }
+
+    /// Remove a reference to a blob
+    pub fn release(project_id: Uuid, hash: &str) -> Result<usize, ApiError> {
+        let conn = database::connection()?;
+
+        let res = diesel::update(static_blobs::table.find((project_id, hash)))
+            .set(static_blobs::reference_count.eq(static_blobs::reference_count - 1))
+            .execute(&conn)?;
+        Ok(res)
+    }
+
+    /// Delete a blob if it is still unreferenced and unleased. The row is locked
+    /// while `cleanup` removes the contents so it cannot be re-acquired midway.
+    pub fn delete_unreferenced<F>(
+        project_id: Uuid,
+        hash: &str,
+        cleanup: F,
+    ) -> Result<bool, ApiError>
+    where
+        F: FnOnce() -> Result<(), ApiError>,
+    {
+        let conn = database::connection()?;
+
+        conn.transaction(|| {
+            let blob = static_blobs::table
+                .find((project_id, hash))
+                .filter(static_blobs::reference_count.le(0))
+                .filter(
+                    static_blobs::leased_until
+                        .is_null()
+                        .or(static_blobs::leased_until.lt(Utc::now().naive_utc())),
+                )
+                .for_update()
+                .first::<StaticBlob>(&conn);
+            match blob {
+                Ok(_) => {}
+                Err(DieselError::NotFound) => return Ok(false),
+                Err(e) => return Err(ApiError::from(e)),
+            }
+
+            cleanup()?;
+
+            diesel::delete(static_blobs::table.find((project_id, hash))).execute(&conn)?;
+            Ok(true)
+        })
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    database,
+    errors::ApiError,
+    models::{Deployment, StaticBlob},
+    schema::{deployments, static_blobs, static_files},
+};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+/// An entry in a deployment's static file manifest
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Associations, Identifiable)]
+#[belongs_to(Deployment)]
+#[table_name = "static_files"]
+pub struct StaticFile {
+    #[serde(skip_serializing)]
+    pub id: Uuid,
+    #[serde(skip_serializing)]
+    pub deployment_id: Uuid,
+    pub path: String,
+    pub hash: String,
+    pub size: i64,
+    pub content_type: String,
+}
+
+impl StaticFile {
+    /// Retrieve a deployment's manifest
+    pub fn find_all(deployment_id: Uuid) -> Result<Vec<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let results = static_files::table
+            .filter(static_files::deployment_id.eq(deployment_id))
+            .order(static_files::path.asc())
+            .load::<StaticFile>(&conn)?;
+        Ok(results)
+    }
+
+    /// Add a file to a deployment's manifest
+    pub fn create(
+        deployment_id: Uuid,
+        path: String,
+        hash: String,
+        size: i64,
+        content_type: String,
+    ) -> Result<Self, ApiError> {
+        let conn = database::connection()?;
+
+        let file = diesel::insert_into(static_files::table)
+            .values(StaticFile {
+                id: Uuid::new_v4(),
+                deployment_id,
+                path,
+                hash,
+                size,
+                content_type,
+            })
+            .get_result(&conn)?;
+        Ok(file)
+    }
+
+    /// Create a deployment's manifest from a mapping of paths to the hashes
+    /// of uploaded blobs and mark it as having static files. Nothing is
+    /// created if any of the blobs have not been uploaded.
+    pub fn create_all(
+        project_id: Uuid,
+        deployment_id: Uuid,
+        manifest: &BTreeMap<String, String>,
+    ) -> Result<(), ApiError> {
+        let conn = database::connection()?;
+
+        conn.transaction(|| {
+            create_manifest(&conn, project_id, deployment_id, manifest)?;
+            diesel::update(deployments::table.find(deployment_id))
+                .set(deployments::has_static.eq(true))
+                .execute(&conn)?;
+            Ok(())
+        })
+    }
+
+    /// Remove a deployment's manifest and release its references to blobs
+    pub fn release_all(project_id: Uuid, deployment_id: Uuid) -> Result<usize, ApiError> {
+        let conn = database::connection()?;
+
+        conn.transaction(|| {
+            let released = release_references(&conn, project_id, deployment_id)?;
+            diesel::delete(static_files::table)
+                .filter(static_files::deployment_id.eq(deployment_id))
+                .execute(&conn)?;
+            Ok(released)
+        })
+    }
+}
+
+/// Add each file in a manifest to a deployment, referencing its blob
+pub(super) fn create_manifest(
+    conn: &PgConnection,
+    project_id: Uuid,
+    deployment_id: Uuid,
+    manifest: &BTreeMap<String, String>,
+) -> Result<(), ApiError> {
+    for (path, hash) in manifest {
+        // The blob may have been removed since it was uploaded
+        let blob = diesel::update(
+            static_blobs::table
+                .find((project_id, hash))
+                .filter(static_blobs::uploaded.eq(true)),
+        )
+        .set(static_blobs::reference_count.eq(static_blobs::reference_count + 1))
+        .get_result::<StaticBlob>(conn);
+        let blob = match blob {
+            Ok(b) => b,
+            Err(DieselError::NotFound) => {
+                return Err(ApiError::new(
+                    409,
+                    format!("blob for static file '{}' has not been uploaded", path),
+                ))
+            }
+            Err(e) => return Err(ApiError::from(e)),
+        };
+
+        diesel::insert_into(static_files::table)
+            .values(StaticFile {
+                id: Uuid::new_v4(),
+                deployment_id,
+                path: path.clone(),
+                hash: hash.clone(),
+                size: blob.size,
+                content_type: blob.content_type,
+            })
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+/// Release the references a deployment's manifest holds to blobs
+pub(super) fn release_references(
+    conn: &PgConnection,
+    project_id: Uuid,
+    deployment_id: Uuid,
+) -> QueryResult<usize> {
+    let files = static_files::table
+        .filter(static_files::deployment_id.eq(deployment_id))
+        .load::<StaticFile>(conn)?;
+
+    // Count each blob's references so it is only updated once
+    let mut counts: HashMap<&str, i32> = HashMap::new();
+    for file in &files {
+        *counts.entry(file.hash.as_str()).or_insert(0) += 1;
+    }
+
+    for (hash, count) in counts {
+        diesel::update(static_blobs::table.find((project_id, hash)))
+            .set(static_blobs::reference_count.eq(static_blobs::reference_count - count))
+            .execute(conn)?;
+    }
+
+    Ok(files.len())
+}
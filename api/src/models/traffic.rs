@@ -0,0 +1,118 @@
+use crate::{database, errors::ApiError, models::Project, schema::traffic};
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize)]
+pub struct TrafficMessage {
+    /// Deployment receiving the remaining traffic, defaults to the current one
+    pub stable: Option<Uuid>,
+    pub candidate: Option<Uuid>,
+    /// Percentage of traffic sent to the candidate
+    #[serde(default)]
+    pub weight: i32,
+    /// Header identifying clients, otherwise a cookie is set
+    pub sticky_header: Option<String>,
+}
+
+/// How a project's unversioned traffic is split between deployments
+#[derive(
+    AsChangeset,
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    Queryable,
+    Insertable,
+    Associations,
+    Identifiable,
+)]
+#[belongs_to(Project)]
+#[primary_key(project_id)]
+#[changeset_options(treat_none_as_null = "true")]
+#[table_name = "traffic"]
+pub struct Traffic {
+    pub project_id: Uuid,
+    pub stable_id: Uuid,
+    pub candidate_id: Option<Uuid>,
+    pub weight: i32,
+    pub sticky_header: Option<String>,
+    pub updated_at: NaiveDateTime,
+}
+
+impl Traffic {
+    /// Find how a project's traffic is split, if it is
+    pub fn find(project_id: Uuid) -> Result<Option<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let traffic = traffic::table
+            .filter(traffic::project_id.eq(project_id))
+            .first(&conn)
+            .optional()?;
+        Ok(traffic)
+    }
+
+    /// Replace how a project's traffic is split
+    pub fn set(
+        project_id: Uuid,
+        stable_id: Uuid,
+        candidate_id: Option<Uuid>,
+        weight: i32,
+        sticky_header: Option<String>,
+    ) -> Result<Self, ApiError> {
+        let conn = database::connection()?;
+
+        let traffic = Traffic {
+            project_id,
+            stable_id,
+            candidate_id,
+            weight,
+            sticky_header,
+            updated_at: Utc::now().naive_utc(),
+        };
+        let traffic = diesel::insert_into(traffic::table)
+            .values(&traffic)
+            .on_conflict(traffic::project_id)
+            .do_update()
+            .set(&traffic)
+            .get_result(&conn)?;
+        Ok(traffic)
+    }
+
+    /// Send all traffic to the candidate
+    pub fn promote(&self) -> Result<Self, ApiError> {
+        let candidate_id = self
+            .candidate_id
+            .ok_or_else(|| ApiError::new(409, "project has no candidate deployment".to_string()))?;
+        Traffic::set(
+            self.project_id,
+            candidate_id,
+            None,
+            0,
+            self.sticky_header.clone(),
+        )
+    }
+
+    /// Send all traffic back to the stable deployment
+    pub fn abort(&self) -> Result<Self, ApiError> {
+        if self.candidate_id.is_none() {
+            return Err(ApiError::new(
+                409,
+                "project has no candidate deployment".to_string(),
+            ));
+        }
+        Traffic::set(
+            self.project_id,
+            self.stable_id,
+            None,
+            0,
+            self.sticky_header.clone(),
+        )
+    }
+
+    /// Whether a deployment receives any of the traffic
+    pub fn includes(&self, deployment_id: Uuid) -> bool {
+        self.stable_id == deployment_id || self.candidate_id == Some(deployment_id)
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{database, errors::ApiError, models::Deployment, schema::uploads};
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A resumable upload of a static file archive
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Associations, Identifiable)]
+#[belongs_to(Deployment)]
+#[table_name = "uploads"]
+pub struct Upload {
+    pub id: Uuid,
+    pub deployment_id: Uuid,
+    pub content_type: String,
+    pub length: i64,
+    pub received: i64,
+    pub created_at: NaiveDateTime,
+}
+
+impl Upload {
+    /// Get an upload by id
+    pub fn find(id: Uuid) -> Result<Self, ApiError> {
+        let conn = database::connection()?;
+
+        let upload = uploads::table.filter(uploads::id.eq(id)).first(&conn)?;
+        Ok(upload)
+    }
+
+    /// Retrieve all uploads for a deployment
+    pub fn find_all(deployment_id: Uuid) -> Result<Vec<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let results = uploads::table
+            .filter(uploads::deployment_id.eq(deployment_id))
+            .load::<Upload>(&conn)?;
+        Ok(results)
+    }
+
+    /// Retrieve all uploads started before a point in time
+    pub fn find_expired(before: NaiveDateTime) -> Result<Vec<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let results = uploads::table
+            .filter(uploads::created_at.lt(before))
+            .load::<Upload>(&conn)?;
+        Ok(results)
+    }
+
+    /// Find which of the given ids belong to uploads which still exist
+    pub fn find_existing(ids: Vec<Uuid>) -> Result<Vec<Uuid>, ApiError> {
+        let conn = database::connection()?;
+
+        let results = uploads::table
+            .filter(uploads::id.eq_any(ids))
+            .select(uploads::id)
+            .load::<Uuid>(&conn)?;
+        Ok(results)
+    }
+
+    /// Start a new upload
+    pub fn create(
+        deployment_id: Uuid,
+        content_type: String,
+        length: i64,
+    ) -> Result<Self, ApiError> {
+        let conn = database::connection()?;
+
+        let upload = diesel::insert_into(uploads::table)
+            .values(Upload {
+                id: Uuid::new_v4(),
+                deployment_id,
+                content_type,
+                length,
+                received: 0,
+                created_at: Utc::now().naive_utc(),
+            })
+            .get_result(&conn)?;
+        Ok(upload)
+    }
+
+    /// Append to an upload. The upload is locked while `write` stores the
+    /// data so concurrent requests cannot write to the same offset. Fails
+    /// if the upload has moved past `offset`.
+    pub fn append<F>(&self, offset: i64, size: i64, write: F) -> Result<i64, ApiError>
+    where
+        F: FnOnce() -> Result<(), ApiError>,
+    {
+        let conn = database::connection()?;
+
+        conn.transaction(|| {
+            let upload = uploads::table
+                .filter(uploads::id.eq(self.id))
+                .for_update()
+                .first::<Upload>(&conn)?;
+            if upload.received != offset {
+                return Err(ApiError::new(
+                    409,
+                    format!(
+                        "offset {} does not match current offset {}",
+                        offset, upload.received
+                    ),
+                ));
+            } else if upload.received + size > upload.length {
+                return Err(ApiError::new(
+                    413,
+                    "chunk exceeds declared upload length".to_string(),
+                ));
+            }
+
+            write()?;
+
+            diesel::update(uploads::table.filter(uploads::id.eq(self.id)))
+                .set(uploads::received.eq(upload.received + size))
+                .execute(&conn)?;
+            Ok(upload.received + size)
+        })
+    }
+
+    /// Whether all of the declared data has been received
+    pub fn is_complete(&self) -> bool {
+        self.received == self.length
+    }
+
+    /// Delete an upload
+    pub fn delete(id: Uuid) -> Result<usize, ApiError> {
+        let conn = database::connection()?;
+
+        let res = diesel::delete(uploads::table)
+            .filter(uploads::id.eq(id))
+            .execute(&conn)?;
+        Ok(res)
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{database, errors::ApiError, models::Project, schema::webhooks};
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize)]
+pub struct WebhookMessage {
+    pub url: String,
+    /// Events to deliver, or all events if empty
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// Key deliveries are signed with, generated if not provided
+    pub secret: Option<String>,
+}
+
+/// A subscription to a project's events
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Associations, Identifiable)]
+#[belongs_to(Project)]
+#[table_name = "webhooks"]
+pub struct Webhook {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl Webhook {
+    /// Retrieve all webhooks for a project
+    pub fn find_all(project_id: Uuid) -> Result<Vec<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let results = webhooks::table
+            .filter(webhooks::project_id.eq(project_id))
+            .order(webhooks::created_at.asc())
+            .load::<Webhook>(&conn)?;
+        Ok(results)
+    }
+
+    /// Find a webhook by id
+    pub fn find(id: Uuid) -> Result<Self, ApiError> {
+        let conn = database::connection()?;
+
+        let webhook = webhooks::table.filter(webhooks::id.eq(id)).first(&conn)?;
+        Ok(webhook)
+    }
+
+    /// Create a webhook for a project
+    pub fn create(
+        project_id: Uuid,
+        webhook: WebhookMessage,
+        secret: String,
+    ) -> Result<Self, ApiError> {
+        let conn = database::connection()?;
+
+        let webhook = diesel::insert_into(webhooks::table)
+            .values(Webhook {
+                id: Uuid::new_v4(),
+                project_id,
+                url: webhook.url,
+                events: webhook.events,
+                secret,
+                created_at: Utc::now().naive_utc(),
+            })
+            .get_result(&conn)?;
+        Ok(webhook)
+    }
+
+    /// Delete a webhook along with its delivery history
+    pub fn delete(id: Uuid) -> Result<usize, ApiError> {
+        let conn = database::connection()?;
+
+        let res = diesel::delete(webhooks::table)
+            .filter(webhooks::id.eq(id))
+            .execute(&conn)?;
+        Ok(res)
+    }
+
+    /// Whether the webhook should receive an event
+    pub fn subscribes_to(&self, event: &str) -> bool {
+        self.events.is_empty() || self.events.iter().any(|e| e == event)
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{database, errors::ApiError, models::Webhook, schema::webhook_deliveries};
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// The delivery is waiting to be sent or retried
+pub static PENDING: &str = "pending";
+/// The receiver accepted the delivery
+pub static SUCCEEDED: &str = "succeeded";
+/// The delivery was abandoned after too many attempts
+pub static FAILED: &str = "failed";
+
+/// A single event sent to a webhook, along with the result of the last attempt
+#[derive(
+    Clone, Debug, Serialize, Deserialize, Queryable, Insertable, Associations, Identifiable,
+)]
+#[belongs_to(Webhook)]
+#[table_name = "webhook_deliveries"]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: Option<NaiveDateTime>,
+    pub response_status: Option<i32>,
+    pub response_body: Option<String>,
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub completed_at: Option<NaiveDateTime>,
+}
+
+impl WebhookDelivery {
+    /// Find a delivery by id
+    pub fn find(id: Uuid) -> Result<Self, ApiError> {
+        let conn = database::connection()?;
+
+        let delivery = webhook_deliveries::table
+            .filter(webhook_deliveries::id.eq(id))
+            .first(&conn)?;
+        Ok(delivery)
+    }
+
+    /// Retrieve the most recent deliveries for a webhook, newest first
+    pub fn find_all(webhook_id: Uuid, limit: i64) -> Result<Vec<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        let results = webhook_deliveries::table
+            .filter(webhook_deliveries::webhook_id.eq(webhook_id))
+            .order(webhook_deliveries::created_at.desc())
+            .limit(limit)
+            .load::<WebhookDelivery>(&conn)?;
+        Ok(results)
+    }
+
+    /// Queue an event to be sent to a webhook immediately
+    pub fn create(
+        webhook_id: Uuid,
+        event: String,
+        payload: serde_json::Value,
+    ) -> Result<Self, ApiError> {
+        let conn = database::connection()?;
+
+        let now = Utc::now().naive_utc();
+        let delivery = diesel::insert_into(webhook_deliveries::table)
+            .values(WebhookDelivery {
+                id: Uuid::new_v4(),
+                webhook_id,
+                event,
+                payload,
+                status: PENDING.to_string(),
+                attempts: 0,
+                next_attempt_at: Some(now),
+                response_status: None,
+                response_body: None,
+                error: None,
+                created_at: now,
+                completed_at: None,
+            })
+            .get_result(&conn)?;
+        Ok(delivery)
+    }
+
+    /// Take the deliveries which are due to be sent. Each is leased by
+    /// pushing back its next attempt, so other workers skip it while it is
+    /// being sent and it is retried if the worker stops before recording
+    /// the result.
+    pub fn claim_due(limit: i64, lease: Duration) -> Result<Vec<Self>, ApiError> {
+        let conn = database::connection()?;
+
+        conn.transaction(|| {
+            let now = Utc::now().naive_utc();
+            let due = webhook_deliveries::table
+                .filter(webhook_deliveries::status.eq(PENDING))
+                .filter(webhook_deliveries::next_attempt_at.le(now))
+                .order(webhook_deliveries::next_attempt_at.asc())
+                .limit(limit)
+                .for_update()
+                .skip_locked()
+                .load::<WebhookDelivery>(&conn)?;
+
+            let ids: Vec<Uuid> = due.iter().map(|d| d.id).collect();
+            diesel::update(webhook_deliveries::table.filter(webhook_deliveries::id.eq_any(ids)))
+                .set(webhook_deliveries::next_attempt_at.eq(now + lease))
+                .execute(&conn)?;
+            Ok(due)
+        })
+    }
+
+    /// Record the result of an attempt. The delivery is retried at
+    /// `retry_at` if it did not succeed, or marked as failed if there is
+    /// no retry.
+    pub fn record_attempt(
+        &self,
+        succeeded: bool,
+        response_status: Option<i32>,
+        response_body: Option<String>,
+        error: Option<String>,
+        retry_at: Option<NaiveDateTime>,
+    ) -> Result<Self, ApiError> {
+        let conn = database::connection()?;
+
+        let now = Utc::now().naive_utc();
+        let (status, next_attempt_at, completed_at) = match (succeeded, retry_at) {
+            (true, _) => (SUCCEEDED, None, Some(now)),
+            (false, Some(at)) => (PENDING, Some(at), None),
+            (false, None) => (FAILED, None, Some(now)),
+        };
+
+        let delivery = diesel::update(webhook_deliveries::table)
+            .filter(webhook_deliveries::id.eq(self.id))
+            .set((
+                webhook_deliveries::status.eq(status),
+                webhook_deliveries::attempts.eq(self.attempts + 1),
+                webhook_deliveries::next_attempt_at.eq(next_attempt_at),
+                webhook_deliveries::response_status.eq(response_status),
+                webhook_deliveries::response_body.eq(response_body),
+                webhook_deliveries::error.eq(error),
+                webhook_deliveries::completed_at.eq(completed_at),
+            ))
+            .get_result(&conn)?;
+        Ok(delivery)
+    }
+}
@@ -0,0 +1,711 @@
+use crate::{
+    errors::ApiError,
+    models::{Deployment, Handler, Project, Route},
+    project_format::{Handler as HandlerFormat, ProjectFormat, Route as RouteFormat},
+};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+
+// HTTP methods which can be described by a path item
+static METHODS: &[&str] = &[
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+// Maximum number of `$ref`s followed from a single value before giving up
+static MAX_REFERENCE_DEPTH: usize = 16;
+
+// Starlette path convertors and their OpenAPI equivalents
+static CONVERTORS: &[(&str, &str, Option<&str>)] = &[
+    ("str", "string", None),
+    ("path", "string", None),
+    ("int", "integer", None),
+    ("float", "number", None),
+    ("uuid", "string", Some("uuid")),
+];
+
+/// A path parameter extracted from a route's path
+#[derive(Debug, PartialEq)]
+pub struct PathParameter {
+    pub name: String,
+    pub convertor: String,
+}
+
+/// Generate an OpenAPI 3 document describing a deployment
+pub fn generate(
+    project: &Project,
+    deployment: &Deployment,
+    routes: &[Route],
+    handlers: &[Handler],
+) -> Value {
+    let handlers_by_name: HashMap<&str, &Handler> =
+        handlers.iter().map(|h| (h.name.as_str(), h)).collect();
+
+    let mut paths = Map::new();
+    for route in routes {
+        // Routes without a matching handler are never served by the runtime
+        let handler = match handlers_by_name.get(route.handler.as_str()) {
+            Some(h) => h,
+            None => continue,
+        };
+
+        let (path, parameters) = parse_path(&route.path);
+        let item = paths
+            .entry(path)
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .unwrap();
+
+        for method in &route.methods {
+            let method = method.to_lowercase();
+            let operation_id = if route.methods.len() > 1 {
+                format!("{}_{}", handler.name, method)
+            } else {
+                handler.name.clone()
+            };
+            let operation = operation(operation_id, &method, &parameters, handler);
+            item.insert(method, operation);
+        }
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": project.name,
+            "description": project.description,
+            "version": deployment.version,
+        },
+        "servers": [{ "url": format!("/{}/{}", project.name, deployment.version) }],
+        "paths": paths,
+    })
+}
+
+/// Convert a Starlette route path into an OpenAPI path template and its parameters.
+/// Both `{int:id}` and `{id:int}` forms are accepted, defaulting to a string.
+pub fn parse_path(path: &str) -> (String, Vec<PathParameter>) {
+    let mut template = String::with_capacity(path.len());
+    let mut parameters = Vec::new();
+
+    let mut rest = path;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(e) => start + e,
+            None => break,
+        };
+        template.push_str(&rest[..start]);
+
+        let inner = &rest[start + 1..end];
+        let parameter = match inner.find(':') {
+            Some(i) if is_convertor(&inner[..i]) => PathParameter {
+                name: inner[i + 1..].to_string(),
+                convertor: inner[..i].to_string(),
+            },
+            Some(i) => PathParameter {
+                name: inner[..i].to_string(),
+                convertor: inner[i + 1..].to_string(),
+            },
+            None => PathParameter {
+                name: inner.to_string(),
+                convertor: "str".to_string(),
+            },
+        };
+
+        template.push('{');
+        template.push_str(&parameter.name);
+        template.push('}');
+        parameters.push(parameter);
+
+        rest = &rest[end + 1..];
+    }
+    template.push_str(rest);
+
+    (template, parameters)
+}
+
+/// Get the JSON schema for a path convertor
+pub fn convertor_schema(convertor: &str) -> Value {
+    match CONVERTORS.iter().find(|(name, _, _)| *name == convertor) {
+        Some((_, kind, Some(format))) => json!({ "type": kind, "format": format }),
+        Some((_, kind, None)) => json!({ "type": kind }),
+        None => json!({ "type": "string" }),
+    }
+}
+
+fn is_convertor(name: &str) -> bool {
+    CONVERTORS.iter().any(|(n, _, _)| *n == name)
+}
+
+/// Build the operation object for a single method of a route
+fn operation(
+    operation_id: String,
+    method: &str,
+    path_parameters: &[PathParameter],
+    handler: &Handler,
+) -> Value {
+    let mut parameters = Vec::new();
+    for parameter in path_parameters {
+        parameters.push(json!({
+            "name": parameter.name,
+            "in": "path",
+            "required": true,
+            "schema": convertor_schema(&parameter.convertor),
+        }));
+    }
+    for name in handler.query_parameters.iter().flatten() {
+        parameters.push(json!({ "name": name, "in": "query", "schema": { "type": "string" } }));
+    }
+    for name in handler.headers.iter().flatten() {
+        parameters.push(json!({ "name": name, "in": "header", "schema": { "type": "string" } }));
+    }
+
+    let mut operation = json!({
+        "operationId": operation_id,
+        "parameters": parameters,
+        "responses": responses(&handler.logic),
+    });
+
+    if let Some(body) = request_body(method, &handler.body) {
+        operation["requestBody"] = json!({
+            "required": true,
+            "content": { "application/json": { "schema": body_schema(body) } },
+        });
+    }
+
+    operation
+}
+
+/// Get a handler's body definition if a request using the method can send one.
+/// Bodies without any fields are treated as if there were no body at all.
+pub fn request_body<'a>(method: &str, body: &'a Option<Value>) -> Option<&'a Value> {
+    if method.eq_ignore_ascii_case("get") || method.eq_ignore_ascii_case("head") {
+        return None;
+    }
+
+    body.as_ref().filter(|b| {
+        b.get("fields")
+            .and_then(Value::as_object)
+            .map_or(false, |f| !f.is_empty())
+    })
+}
+
+/// Convert a handler's body definition into a JSON schema object
+pub fn body_schema(body: &Value) -> Value {
+    let mut schema = json!({ "type": "object" });
+    if let Some(fields) = body.get("fields") {
+        schema["properties"] = fields.clone();
+    }
+    if let Some(required) = body.get("required") {
+        if required.as_array().map_or(false, |r| !r.is_empty()) {
+            schema["required"] = required.clone();
+        }
+    }
+    schema
+}
+
+/// Derive the possible responses from the `return` statements in a handler's logic
+fn responses(logic: &Value) -> Value {
+    let mut returns = Vec::new();
+    collect_returns(logic, &mut returns);
+
+    let mut responses = Map::new();
+    for (status, data_type) in returns {
+        let content_type = match data_type.as_str() {
+            "text" => "text/plain",
+            _ => "application/json",
+        };
+        responses.entry(status.to_string()).or_insert_with(|| {
+            json!({
+                "description": format!("{} response", status),
+                "content": { content_type: {} },
+            })
+        });
+    }
+
+    // Handlers are also able to fail on invalid input
+    responses
+        .entry("400".to_string())
+        .or_insert_with(|| json!({ "description": "invalid request" }));
+
+    Value::Object(responses)
+}
+
+fn collect_returns(logic: &Value, returns: &mut Vec<(u64, String)>) {
+    let statements = match logic.as_array() {
+        Some(s) => s,
+        None => return,
+    };
+
+    for statement in statements {
+        match statement.get("action").and_then(Value::as_str) {
+            Some("return") => {
+                let status = statement
+                    .get("status")
+                    .and_then(Value::as_u64)
+                    .unwrap_or(200);
+                let data_type = statement
+                    .get("data_type")
+                    .and_then(Value::as_str)
+                    .unwrap_or("json")
+                    .to_string();
+                returns.push((status, data_type));
+            }
+            Some("if") => {
+                collect_returns(&statement["true"], returns);
+                collect_returns(&statement["false"], returns);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Translate an OpenAPI 3 document into a project skeleton. Every operation
+/// becomes a route with a stub handler that returns 501 Not Implemented.
+pub fn import(spec: &Value, name: &str) -> Result<ProjectFormat, ApiError> {
+    let paths = spec
+        .get("paths")
+        .and_then(Value::as_object)
+        .ok_or_else(|| ApiError::new(400, "field 'paths' must be an object".to_string()))?;
+
+    let mut format = ProjectFormat {
+        name: name.to_string(),
+        version: spec["info"]["version"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        static_directory: "./static".to_string(),
+        routes: vec![],
+        handlers: vec![],
+    };
+
+    let mut names = HashSet::new();
+    for (path, item) in paths {
+        let item = resolve(spec, item)?;
+        let shared = item.get("parameters").and_then(Value::as_array);
+
+        for method in METHODS {
+            let operation = match item.get(*method) {
+                Some(o) => resolve(spec, o)?,
+                None => continue,
+            };
+
+            let mut parameters = Vec::new();
+            for parameter in shared
+                .into_iter()
+                .chain(operation.get("parameters").and_then(Value::as_array))
+                .flatten()
+            {
+                parameters.push(resolve(spec, parameter)?);
+            }
+
+            let handler_name = match operation.get("operationId").and_then(Value::as_str) {
+                Some(id) => id.to_string(),
+                None => operation_name(method, path),
+            };
+            if !names.insert(handler_name.clone()) {
+                return Err(ApiError::new(
+                    400,
+                    format!("operation '{}' is defined more than once", handler_name),
+                ));
+            }
+
+            format.routes.push(RouteFormat {
+                path: import_path(path, &parameters),
+                methods: vec![method.to_uppercase()],
+                handler: handler_name.clone(),
+            });
+            format.handlers.push(HandlerFormat {
+                name: handler_name,
+                query_parameters: parameters_in(&parameters, "query"),
+                headers: parameters_in(&parameters, "header"),
+                path_parameters: parameters_in(&parameters, "path"),
+                body: import_body(spec, operation)?,
+                logic: json!([{
+                    "action": "return",
+                    "data_type": "json",
+                    "status": 501,
+                    "value": { "success": false, "reason": "not implemented" },
+                }]),
+            });
+        }
+    }
+
+    if format.routes.is_empty() {
+        return Err(ApiError::new(
+            400,
+            "specification does not define any operations".to_string(),
+        ));
+    }
+
+    Ok(format)
+}
+
+/// Follow a local `$ref` to the object it points at
+fn resolve<'a>(spec: &'a Value, value: &'a Value) -> Result<&'a Value, ApiError> {
+    follow(spec, value, &mut Vec::new())
+}
+
+/// Follow a chain of local `$ref`s, recording each one in `visited` so
+/// references which lead back to themselves are rejected
+fn follow<'a>(
+    spec: &'a Value,
+    mut value: &'a Value,
+    visited: &mut Vec<String>,
+) -> Result<&'a Value, ApiError> {
+    while let Some(reference) = value.get("$ref").and_then(Value::as_str) {
+        if !reference.starts_with("#/") {
+            return Err(ApiError::new(
+                400,
+                format!("only local references are supported, got '{}'", reference),
+            ));
+        }
+        if visited.iter().any(|v| v == reference) {
+            return Err(ApiError::new(
+                400,
+                format!("circular reference to '{}'", reference),
+            ));
+        }
+        if visited.len() >= MAX_REFERENCE_DEPTH {
+            return Err(ApiError::new(
+                400,
+                "maximum reference depth exceeded".to_string(),
+            ));
+        }
+
+        value = spec.pointer(&reference[1..]).ok_or_else(|| {
+            ApiError::new(400, format!("reference '{}' does not exist", reference))
+        })?;
+        visited.push(reference.to_string());
+    }
+
+    Ok(value)
+}
+
+/// Recursively inline local `$ref`s within a schema, where `visited` holds
+/// the references which were followed to reach the value
+fn inline(spec: &Value, value: &Value, visited: &mut Vec<String>) -> Result<Value, ApiError> {
+    let followed = visited.len();
+    let result = match follow(spec, value, visited)? {
+        Value::Object(object) => {
+            let mut result = Map::new();
+            for (key, value) in object {
+                result.insert(key.clone(), inline(spec, value, visited)?);
+            }
+            Value::Object(result)
+        }
+        Value::Array(array) => {
+            let mut result = Vec::with_capacity(array.len());
+            for value in array {
+                result.push(inline(spec, value, visited)?);
+            }
+            Value::Array(result)
+        }
+        value => value.clone(),
+    };
+
+    visited.truncate(followed);
+    Ok(result)
+}
+
+/// Convert an OpenAPI path template into a Starlette path with typed parameters
+fn import_path(path: &str, parameters: &[&Value]) -> String {
+    let (_, path_parameters) = parse_path(path);
+
+    let mut result = path.to_string();
+    for parameter in path_parameters {
+        let schema = parameters
+            .iter()
+            .find(|p| p["in"] == "path" && p["name"] == parameter.name.as_str())
+            .map(|p| &p["schema"]);
+        let convertor = match schema {
+            Some(s) if s["type"] == "integer" => "int",
+            Some(s) if s["type"] == "number" => "float",
+            Some(s) if s["format"] == "uuid" => "uuid",
+            _ => continue,
+        };
+
+        result = result.replace(
+            &format!("{{{}}}", parameter.name),
+            &format!("{{{}:{}}}", convertor, parameter.name),
+        );
+    }
+
+    result
+}
+
+/// Get the names of all parameters in a given location
+fn parameters_in(parameters: &[&Value], location: &str) -> Option<Vec<String>> {
+    let names: Vec<String> = parameters
+        .iter()
+        .filter(|p| p["in"] == location)
+        .filter_map(|p| p["name"].as_str().map(str::to_string))
+        .collect();
+    if names.is_empty() {
+        None
+    } else {
+        Some(names)
+    }
+}
+
+/// Convert an operation's JSON request body into a handler body definition
+fn import_body(spec: &Value, operation: &Value) -> Result<Option<Value>, ApiError> {
+    let body = match operation.get("requestBody") {
+        Some(b) => resolve(spec, b)?,
+        None => return Ok(None),
+    };
+    let schema = match body.pointer("/content/application~1json/schema") {
+        Some(s) => inline(spec, s, &mut Vec::new())?,
+        None => return Ok(None),
+    };
+
+    let mut definition = json!({
+        "fields": schema.get("properties").cloned().unwrap_or_else(|| json!({})),
+    });
+    if let Some(required) = schema.get("required") {
+        definition["required"] = required.clone();
+    }
+    Ok(Some(definition))
+}
+
+/// Generate a handler name for an operation without an `operationId`
+fn operation_name(method: &str, path: &str) -> String {
+    let segments: Vec<&str> = path
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if segments.is_empty() {
+        method.to_string()
+    } else {
+        format!("{}_{}", method, segments.join("_"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(paths: Value, components: Value) -> Value {
+        json!({
+            "openapi": "3.0.3",
+            "info": { "title": "test", "version": "1.0.0" },
+            "paths": paths,
+            "components": components,
+        })
+    }
+
+    #[test]
+    fn parses_both_convertor_forms() {
+        let (template, parameters) = parse_path("/users/{int:id}/posts/{slug:str}/{rest}");
+        assert_eq!(template, "/users/{id}/posts/{slug}/{rest}");
+        assert_eq!(
+            parameters,
+            vec![
+                PathParameter {
+                    name: "id".to_string(),
+                    convertor: "int".to_string(),
+                },
+                PathParameter {
+                    name: "slug".to_string(),
+                    convertor: "str".to_string(),
+                },
+                PathParameter {
+                    name: "rest".to_string(),
+                    convertor: "str".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_unterminated_parameters() {
+        let (template, parameters) = parse_path("/users/{id");
+        assert_eq!(template, "/users/{id");
+        assert!(parameters.is_empty());
+    }
+
+    #[test]
+    fn ignores_empty_bodies() {
+        let empty = Some(json!({ "fields": {} }));
+        assert_eq!(request_body("post", &empty), None);
+        assert_eq!(request_body("post", &Some(json!({}))), None);
+        assert_eq!(request_body("post", &None), None);
+    }
+
+    #[test]
+    fn ignores_bodies_of_get_and_head() {
+        let body = Some(json!({ "fields": { "name": { "type": "string" } } }));
+        assert_eq!(request_body("GET", &body), None);
+        assert_eq!(request_body("head", &body), None);
+        assert_eq!(request_body("post", &body), body.as_ref());
+    }
+
+    #[test]
+    fn omits_empty_required_list() {
+        let schema = body_schema(&json!({ "fields": { "a": {} }, "required": [] }));
+        assert_eq!(
+            schema,
+            json!({ "type": "object", "properties": { "a": {} } })
+        );
+    }
+
+    #[test]
+    fn imports_operations_with_references() {
+        let spec = spec(
+            json!({
+                "/users/{id}": {
+                    "parameters": [{ "$ref": "#/components/parameters/id" }],
+                    "put": {
+                        "requestBody": { "$ref": "#/components/requestBodies/user" },
+                    },
+                },
+            }),
+            json!({
+                "parameters": {
+                    "id": { "name": "id", "in": "path", "schema": { "type": "integer" } },
+                },
+                "requestBodies": {
+                    "user": {
+                        "content": {
+                            "application/json": {
+                                "schema": { "$ref": "#/components/schemas/user" },
+                            },
+                        },
+                    },
+                },
+                "schemas": {
+                    "user": {
+                        "type": "object",
+                        "properties": { "name": { "$ref": "#/components/schemas/name" } },
+                        "required": ["name"],
+                    },
+                    "name": { "type": "string" },
+                },
+            }),
+        );
+
+        let format = import(&spec, "test").unwrap();
+        assert_eq!(format.version, "1.0.0");
+        assert_eq!(format.routes.len(), 1);
+        assert_eq!(format.routes[0].path, "/users/{int:id}");
+        assert_eq!(format.routes[0].handler, "put_users_id");
+        assert_eq!(
+            format.handlers[0].path_parameters,
+            Some(vec!["id".to_string()])
+        );
+        assert_eq!(
+            format.handlers[0].body,
+            Some(json!({
+                "fields": { "name": { "type": "string" } },
+                "required": ["name"],
+            }))
+        );
+    }
+
+    #[test]
+    fn rejects_reference_cycles() {
+        let spec = spec(
+            json!({ "/": { "get": { "$ref": "#/components/a" } } }),
+            json!({ "a": { "$ref": "#/components/b" }, "b": { "$ref": "#/components/a" } }),
+        );
+        let error = import(&spec, "test").unwrap_err();
+        assert_eq!(error.status_code, 400);
+        assert_eq!(error.message, "circular reference to '#/components/a'");
+    }
+
+    #[test]
+    fn rejects_recursive_schemas() {
+        let spec = spec(
+            json!({
+                "/": {
+                    "post": {
+                        "requestBody": {
+                            "content": {
+                                "application/json": { "schema": { "$ref": "#/components/node" } },
+                            },
+                        },
+                    },
+                },
+            }),
+            json!({
+                "node": {
+                    "type": "object",
+                    "properties": { "children": { "items": { "$ref": "#/components/node" } } },
+                },
+            }),
+        );
+        let error = import(&spec, "test").unwrap_err();
+        assert_eq!(error.message, "circular reference to '#/components/node'");
+    }
+
+    #[test]
+    fn rejects_long_reference_chains() {
+        let mut components = Map::new();
+        for i in 0..MAX_REFERENCE_DEPTH {
+            components.insert(
+                i.to_string(),
+                json!({ "$ref": format!("#/components/{}", i + 1) }),
+            );
+        }
+        components.insert(MAX_REFERENCE_DEPTH.to_string(), json!({}));
+        let spec = spec(
+            json!({ "/": { "get": { "$ref": "#/components/0" } } }),
+            Value::Object(components),
+        );
+        let error = import(&spec, "test").unwrap_err();
+        assert_eq!(error.message, "maximum reference depth exceeded");
+    }
+
+    #[test]
+    fn imports_deeply_nested_schemas() {
+        let mut schema = json!({ "type": "string" });
+        for _ in 0..4 * MAX_REFERENCE_DEPTH {
+            schema = json!({ "type": "object", "properties": { "a": schema } });
+        }
+        let spec = spec(
+            json!({
+                "/": {
+                    "post": {
+                        "requestBody": { "content": { "application/json": { "schema": schema } } },
+                    },
+                },
+            }),
+            json!({}),
+        );
+        assert!(import(&spec, "test").unwrap().handlers[0].body.is_some());
+    }
+
+    #[test]
+    fn rejects_duplicate_operation_ids() {
+        let spec = spec(
+            json!({
+                "/a": { "get": { "operationId": "list" } },
+                "/b": { "get": { "operationId": "list" } },
+            }),
+            json!({}),
+        );
+        let error = import(&spec, "test").unwrap_err();
+        assert_eq!(error.message, "operation 'list' is defined more than once");
+    }
+
+    #[test]
+    fn rejects_remote_and_missing_references() {
+        let remote = spec(json!({ "/": { "$ref": "other.yaml#/a" } }), json!({}));
+        assert_eq!(import(&remote, "test").unwrap_err().status_code, 400);
+
+        let missing = spec(json!({ "/": { "$ref": "#/components/a" } }), json!({}));
+        assert_eq!(import(&missing, "test").unwrap_err().status_code, 400);
+    }
+
+    #[test]
+    fn rejects_specifications_without_operations() {
+        let spec = spec(json!({ "/": {} }), json!({}));
+        assert_eq!(import(&spec, "test").unwrap_err().status_code, 400);
+    }
+
+    #[test]
+    fn names_operations_from_paths() {
+        assert_eq!(operation_name("get", "/"), "get");
+        assert_eq!(
+            operation_name("post", "/users/{id}/posts"),
+            "post_users_id_posts"
+        );
+    }
+}
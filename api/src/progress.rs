@@ -0,0 +1,400 @@
+//! Progress of a deployment, streamed to clients as Server-Sent Events.
+//!
+//! Progress is recorded in a redis stream per deployment so any instance of
+//! the API can serve it, and clients which reconnect with `Last-Event-ID`
+//! receive what they missed. Each message has one of the following events:
+//!
+//! | Event        | Data                                                        |
+//! |--------------|-------------------------------------------------------------|
+//! | `validation` | `{"status": "started" \| "passed" \| "failed", "message"}`  |
+//! | `upload`     | `{"path", "uploaded", "total"}` after each static file      |
+//! | `state`      | `{"state": "created" \| "uploading" \| "ready" \| "failed" \| "deleted", "message"}` |
+//! | `rollout`    | The deployment's rollout status whenever it changes         |
+//!
+//! `rollout` events are computed from the runtime registry while the client
+//! is connected, so they have no id and are not replayed.
+//!
+//! Each instance of the API reads the progress of every deployment its
+//! clients are subscribed to on a single thread, blocking on redis until a
+//! message arrives, and forwards messages to each client.
+
+use crate::{config::CFG, errors::ApiError, runtimes::Rollout};
+use actix_web::web::Bytes;
+use futures::{
+    channel::mpsc::{self as channel, UnboundedSender},
+    stream, Stream, StreamExt,
+};
+use redis::{
+    streams::{StreamMaxlen, StreamReadOptions, StreamReadReply},
+    Commands,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+// Approximate number of messages kept for each deployment
+const MAX_LENGTH: usize = 1000;
+
+// Seconds progress is kept after the last message
+const EXPIRY: usize = 24 * 60 * 60;
+
+// Milliseconds to wait for new messages before checking for new clients
+const BLOCK_TIME: usize = 1000;
+
+// Messages read for each deployment at once
+const BATCH_SIZE: usize = 100;
+
+// Time between checks of the rollout status
+const ROLLOUT_INTERVAL: Duration = Duration::from_secs(2);
+
+// Time without a message before a comment is sent to keep the connection open
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+// Time to wait before reconnecting after redis fails
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+lazy_static! {
+    // New subscriptions for the reader shared by every client of this instance
+    static ref SUBSCRIPTIONS: Mutex<mpsc::Sender<Subscription>> = {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || Reader::new(receiver).run());
+        Mutex::new(sender)
+    };
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "lowercase")]
+pub enum Progress {
+    Validation {
+        status: Validation,
+        message: Option<String>,
+    },
+    Upload {
+        path: String,
+        uploaded: usize,
+        total: usize,
+    },
+    State {
+        state: State,
+        message: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Validation {
+    Started,
+    Passed,
+    Failed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    Created,
+    Uploading,
+    Ready,
+    Failed,
+    Deleted,
+}
+
+impl Progress {
+    /// A state transition without a message
+    pub fn state(state: State) -> Self {
+        Progress::State {
+            state,
+            message: None,
+        }
+    }
+}
+
+/// Record progress for a deployment. Failures are only logged since
+/// progress is informational and must not fail the deployment.
+pub fn emit(deployment_id: Uuid, progress: Progress) {
+    if let Err(e) = append(deployment_id, &progress) {
+        warn!(
+            "failed to record progress for deployment {}: {}",
+            deployment_id, e
+        );
+    }
+}
+
+fn append(deployment_id: Uuid, progress: &Progress) -> Result<(), ApiError> {
+    let value = serde_json::to_value(progress)
+        .map_err(|e| ApiError::new(500, format!("failed to encode progress: {}", e)))?;
+    let event = value["event"].as_str().unwrap_or_default().to_string();
+    let data = value["data"].to_string();
+
+    let key = key(deployment_id);
+    let mut connection = crate::redis::connection()?;
+    let _id: String = connection.xadd_maxlen(
+        &key,
+        StreamMaxlen::Approx(MAX_LENGTH),
+        "*",
+        &[("event", event), ("data", data)],
+    )?;
+    connection.expire::<_, ()>(&key, EXPIRY)?;
+    Ok(())
+}
+
+/// Whether a string is a valid id to resume a subscription after
+pub fn is_valid_id(id: &str) -> bool {
+    let mut parts = id.splitn(2, '-');
+    let numeric = |p: &str| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit());
+    parts.next().map_or(false, numeric) && parts.next().map_or(true, numeric)
+}
+
+/// Stream the progress of a deployment as Server-Sent Events, starting
+/// after the message with the given id. The stream ends once the
+/// deployment is deleted.
+pub fn subscribe(
+    deployment_id: Uuid,
+    last_id: String,
+) -> impl Stream<Item = Result<Bytes, ApiError>> {
+    let (sender, receiver) = channel::unbounded();
+    let subscription = Subscription {
+        deployment_id,
+        last_id: parse_id(&last_id),
+        rollout: None,
+        sender,
+    };
+
+    // The stream ends immediately if the reader is gone
+    let registered = SUBSCRIPTIONS
+        .lock()
+        .map_err(|_| ())
+        .and_then(|s| s.send(subscription).map_err(|_| ()));
+    if registered.is_err() {
+        error!(
+            "failed to subscribe to progress of deployment {}",
+            deployment_id
+        );
+    }
+
+    stream::unfold((receiver, false), |(mut receiver, done)| async move {
+        if done {
+            return None;
+        }
+
+        match actix_rt::time::timeout(KEEP_ALIVE_INTERVAL, receiver.next()).await {
+            Ok(Some(message)) => Some((Ok(message.data), (receiver, message.last))),
+            Ok(None) => None,
+            Err(_) => Some((
+                Ok(Bytes::from_static(b": keep-alive\n\n")),
+                (receiver, false),
+            )),
+        }
+    })
+}
+
+/// A message in the event stream format, marking whether it is the last
+struct Message {
+    data: Bytes,
+    last: bool,
+}
+
+struct Subscription {
+    deployment_id: Uuid,
+    last_id: (u64, u64),
+    rollout: Option<String>,
+    sender: UnboundedSender<Message>,
+}
+
+impl Subscription {
+    /// Send a message to the client, which is forgotten once it disconnects
+    fn send(&mut self, id: Option<&str>, event: &str, data: &str, last: bool) {
+        let mut message = String::new();
+        if let Some(id) = id {
+            message.push_str(&format!("id: {}\n", id));
+        }
+        message.push_str(&format!("event: {}\ndata: {}\n\n", event, data));
+
+        let message = Message {
+            data: Bytes::from(message),
+            last,
+        };
+        let _ = self.sender.unbounded_send(message);
+    }
+
+    /// Send the rollout status if it changed since it was last sent
+    fn send_rollout(&mut self, rollout: &str) {
+        if self.rollout.as_deref() != Some(rollout) {
+            self.send(None, "rollout", rollout, false);
+            self.rollout = Some(rollout.to_string());
+        }
+    }
+}
+
+/// Reads the progress of every subscribed deployment on a dedicated
+/// connection, so clients don't each need a thread and connection
+struct Reader {
+    receiver: mpsc::Receiver<Subscription>,
+    subscriptions: HashMap<Uuid, Vec<Subscription>>,
+    connection: Option<redis::Connection>,
+    rollout_checked: Instant,
+}
+
+impl Reader {
+    fn new(receiver: mpsc::Receiver<Subscription>) -> Self {
+        Reader {
+            receiver,
+            subscriptions: HashMap::new(),
+            connection: None,
+            rollout_checked: Instant::now(),
+        }
+    }
+
+    /// Forward messages to subscribers until every sender of subscriptions is gone
+    fn run(mut self) {
+        loop {
+            // Wait for a client rather than reading nothing
+            if self.subscriptions.is_empty() {
+                match self.receiver.recv() {
+                    Ok(s) => self.add(s),
+                    Err(_) => return,
+                }
+            }
+            while let Ok(s) = self.receiver.try_recv() {
+                self.add(s);
+            }
+
+            if let Err(e) = self.read() {
+                warn!("failed to read deployment progress: {}", e);
+                self.connection = None;
+                std::thread::sleep(RETRY_DELAY);
+            }
+
+            // Forget clients which disconnected
+            for subscriptions in self.subscriptions.values_mut() {
+                subscriptions.retain(|s| !s.sender.is_closed());
+            }
+            self.subscriptions.retain(|_, s| !s.is_empty());
+        }
+    }
+
+    fn add(&mut self, mut subscription: Subscription) {
+        // Clients get the rollout status as soon as they connect
+        match rollout(subscription.deployment_id) {
+            Ok(r) => subscription.send_rollout(&r),
+            Err(e) => warn!("failed to get rollout status: {}", e),
+        }
+
+        self.subscriptions
+            .entry(subscription.deployment_id)
+            .or_insert_with(Vec::new)
+            .push(subscription);
+    }
+
+    /// Wait for new messages for any subscribed deployment and send them to
+    /// each client which hasn't received them yet
+    fn read(&mut self) -> Result<(), ApiError> {
+        let mut deployments = Vec::with_capacity(self.subscriptions.len());
+        let mut keys = Vec::with_capacity(self.subscriptions.len());
+        let mut ids = Vec::with_capacity(self.subscriptions.len());
+        for (deployment_id, subscriptions) in &self.subscriptions {
+            // Read from the client furthest behind, others skip what they already have
+            let (ms, seq) = subscriptions
+                .iter()
+                .map(|s| s.last_id)
+                .min()
+                .unwrap_or((0, 0));
+            deployments.push(*deployment_id);
+            keys.push(key(*deployment_id));
+            ids.push(format!("{}-{}", ms, seq));
+        }
+
+        if self.connection.is_none() {
+            let client = redis::Client::open(CFG.redis.address.as_str())?;
+            self.connection = Some(client.get_connection()?);
+        }
+        let connection = self.connection.as_mut().unwrap();
+
+        let reply: Option<StreamReadReply> = connection.xread_options(
+            &keys,
+            &ids,
+            StreamReadOptions::default()
+                .count(BATCH_SIZE)
+                .block(BLOCK_TIME),
+        )?;
+
+        for stream in reply.map_or_else(Vec::new, |r| r.keys) {
+            let subscriptions = match keys.iter().position(|k| *k == stream.key) {
+                Some(i) => self.subscriptions.get_mut(&deployments[i]).unwrap(),
+                None => continue,
+            };
+
+            for message in stream.ids {
+                let event: String = message.get("event").unwrap_or_default();
+                let data: String = message.get("data").unwrap_or_default();
+                let deleted = serde_json::from_str::<serde_json::Value>(&data)
+                    .map(|d| d["state"] == "deleted")
+                    .unwrap_or(false);
+                let last = event == "state" && deleted;
+
+                let id = parse_id(&message.id);
+                for subscription in subscriptions.iter_mut().filter(|s| s.last_id < id) {
+                    subscription.send(Some(&message.id), &event, &data, last);
+                    subscription.last_id = id;
+                }
+            }
+        }
+
+        if self.rollout_checked.elapsed() >= ROLLOUT_INTERVAL {
+            for (deployment_id, subscriptions) in self.subscriptions.iter_mut() {
+                match rollout(*deployment_id) {
+                    Ok(r) => subscriptions.iter_mut().for_each(|s| s.send_rollout(&r)),
+                    Err(e) => warn!("failed to get rollout status: {}", e),
+                }
+            }
+            self.rollout_checked = Instant::now();
+        }
+
+        Ok(())
+    }
+}
+
+/// The encoded rollout status of a deployment
+fn rollout(deployment_id: Uuid) -> Result<String, ApiError> {
+    serde_json::to_string(&Rollout::find(deployment_id)?)
+        .map_err(|e| ApiError::new(500, format!("failed to encode rollout: {}", e)))
+}
+
+/// Parse a stream message id into its time and sequence number so ids can
+/// be compared, where a missing sequence number is zero
+fn parse_id(id: &str) -> (u64, u64) {
+    let mut parts = id.splitn(2, '-');
+    let ms = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+    let seq = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+    (ms, seq)
+}
+
+/// The key a deployment's progress is stored at
+fn key(deployment_id: Uuid) -> String {
+    format!("deployment-progress:{}", deployment_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_message_ids() {
+        assert_eq!(parse_id("0"), (0, 0));
+        assert_eq!(parse_id("1600000000000-3"), (1_600_000_000_000, 3));
+        assert!(parse_id("1600000000000") < parse_id("1600000000000-1"));
+        assert!(parse_id("999-10") < parse_id("1000-0"));
+        assert!(parse_id("1000-2") < parse_id("1000-10"));
+    }
+
+    #[test]
+    fn validates_resume_ids() {
+        assert!(is_valid_id("0"));
+        assert!(is_valid_id("1600000000000-3"));
+        assert!(!is_valid_id(""));
+        assert!(!is_valid_id("-3"));
+        assert!(!is_valid_id("1-"));
+        assert!(!is_valid_id("$"));
+    }
+}
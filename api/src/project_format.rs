@@ -1,3 +1,5 @@
+use crate::errors::ApiError;
+use ring::digest;
 use serde::{Deserialize, Serialize};
 use serde_json::value::Value;
 
@@ -11,6 +13,23 @@ pub struct ProjectFormat {
     pub handlers: Vec<Handler>,
 }
 
+impl ProjectFormat {
+    /// Hash the configuration, identifying deployments created from it
+    pub fn hash(&self) -> Result<String, ApiError> {
+        let json = serde_json::to_string(self).map_err(|e| {
+            ApiError::new(
+                500,
+                format!(
+                    "failed to re-encode project configuration for hashing: {}",
+                    e
+                ),
+            )
+        })?;
+        let hash = digest::digest(&digest::SHA256, json.as_bytes());
+        Ok(hex::encode(hash))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Route {
     pub path: String,
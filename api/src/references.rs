@@ -0,0 +1,300 @@
+use crate::errors::ApiError;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+// Maximum number of references resolved in a single document
+const MAX_REFERENCES: usize = 10_000;
+
+// Maximum size of a resolved document, counting each value and the bytes
+// of each string and key, preventing references which repeatedly expand
+// each other from exhausting memory
+const MAX_RESOLVED_SIZE: usize = 16 * 1024 * 1024;
+
+/// Replace each `$ref` object within a document with the value it points
+/// to. References are paths relative to the file containing them with an
+/// optional JSON pointer fragment, such as `./handlers/list.yaml` or
+/// `common.yaml#/logic`. Files are loaded with `read`, given a normalized
+/// path relative to the project directory.
+pub fn resolve<F>(path: &str, document: Value, read: F) -> Result<Value, ApiError>
+where
+    F: Fn(&str) -> Result<Value, ApiError>,
+{
+    let mut resolver = Resolver {
+        read: &read,
+        files: HashMap::new(),
+        stack: vec![format!("{}#", path)],
+        resolved: 0,
+        size: 0,
+    };
+    resolver.files.insert(path.to_string(), document.clone());
+    resolver.value(path, &document)
+}
+
+struct Resolver<'a> {
+    read: &'a dyn Fn(&str) -> Result<Value, ApiError>,
+    files: HashMap<String, Value>,
+    stack: Vec<String>,
+    resolved: usize,
+    size: usize,
+}
+
+impl<'a> Resolver<'a> {
+    fn value(&mut self, file: &str, value: &Value) -> Result<Value, ApiError> {
+        match value {
+            Value::Object(object) => match reference(object) {
+                Some(r) => self.reference(file, r),
+                None => {
+                    self.grow(1)?;
+                    let mut resolved = Map::new();
+                    for (key, value) in object {
+                        self.grow(key.len())?;
+                        resolved.insert(key.clone(), self.value(file, value)?);
+                    }
+                    Ok(Value::Object(resolved))
+                }
+            },
+            Value::Array(items) => {
+                self.grow(1)?;
+                let mut resolved = Vec::with_capacity(items.len());
+                for item in items {
+                    resolved.push(self.value(file, item)?);
+                }
+                Ok(Value::Array(resolved))
+            }
+            Value::String(string) => {
+                self.grow(1 + string.len())?;
+                Ok(value.clone())
+            }
+            other => {
+                self.grow(1)?;
+                Ok(other.clone())
+            }
+        }
+    }
+
+    /// Count the size of the resolved document so far
+    fn grow(&mut self, amount: usize) -> Result<(), ApiError> {
+        self.size += amount;
+        if self.size > MAX_RESOLVED_SIZE {
+            return Err(ApiError::new(
+                400,
+                format!(
+                    "document is larger than {} bytes once references are resolved",
+                    MAX_RESOLVED_SIZE
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn reference(&mut self, file: &str, reference: &str) -> Result<Value, ApiError> {
+        self.resolved += 1;
+        if self.resolved > MAX_REFERENCES {
+            return Err(ApiError::new(
+                400,
+                format!("document contains more than {} references", MAX_REFERENCES),
+            ));
+        }
+
+        let (target, pointer) = match reference.find('#') {
+            Some(i) => (&reference[..i], &reference[i + 1..]),
+            None => (reference, ""),
+        };
+        let target = if target.is_empty() {
+            file.to_string()
+        } else {
+            join(file, target)?
+        };
+
+        // Detect references which eventually point back to themselves
+        let key = format!("{}#{}", target, pointer);
+        if let Some(i) = self.stack.iter().position(|k| *k == key) {
+            let mut cycle = self.stack[i..].to_vec();
+            cycle.push(key);
+            return Err(ApiError::new(
+                400,
+                format!("circular reference: {}", cycle.join(" -> ")),
+            ));
+        }
+
+        if !self.files.contains_key(&target) {
+            let document = (self.read)(&target)?;
+            self.files.insert(target.clone(), document);
+        }
+        let node = self.files[&target]
+            .pointer(pointer)
+            .cloned()
+            .ok_or_else(|| {
+                ApiError::new(
+                    400,
+                    format!("reference '{}' does not point to a value", reference),
+                )
+            })?;
+
+        self.stack.push(key);
+        let resolved = self.value(&target, &node);
+        self.stack.pop();
+        resolved
+    }
+}
+
+/// Get the target of a JSON reference object
+fn reference(object: &Map<String, Value>) -> Option<&str> {
+    if object.len() != 1 {
+        return None;
+    }
+    object.get("$ref").and_then(Value::as_str)
+}
+
+/// Resolve a relative path against the directory of a file, rejecting any
+/// which would leave the project directory
+fn join(file: &str, path: &str) -> Result<String, ApiError> {
+    if path.starts_with('/') || path.contains("://") {
+        return Err(ApiError::new(
+            400,
+            format!("reference '{}' must be a relative path", path),
+        ));
+    }
+
+    let mut parts: Vec<&str> = file.split('/').collect();
+    parts.pop();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                if parts.pop().is_none() {
+                    return Err(ApiError::new(
+                        400,
+                        format!("reference '{}' escapes the project directory", path),
+                    ));
+                }
+            }
+            p => parts.push(p),
+        }
+    }
+
+    Ok(parts.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_files(path: &str) -> Result<Value, ApiError> {
+        Err(ApiError::new(
+            400,
+            format!("file '{}' does not exist", path),
+        ))
+    }
+
+    #[test]
+    fn resolves_local_and_relative_references() {
+        let mut files = HashMap::new();
+        files.insert(
+            "handlers/list.yaml",
+            json!({ "logic": [{ "$ref": "../common.yaml#/steps" }] }),
+        );
+        files.insert("common.yaml", json!({ "steps": { "action": "return" } }));
+
+        let document = json!({
+            "logic": { "$ref": "./handlers/list.yaml#/logic" },
+            "name": { "$ref": "#/definitions/name" },
+            "definitions": { "name": "list" },
+        });
+        let resolved = resolve("project.yaml", document, |path| {
+            files
+                .get(path)
+                .cloned()
+                .ok_or_else(|| no_files(path).unwrap_err())
+        })
+        .unwrap();
+        assert_eq!(
+            resolved,
+            json!({
+                "logic": [{ "action": "return" }],
+                "name": "list",
+                "definitions": { "name": "list" },
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_missing_files() {
+        let document = json!({ "a": { "$ref": "b.yaml" } });
+        let error = resolve("project.yaml", document, no_files).unwrap_err();
+        assert_eq!(error.message, "file 'b.yaml' does not exist");
+    }
+
+    #[test]
+    fn keeps_objects_with_other_keys() {
+        let document = json!({ "$ref": "#/a", "description": "not a reference" });
+        let resolved = resolve("project.yaml", document.clone(), no_files).unwrap();
+        assert_eq!(resolved, document);
+    }
+
+    #[test]
+    fn rejects_circular_references() {
+        let document = json!({ "a": { "$ref": "#/b" }, "b": { "$ref": "#/a" } });
+        let error = resolve("project.yaml", document, no_files).unwrap_err();
+        assert_eq!(
+            error.message,
+            "circular reference: project.yaml#/b -> project.yaml#/a -> project.yaml#/b"
+        );
+    }
+
+    #[test]
+    fn rejects_self_references() {
+        let document = json!({ "a": { "$ref": "#" } });
+        let error = resolve("project.yaml", document, no_files).unwrap_err();
+        assert_eq!(error.status_code, 400);
+    }
+
+    #[test]
+    fn rejects_missing_pointers() {
+        let document = json!({ "a": { "$ref": "#/b" } });
+        let error = resolve("project.yaml", document, no_files).unwrap_err();
+        assert_eq!(error.message, "reference '#/b' does not point to a value");
+    }
+
+    #[test]
+    fn rejects_expanding_references() {
+        // Each level repeats the one below, so the resolved document is far
+        // larger than the document itself
+        let document = json!({
+            "a": "x".repeat(1024 * 1024),
+            "b": vec![json!({ "$ref": "#/a" }); 20],
+        });
+        let error = resolve("project.yaml", document, no_files).unwrap_err();
+        assert_eq!(
+            error.message,
+            format!(
+                "document is larger than {} bytes once references are resolved",
+                MAX_RESOLVED_SIZE
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_too_many_references() {
+        let document = json!({
+            "a": "x",
+            "b": vec![json!({ "$ref": "#/a" }); 100],
+            "c": vec![json!({ "$ref": "#/b" }); 101],
+        });
+        let error = resolve("project.yaml", document, no_files).unwrap_err();
+        assert_eq!(
+            error.message,
+            format!("document contains more than {} references", MAX_REFERENCES)
+        );
+    }
+
+    #[test]
+    fn joins_relative_paths() {
+        assert_eq!(join("a/b.yaml", "c.yaml").unwrap(), "a/c.yaml");
+        assert_eq!(join("a/b.yaml", "./c/../d.yaml").unwrap(), "a/d.yaml");
+        assert_eq!(join("a/b.yaml", "../c.yaml").unwrap(), "c.yaml");
+        assert!(join("a/b.yaml", "../../c.yaml").is_err());
+        assert!(join("a.yaml", "/etc/passwd").is_err());
+        assert!(join("a.yaml", "https://example.com/a.yaml").is_err());
+    }
+}
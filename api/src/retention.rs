@@ -0,0 +1,371 @@
+use crate::{
+    config::CFG,
+    errors::ApiError,
+    events::{Event, EventType},
+    jobs,
+    models::{Alias, Deployment, Project, Traffic, Upload},
+    static_files, versions,
+};
+use chrono::{Duration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// A deployment removed by garbage collection
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Removed {
+    pub id: Uuid,
+    pub version: String,
+}
+
+/// What a garbage collection removed from a project
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Report {
+    pub deployments: Vec<Removed>,
+    /// Blobs no longer referenced by any deployment
+    pub blobs: usize,
+    /// Stored blobs which had no record
+    pub orphans: usize,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CollectGarbage {
+    pub project_id: Uuid,
+    pub correlation_id: String,
+}
+
+/// Delete a deployment and anything only it was using, then notify the
+/// runtimes. Blobs it referenced are released but not removed, see
+/// `static_files::remove_unreferenced`. Deployments receiving traffic or
+/// with aliases cannot be deleted until they are moved elsewhere. The
+/// deployment is locked while it is checked and deleted, so traffic or an
+/// alias moved to it in the meantime fails to reference it instead.
+pub async fn delete(deployment: &Deployment, correlation_id: String) -> Result<(), ApiError> {
+    // Handlers, routes and static files are removed along with the deployment
+    let uploads = Upload::find_all(deployment.id)?;
+    Deployment::lock(deployment.id, |conn, deployment| {
+        if let Some(traffic) = Traffic::find(deployment.project_id)? {
+            if traffic.includes(deployment.id) {
+                return Err(ApiError::new(
+                    409,
+                    "deployment is receiving traffic".to_string(),
+                ));
+            }
+        }
+
+        let aliases = Alias::find_for_deployment(conn, deployment.id)?;
+        if !aliases.is_empty() {
+            let names: Vec<_> = aliases.into_iter().map(|a| a.name).collect();
+            return Err(ApiError::new(
+                409,
+                format!("deployment has aliases: {}", names.join(", ")),
+            ));
+        }
+
+        deployment.delete_with(conn)
+    })?;
+
+    for upload in uploads {
+        static_files::remove_upload(upload.id).await?;
+    }
+
+    Event::new(EventType::DeploymentDeleted, deployment, correlation_id).publish()
+}
+
+/// Find the deployments which have expired under the project's retention
+/// policy, oldest first. A deployment is kept if it is one of the most
+/// recent `retention_count` or is newer than `retention_days`, and is
+/// never removed while it is protected, see `protected`.
+pub fn expired(project: &Project) -> Result<Vec<Deployment>, ApiError> {
+    Ok(select_expired(
+        project,
+        Deployment::find_all(project.id)?,
+        Traffic::find(project.id)?,
+        Alias::find_all(project.id)?,
+        Utc::now().naive_utc(),
+    ))
+}
+
+/// Choose which of a project's deployments have expired as of `now`.
+/// Nothing expires without a retention policy.
+fn select_expired(
+    project: &Project,
+    mut deployments: Vec<Deployment>,
+    traffic: Option<Traffic>,
+    aliases: Vec<Alias>,
+    now: NaiveDateTime,
+) -> Vec<Deployment> {
+    if project.retention_count.is_none() && project.retention_days.is_none() {
+        return Vec::new();
+    }
+
+    deployments.sort_by(|a, b| b.published_at.cmp(&a.published_at));
+    let protected = protected(&deployments, traffic, aliases);
+
+    let cutoff = project
+        .retention_days
+        .map(|days| now - Duration::days(days as i64));
+    let mut expired: Vec<Deployment> = deployments
+        .into_iter()
+        .enumerate()
+        .filter(|(i, d)| {
+            let recent = project.retention_count.map_or(false, |c| *i < c as usize);
+            let new = cutoff.map_or(false, |c| d.published_at >= c);
+            !recent && !new && !protected.contains(&d.id)
+        })
+        .map(|(_, d)| d)
+        .collect();
+
+    expired.reverse();
+    expired
+}
+
+/// Deployments which must be kept regardless of the retention policy,
+/// given all of a project's deployments. These are deployments which are
+/// pinned, receiving traffic, or aliased, along with the highest ready
+/// version of each version line so a range such as `^1` keeps resolving.
+/// A version line is the major version, or the minor version for `0.x`
+/// versions since SemVer treats each as incompatible with the last.
+fn protected(
+    deployments: &[Deployment],
+    traffic: Option<Traffic>,
+    aliases: Vec<Alias>,
+) -> HashSet<Uuid> {
+    let mut protected: HashSet<Uuid> = deployments
+        .iter()
+        .filter(|d| d.pinned)
+        .map(|d| d.id)
+        .collect();
+
+    let mut lines = HashMap::new();
+    for deployment in deployments.iter().filter(|d| d.has_static) {
+        let version = match versions::parse(&deployment.version) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let line = if version.major == 0 {
+            (0, version.minor)
+        } else {
+            (version.major, 0)
+        };
+
+        let highest = lines
+            .entry(line)
+            .or_insert((version.clone(), deployment.id));
+        if version > highest.0 {
+            *highest = (version, deployment.id);
+        }
+    }
+    protected.extend(lines.values().map(|(_, id)| *id));
+
+    if let Some(traffic) = traffic {
+        protected.insert(traffic.stable_id);
+        protected.extend(traffic.candidate_id);
+    }
+
+    protected.extend(aliases.into_iter().map(|a| a.deployment_id));
+
+    protected
+}
+
+/// Remove a project's expired deployments along with any storage they
+/// were the last to use
+pub async fn collect(project_id: Uuid, correlation_id: String) -> Result<Report, ApiError> {
+    let project = Project::find(project_id)?;
+
+    let mut removed = Vec::new();
+    for deployment in expired(&project)? {
+        delete(&deployment, correlation_id.clone()).await?;
+        removed.push(Removed {
+            id: deployment.id,
+            version: deployment.version,
+        });
+    }
+
+    Ok(Report {
+        deployments: removed,
+        blobs: static_files::remove_unreferenced(project.id).await?,
+        orphans: static_files::remove_orphaned(project.id).await?,
+    })
+}
+
+/// Queue a garbage collection for every project with a retention policy
+/// at the configured interval, should be spawned when the server starts.
+/// Collections are keyed by interval so each project is only collected
+/// once per interval when multiple instances of the API are running.
+pub async fn schedule() {
+    let interval = CFG.retention.interval.max(1);
+    loop {
+        if let Err(e) = enqueue_all(interval) {
+            error!("failed to schedule garbage collection: {}", e);
+        }
+
+        actix_rt::time::delay_for(std::time::Duration::from_secs(interval)).await;
+    }
+}
+
+fn enqueue_all(interval: u64) -> Result<(), ApiError> {
+    let window = Utc::now().timestamp() as u64 / interval;
+    for project in Project::find_with_retention()? {
+        jobs::enqueue(
+            project.user_id,
+            jobs::COLLECT_GARBAGE,
+            &CollectGarbage {
+                project_id: project.id,
+                correlation_id: Uuid::new_v4().to_string(),
+            },
+            Some(format!("scheduled:{}:{}", project.id, window)),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> NaiveDateTime {
+        NaiveDateTime::from_timestamp(1_000_000_000, 0)
+    }
+
+    fn project(retention_count: Option<i32>, retention_days: Option<i32>) -> Project {
+        Project {
+            id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            name: "shop".to_string(),
+            description: String::new(),
+            created_at: now(),
+            updated_at: None,
+            retention_count,
+            retention_days,
+        }
+    }
+
+    /// A complete deployment published a number of days ago
+    fn deployment(version: &str, days_ago: i64) -> Deployment {
+        Deployment {
+            id: Uuid::new_v4(),
+            project_id: Uuid::nil(),
+            version: version.to_string(),
+            hash: String::new(),
+            has_static: true,
+            published_at: now() - Duration::days(days_ago),
+            source: None,
+            pinned: false,
+            normalized_version: None,
+        }
+    }
+
+    fn alias(deployment: &Deployment) -> Alias {
+        Alias {
+            id: Uuid::new_v4(),
+            project_id: Uuid::nil(),
+            name: "latest".to_string(),
+            deployment_id: deployment.id,
+            created_at: now(),
+            updated_at: now(),
+        }
+    }
+
+    fn traffic(stable: &Deployment, candidate: Option<&Deployment>) -> Traffic {
+        Traffic {
+            project_id: Uuid::nil(),
+            stable_id: stable.id,
+            candidate_id: candidate.map(|d| d.id),
+            weight: 10,
+            sticky_header: None,
+            updated_at: now(),
+        }
+    }
+
+    fn versions(deployments: &[Deployment]) -> Vec<&str> {
+        deployments.iter().map(|d| d.version.as_str()).collect()
+    }
+
+    fn history() -> Vec<Deployment> {
+        (1..=5)
+            .map(|i| deployment(&format!("1.0.{}", i), 10 - i))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_everything_without_a_policy() {
+        let expired = select_expired(&project(None, None), history(), None, vec![], now());
+        assert!(expired.is_empty());
+    }
+
+    #[test]
+    fn expires_beyond_count_oldest_first() {
+        let expired = select_expired(&project(Some(2), None), history(), None, vec![], now());
+        assert_eq!(versions(&expired), vec!["1.0.1", "1.0.2", "1.0.3"]);
+
+        // The highest version of the line is kept even when it is beyond the count
+        let mut deployments = history();
+        deployments[0].published_at = now();
+        let expired = select_expired(&project(Some(1), None), deployments, None, vec![], now());
+        assert_eq!(versions(&expired), vec!["1.0.2", "1.0.3", "1.0.4"]);
+    }
+
+    #[test]
+    fn expires_beyond_days() {
+        // Deployments are published between 9 and 5 days ago
+        let expired = select_expired(&project(None, Some(7)), history(), None, vec![], now());
+        assert_eq!(versions(&expired), vec!["1.0.1", "1.0.2"]);
+    }
+
+    #[test]
+    fn keeps_deployments_within_either_limit() {
+        let expired = select_expired(&project(Some(1), Some(6)), history(), None, vec![], now());
+        assert_eq!(versions(&expired), vec!["1.0.1", "1.0.2", "1.0.3"]);
+    }
+
+    #[test]
+    fn protects_pinned_aliased_and_routed_deployments() {
+        let mut deployments = history();
+        deployments[0].pinned = true;
+        let aliases = vec![alias(&deployments[1])];
+        let traffic = traffic(&deployments[2], Some(&deployments[3]));
+
+        let expired = select_expired(
+            &project(Some(0), None),
+            deployments,
+            Some(traffic),
+            aliases,
+            now(),
+        );
+        assert!(expired.is_empty());
+    }
+
+    #[test]
+    fn protects_highest_version_of_each_line() {
+        let deployments: Vec<Deployment> = vec![
+            ("0.1.0", 9),
+            ("0.1.3", 8),
+            ("0.2.0", 7),
+            ("1.0.0", 6),
+            ("1.4.0", 5),
+            ("1.3.9", 4),
+            ("2.0.0-beta.1", 3),
+            ("legacy", 2),
+        ]
+        .into_iter()
+        .map(|(v, days_ago)| deployment(v, days_ago))
+        .collect();
+
+        let expired = select_expired(&project(Some(0), None), deployments, None, vec![], now());
+        assert_eq!(
+            versions(&expired),
+            vec!["0.1.0", "1.0.0", "1.3.9", "legacy"]
+        );
+    }
+
+    #[test]
+    fn only_protects_ready_versions() {
+        let mut deployments = history();
+        deployments[4].has_static = false;
+
+        let expired = select_expired(&project(Some(0), None), deployments, None, vec![], now());
+        assert_eq!(versions(&expired), vec!["1.0.1", "1.0.2", "1.0.3", "1.0.5"]);
+    }
+}
@@ -0,0 +1,143 @@
+use super::utils;
+use crate::{
+    errors::ApiError,
+    events::{Event, EventType},
+    models::{Alias, AliasMessage, Deployment, Project},
+};
+use actix_session::Session;
+use actix_web::{delete, get, put, web, HttpRequest, HttpResponse};
+use regex::Regex;
+use uuid::Uuid;
+
+lazy_static! {
+    static ref ALIAS_NAME_REGEX: Regex =
+        Regex::new(r"^[a-z0-9]([a-z0-9-]{0,61}[a-z0-9])?$").unwrap();
+}
+
+#[get("/projects/{id}/aliases")]
+async fn list(id: web::Path<Uuid>, session: Session) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(id.into_inner())?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let aliases = Alias::find_all(project.id)?;
+    Ok(utils::success_with_data(json!(aliases)))
+}
+
+#[get("/projects/{id}/aliases/{name}")]
+async fn read(path: web::Path<(Uuid, String)>, session: Session) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(path.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let alias = find_alias(&project, &path.1)?;
+    Ok(utils::success_with_data(json!(alias)))
+}
+
+#[put("/projects/{id}/aliases/{name}")]
+async fn update(
+    req: HttpRequest,
+    path: web::Path<(Uuid, String)>,
+    alias: web::Json<AliasMessage>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let (project_id, name) = path.into_inner();
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(project_id)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+    let alias = alias.into_inner();
+
+    if !ALIAS_NAME_REGEX.is_match(&name) {
+        return Err(ApiError::new(
+            400,
+            "alias name must match '^[a-z0-9]([a-z0-9-]{0,61}[a-z0-9])?$'".to_string(),
+        ));
+    }
+
+    // Aliases share the url prefix used by versions
+    let deployments = Deployment::find_all(project.id)?;
+    if deployments.iter().any(|d| d.version == name) {
+        return Err(ApiError::new(
+            409,
+            format!("alias name '{}' is used by a deployment version", name),
+        ));
+    }
+
+    let deployment = deployments
+        .into_iter()
+        .find(|d| d.id == alias.deployment_id)
+        .ok_or_else(|| ApiError::new(404, "specified deployment does not exist".to_string()))?;
+
+    let alias = Alias::set(project.id, name, deployment.id, alias.previous)?;
+    Event::alias(
+        EventType::AliasUpdated,
+        &deployment,
+        alias.clone(),
+        utils::correlation_id(&req),
+    )
+    .publish()?;
+
+    Ok(utils::success_with_data(json!(alias)))
+}
+
+#[delete("/projects/{id}/aliases/{name}")]
+async fn delete(
+    req: HttpRequest,
+    path: web::Path<(Uuid, String)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(path.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let alias = find_alias(&project, &path.1)?;
+    let deployment = Deployment::find(alias.deployment_id)?;
+    Alias::delete(alias.id)?;
+
+    Event::alias(
+        EventType::AliasDeleted,
+        &deployment,
+        alias,
+        utils::correlation_id(&req),
+    )
+    .publish()?;
+
+    Ok(utils::success())
+}
+
+/// Find an alias in a project by name
+fn find_alias(project: &Project, name: &str) -> Result<Alias, ApiError> {
+    Alias::find(project.id, name)?
+        .ok_or_else(|| ApiError::new(404, "specified alias does not exist".to_string()))
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list);
+    cfg.service(read);
+    cfg.service(update);
+    cfg.service(delete);
+}
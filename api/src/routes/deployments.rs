@@ -1,22 +1,31 @@
 use super::utils;
 use crate::{
+    archive, bundle,
     config::CFG,
+    diff,
     errors::ApiError,
-    models::{Deployment, Handler, Project, Route},
+    events::{self, Event, EventType},
+    export,
+    jobs::{self, IngestStatic},
+    models::{Alias, Deployment, Handler, Project, Route, StaticFile},
+    openapi,
+    progress::{self, Progress, State},
     project_format::ProjectFormat,
+    references, retention,
+    runtimes::Rollout,
+    static_files::{self, Manifest},
+    storage, typescript, versions, webhooks,
 };
 use actix_multipart::{Field, Multipart};
 use actix_session::Session;
-use actix_web::{delete, get, post, put, web, HttpResponse};
-use cloud_storage::Object;
-use futures::{StreamExt, TryStreamExt};
-use redis::Commands;
-use ring::digest;
+use actix_web::{delete, get, post, put, web, HttpMessage, HttpRequest, HttpResponse};
+use futures::TryStreamExt;
 use serde::{Deserialize, Serialize};
-use std::io::{Cursor, Read};
-use std::path::PathBuf;
+use std::collections::HashMap;
 use uuid::Uuid;
-use zip::ZipArchive;
+
+// Maximum size of a project configuration
+const MAX_CONFIGURATION_SIZE: u64 = 4 * 1024 * 1024;
 
 #[get("/projects/{id}/deployments")]
 async fn list(id: web::Path<Uuid>, session: Session) -> Result<HttpResponse, ApiError> {
@@ -30,13 +39,46 @@ async fn list(id: web::Path<Uuid>, session: Session) -> Result<HttpResponse, Api
         ));
     }
 
-    let deployments = Deployment::find_all(project.id)?;
+    let mut deployments = Deployment::find_all(project.id)?;
+    versions::sort(&mut deployments);
     Ok(utils::success_with_data(json!(deployments)))
 }
 
+#[derive(Deserialize)]
+struct ResolveQuery {
+    range: String,
+}
+
+#[get("/projects/{id}/deployments/resolve")]
+async fn resolve(
+    id: web::Path<Uuid>,
+    query: web::Query<ResolveQuery>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(id.into_inner())?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let deployments = Deployment::find_all(project.id)?;
+    match versions::resolve(&query.range, deployments)? {
+        Some(deployment) => Ok(utils::success_with_data(json!(deployment))),
+        None => Err(ApiError::new(
+            404,
+            format!("no ready deployment matches '{}'", query.range),
+        )),
+    }
+}
+
 #[post("/projects/{id}/deployments")]
 async fn create(
-    format: web::Json<ProjectFormat>,
+    req: HttpRequest,
+    payload: web::Payload,
     id: web::Path<Uuid>,
     session: Session,
 ) -> Result<HttpResponse, ApiError> {
@@ -50,29 +92,157 @@ async fn create(
         ));
     }
 
-    let format = format.into_inner();
-    let hash = generate_hash(&format)?;
+    // Accept the configuration as either YAML or JSON
+    let body = utils::read_body(payload, MAX_CONFIGURATION_SIZE).await?;
+    let document: serde_json::Value = match req.content_type() {
+        "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => {
+            serde_yaml::from_slice(&body)
+                .map_err(|e| ApiError::new(400, format!("invalid yaml: {}", e)))?
+        }
+        _ => serde_json::from_slice(&body)
+            .map_err(|e| ApiError::new(400, format!("invalid json: {}", e)))?,
+    };
 
-    // Prevent duplicates
-    if let Some(d) = Deployment::find_by_hash(&hash, project.id)? {
-        return Ok(utils::success_with_data(json!({ "id": d.id })));
+    // Only references within the body itself can be resolved
+    let document = references::resolve("", document, |path| {
+        Err(ApiError::new(
+            400,
+            format!(
+                "reference to '{}' can only be resolved when deploying an archive",
+                path
+            ),
+        ))
+    })?;
+    let format: ProjectFormat = serde_json::from_value(document)
+        .map_err(|e| ApiError::new(400, format!("invalid project configuration: {}", e)))?;
+
+    let id = create_deployment(&project, format, None)?;
+    Ok(utils::success_with_data(json!({ "id": id })))
+}
+
+#[post("/projects/{id}/deploy")]
+async fn deploy(
+    req: HttpRequest,
+    mut payload: Multipart,
+    id: web::Path<Uuid>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(id.into_inner())?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
     }
 
-    let deployment = Deployment::create(format.version, hash, project.id)?;
+    let mut upload: Option<Field> = None;
 
-    for handler in format.handlers {
-        deployment.add_handler(handler)?;
+    while let Ok(Some(field)) = payload.try_next().await {
+        let content_disposition = field
+            .content_disposition()
+            .ok_or_else(|| actix_web::error::ParseError::Incomplete)?;
+        let name = content_disposition
+            .get_name()
+            .ok_or_else(|| actix_web::error::ParseError::Incomplete)?;
+
+        if name == "project" {
+            upload = Some(field);
+            break;
+        }
     }
 
-    for route in format.routes {
-        deployment.add_route(route)?;
+    let upload = match upload {
+        Some(s) => s,
+        None => {
+            return Err(ApiError::new(
+                400,
+                "form field 'project' is required".to_string(),
+            ))
+        }
+    };
+
+    let format = match archive::Format::from_mime(upload.content_type()) {
+        Some(f) => f,
+        None => {
+            return Err(ApiError::new(
+                400,
+                "form field 'project' must be a zip, tar, tar.gz, or tar.zst archive".to_string(),
+            ))
+        }
+    };
+
+    // Validate the whole bundle before anything is created
+    let file = archive::receive(upload, CFG.uploads.archive).await?;
+    let (extracted, bundle) = web::block(move || {
+        let extracted = archive::extract(format, file)?;
+        let bundle = bundle::load(&extracted)?;
+        Ok::<_, ApiError>((extracted, bundle))
+    })
+    .await?;
+
+    let hash = bundle.format.hash()?;
+    let existing = Deployment::find_by_hash(&hash, project.id)?;
+    if let Some(d) = existing.as_ref().filter(|d| d.has_static) {
+        return Ok(utils::success_with_data(json!({ "id": d.id })));
     }
 
+    // Store the static files before recording the deployment, so a failure
+    // part way through never leaves a deployment without them
+    let manifest = static_files::upload_extracted(project.id, bundle.static_files).await?;
+    drop(extracted);
+
+    let deployment = match existing {
+        // The same configuration was created without its static files
+        Some(d) => {
+            static_files::commit(project.id, d.id, &manifest)?;
+            d
+        }
+        None => {
+            let id = create_deployment(&project, bundle.format, Some(&manifest))?;
+            Deployment::find(id)?
+        }
+    };
+
+    Event::new(
+        EventType::DeploymentPublished,
+        &deployment,
+        utils::correlation_id(&req),
+    )
+    .publish()?;
+
     Ok(utils::success_with_data(json!({ "id": deployment.id })))
 }
 
+#[post("/projects/{id}/import/openapi")]
+async fn import_openapi(
+    spec: web::Json<serde_json::Value>,
+    id: web::Path<Uuid>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(id.into_inner())?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let format = openapi::import(&spec, &project.name)?;
+    let skeleton = json!(format);
+    let id = create_deployment(&project, format, None)?;
+
+    Ok(utils::success_with_data(
+        json!({ "id": id, "project": skeleton }),
+    ))
+}
+
 #[put("/projects/{project_id}/deployments/{deployment_id}")]
 async fn add_static(
+    req: HttpRequest,
     mut payload: Multipart,
     ids: web::Path<(Uuid, Uuid)>,
     session: Session,
@@ -93,14 +263,27 @@ async fn add_static(
             404,
             "specified deployment does not exist".to_string(),
         ));
-    } else if deployment.has_static {
+    }
+
+    // Return the job queued by an earlier attempt at the same request
+    let key = utils::idempotency_key(&req)?;
+    if let Some(job) = jobs::find_existing(user_id, jobs::INGEST_STATIC, key.as_deref())? {
+        return Ok(utils::accepted(&job));
+    }
+
+    if deployment.has_static {
         return Err(ApiError::new(
             403,
             "static files already registered for deployment".to_string(),
         ));
+    } else if jobs::is_ingesting(deployment.id)? {
+        return Err(ApiError::new(
+            409,
+            "static files are already being added to deployment".to_string(),
+        ));
     }
 
-    let mut static_files: Option<Field> = None;
+    let mut upload: Option<Field> = None;
 
     while let Ok(Some(field)) = payload.try_next().await {
         let content_disposition = field
@@ -111,12 +294,12 @@ async fn add_static(
             .ok_or_else(|| actix_web::error::ParseError::Incomplete)?;
 
         if name == "static" {
-            static_files = Some(field);
+            upload = Some(field);
             break;
         }
     }
 
-    let mut static_files = match static_files {
+    let upload = match upload {
         Some(s) => s,
         None => {
             return Err(ApiError::new(
@@ -126,50 +309,205 @@ async fn add_static(
         }
     };
 
-    let content_type = static_files.content_type();
-    if content_type.type_() != "application" && content_type.subtype() != "zip" {
+    let format = match archive::Format::from_mime(upload.content_type()) {
+        Some(f) => f,
+        None => {
+            return Err(ApiError::new(
+                400,
+                "form field 'static' must be a zip, tar, tar.gz, or tar.zst archive".to_string(),
+            ))
+        }
+    };
+
+    // Extract the archive in the background once it is received
+    let content_type = upload.content_type().to_string();
+    let file = archive::receive(upload, CFG.uploads.archive).await?;
+    let upload = static_files::save_upload(deployment.id, content_type, file).await?;
+
+    // Another request may have started adding static files while the archive was received
+    let queued = jobs::enqueue_ingest(
+        user_id,
+        &IngestStatic {
+            upload_id: upload.id,
+            correlation_id: utils::correlation_id(&req),
+        },
+        key,
+    );
+    match queued {
+        Ok(job) => Ok(utils::accepted(&job)),
+        Err(e) => {
+            static_files::remove_upload(upload.id).await?;
+            Err(e)
+        }
+    }
+}
+
+#[get("/projects/{project_id}/deployments/{deployment_id}")]
+async fn read(ids: web::Path<(Uuid, Uuid)>, session: Session) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
         return Err(ApiError::new(
-            400,
-            "form field 'static' must be a zip file".to_string(),
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let deployment = Deployment::find(ids.1)?;
+    if deployment.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified deployment does not exist".to_string(),
+        ));
+    } else if !deployment.has_static {
+        return Err(ApiError::new(
+            406,
+            "deployment is not yet complete".to_string(),
+        ));
+    }
+
+    let mut response = ReadResponse::from(deployment.clone());
+    response.routes = Route::find_all(deployment.id)?;
+    response.handlers = Handler::find_all(deployment.id)?;
+    let id = deployment.id;
+    response.rollout = Some(web::block(move || Rollout::find(id)).await?);
+
+    Ok(utils::success_with_data(json!(response)))
+}
+
+#[get("/projects/{project_id}/deployments/{deployment_id}/static")]
+async fn static_manifest(
+    ids: web::Path<(Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let deployment = Deployment::find(ids.1)?;
+    if deployment.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified deployment does not exist".to_string(),
+        ));
+    }
+
+    let files = StaticFile::find_all(deployment.id)?;
+    Ok(utils::success_with_data(json!(files)))
+}
+
+#[get("/projects/{project_id}/deployments/{deployment_id}/deliveries")]
+async fn deliveries(
+    ids: web::Path<(Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
         ));
     }
 
-    let mut raw: Vec<u8> = Vec::new();
-    while let Some(chunk) = static_files.next().await {
-        let data = chunk.unwrap();
-        raw.extend_from_slice(data.as_ref());
+    let deployment = Deployment::find(ids.1)?;
+    if deployment.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified deployment does not exist".to_string(),
+        ));
     }
 
-    let buffer = Cursor::new(raw);
-    let mut archive = ZipArchive::new(buffer).map_err(|_| ApiError::new(400, "invalid zip archive format".to_string()))?;
+    let deliveries = web::block(move || events::deliveries(deployment.id)).await?;
+    Ok(utils::success_with_data(json!(deliveries)))
+}
+
+#[get("/projects/{project_id}/deployments/{deployment_id}/events")]
+async fn progress_events(
+    req: HttpRequest,
+    ids: web::Path<(Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
 
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i).unwrap();
-        let name = file.sanitized_name().as_path().display().to_string();
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
 
-        let mut path = PathBuf::new();
-        path.push(project.id.to_string());
-        path.push(&name);
+    let deployment = Deployment::find(ids.1)?;
+    if deployment.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified deployment does not exist".to_string(),
+        ));
+    }
 
-        let mut data = Vec::new();
-        file.read_to_end(&mut data)?;
+    // Resume after the last message the client received, or replay everything
+    let last_id = match req.headers().get("Last-Event-ID") {
+        Some(h) => h
+            .to_str()
+            .ok()
+            .filter(|id| progress::is_valid_id(id))
+            .ok_or_else(|| ApiError::new(400, "invalid header 'Last-Event-ID'".to_string()))?
+            .to_string(),
+        None => "0".to_string(),
+    };
 
-        let guess = mime_guess::from_path(&name);
-        let mime = guess.first_or(mime::APPLICATION_OCTET_STREAM);
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .header("X-Accel-Buffering", "no")
+        .streaming(Box::pin(progress::subscribe(deployment.id, last_id))))
+}
 
-        web::block(move || Object::create(&CFG.gcp.bucket, data.as_ref(), path.to_str().unwrap(), &mime.to_string())).await?;
+#[get("/projects/{project_id}/deployments/{deployment_id}/openapi.json")]
+async fn openapi_document(
+    ids: web::Path<(Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
     }
 
-    deployment.mark_has_static()?;
+    let deployment = Deployment::find(ids.1)?;
+    if deployment.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified deployment does not exist".to_string(),
+        ));
+    }
 
-    let mut connection = crate::redis::connection()?;
-    let _n: i32 = connection.publish("publish", project.id.to_string()).unwrap();
+    let routes = Route::find_all(deployment.id)?;
+    let handlers = Handler::find_all(deployment.id)?;
 
-    Ok(utils::success())
+    // Served without the success wrapper so it can be consumed by OpenAPI tooling directly
+    let document = openapi::generate(&project, &deployment, &routes, &handlers);
+    Ok(HttpResponse::Ok().json(document))
 }
 
-#[get("/projects/{project_id}/deployments/{deployment_id}")]
-async fn read(ids: web::Path<(Uuid, Uuid)>, session: Session) -> Result<HttpResponse, ApiError> {
+#[get("/projects/{project_id}/deployments/{deployment_id}/sdk/typescript")]
+async fn typescript_sdk(
+    ids: web::Path<(Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
     let user_id = utils::is_authenticated(&session)?;
 
     let project = Project::find(ids.0)?;
@@ -186,22 +524,152 @@ async fn read(ids: web::Path<(Uuid, Uuid)>, session: Session) -> Result<HttpResp
             404,
             "specified deployment does not exist".to_string(),
         ));
-    } else if !deployment.has_static {
+    }
+
+    let routes = Route::find_all(deployment.id)?;
+    let handlers = Handler::find_all(deployment.id)?;
+    let archive = typescript::generate_archive(&project, &deployment, &routes, &handlers)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .header(
+            "Content-Disposition",
+            utils::attachment(&format!(
+                "{}-{}-client.zip",
+                project.name, deployment.version
+            )),
+        )
+        .body(archive))
+}
+
+#[get("/projects/{project_id}/deployments/{deployment_id}/export")]
+async fn export_archive(
+    ids: web::Path<(Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
         return Err(ApiError::new(
-            406,
-            "deployment is not yet complete".to_string(),
+            403,
+            "user lacks permission for resource".to_string(),
         ));
     }
 
-    let mut response = ReadResponse::from(deployment.clone());
-    response.routes = Route::find_all(deployment.id)?;
-    response.handlers = Handler::find_all(deployment.id)?;
+    let deployment = Deployment::find(ids.1)?;
+    if deployment.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified deployment does not exist".to_string(),
+        ));
+    }
 
-    Ok(utils::success_with_data(json!(response)))
+    let routes = Route::find_all(deployment.id)?;
+    let handlers = Handler::find_all(deployment.id)?;
+    let format = export::project_format(&project, &deployment, routes, handlers)?;
+
+    let mut static_files = Vec::new();
+    if deployment.has_static {
+        for file in StaticFile::find_all(deployment.id)? {
+            let key = storage::blob_key(project.id, &file.hash);
+            let data = web::block(move || storage::backend().get(&key)).await?;
+            static_files.push((file.path, data));
+        }
+    }
+
+    let archive = export::build_archive(&format, static_files)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .header(
+            "Content-Disposition",
+            utils::attachment(&format!("{}-{}.zip", project.name, deployment.version)),
+        )
+        .body(archive))
+}
+
+#[get("/projects/{project_id}/deployments/{from_id}/diff/{to_id}")]
+async fn compare(
+    ids: web::Path<(Uuid, Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let from = Deployment::find(ids.1)?;
+    let to = Deployment::find(ids.2)?;
+    if from.project_id != project.id || to.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified deployment does not exist".to_string(),
+        ));
+    }
+
+    let from_routes = Route::find_all(from.id)?;
+    let from_handlers = Handler::find_all(from.id)?;
+    let from_static = static_hashes(&from)?;
+    let to_routes = Route::find_all(to.id)?;
+    let to_handlers = Handler::find_all(to.id)?;
+    let to_static = static_hashes(&to)?;
+
+    let changes = diff::deployments(
+        (&from_routes, &from_handlers, from_static.as_ref()),
+        (&to_routes, &to_handlers, to_static.as_ref()),
+    );
+    Ok(utils::success_with_data(json!(changes)))
 }
 
 #[delete("/projects/{project_id}/deployments/{deployment_id}")]
-async fn delete(ids: web::Path<(Uuid, Uuid)>, session: Session) -> Result<HttpResponse, ApiError> {
+async fn delete(
+    req: HttpRequest,
+    ids: web::Path<(Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let deployment = Deployment::find(ids.1)?;
+    if deployment.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified deployment does not exist".to_string(),
+        ));
+    }
+
+    retention::delete(&deployment, utils::correlation_id(&req)).await?;
+    static_files::remove_unreferenced(project.id).await?;
+
+    Ok(utils::success())
+}
+
+#[put("/projects/{project_id}/deployments/{deployment_id}/pin")]
+async fn pin(ids: web::Path<(Uuid, Uuid)>, session: Session) -> Result<HttpResponse, ApiError> {
+    set_pinned(ids.into_inner(), session, true)
+}
+
+#[delete("/projects/{project_id}/deployments/{deployment_id}/pin")]
+async fn unpin(ids: web::Path<(Uuid, Uuid)>, session: Session) -> Result<HttpResponse, ApiError> {
+    set_pinned(ids.into_inner(), session, false)
+}
+
+/// Pin or unpin a deployment, pinned deployments are never removed by
+/// the project's retention policy
+fn set_pinned(ids: (Uuid, Uuid), session: Session, pinned: bool) -> Result<HttpResponse, ApiError> {
     let user_id = utils::is_authenticated(&session)?;
 
     let project = Project::find(ids.0)?;
@@ -220,35 +688,65 @@ async fn delete(ids: web::Path<(Uuid, Uuid)>, session: Session) -> Result<HttpRe
         ));
     }
 
-    for handler in Handler::find_all(deployment.id)? {
-        Handler::delete(handler.id)?;
+    deployment.set_pinned(pinned)?;
+    Ok(utils::success())
+}
+
+/// Create a deployment from a project configuration and the manifest of
+/// its already uploaded static files, if any. Returns the id of an existing
+/// deployment if an identical configuration was already uploaded.
+fn create_deployment(
+    project: &Project,
+    format: ProjectFormat,
+    manifest: Option<&Manifest>,
+) -> Result<Uuid, ApiError> {
+    let hash = format.hash()?;
+
+    // Prevent duplicates
+    if let Some(d) = Deployment::find_by_hash(&hash, project.id)? {
+        return Ok(d.id);
     }
 
-    for route in Route::find_all(deployment.id)? {
-        Route::delete(route.id)?;
+    let version = versions::validate(&format.version, &Deployment::find_all(project.id)?)?;
+
+    // Versions share the url prefix used by aliases
+    if Alias::find(project.id, &format.version)?.is_some() {
+        return Err(ApiError::new(
+            409,
+            format!("version '{}' is used by an alias", format.version),
+        ));
     }
 
-    Deployment::delete(deployment.id)?;
+    let requested = format.version.clone();
+    let deployment = match Deployment::create(format, &version, hash, project.id, manifest) {
+        // The version was taken by a concurrent deploy since it was checked
+        Err(e) if e.status_code == 409 => {
+            versions::validate(&requested, &Deployment::find_all(project.id)?)?;
+            return Err(e);
+        }
+        result => result?,
+    };
 
-    let mut connection = crate::redis::connection()?;
-    let _n: i32 = connection.publish("delete", project.id.to_string()).unwrap();
+    progress::emit(deployment.id, Progress::state(State::Created));
+    webhooks::enqueue(
+        project.id,
+        "deployment.created",
+        json!({ "deployment_id": deployment.id, "version": deployment.version }),
+    )?;
 
-    Ok(utils::success())
+    Ok(deployment.id)
 }
 
-/// Serialize a project to JSON
-fn generate_hash(format: &ProjectFormat) -> Result<String, ApiError> {
-    let json = serde_json::to_string(format).map_err(|e| {
-        ApiError::new(
-            500,
-            format!(
-                "failed to re-encode project configuration for hashing: {}",
-                e
-            ),
-        )
-    })?;
-    let hash = digest::digest(&digest::SHA256, json.as_bytes());
-    Ok(hex::encode(hash))
+/// Get the content hash of each of a deployment's static files keyed by path.
+/// Static files uploaded before manifests were recorded have no comparable
+/// hash, so they aren't compared at all rather than by storage checksums.
+fn static_hashes(deployment: &Deployment) -> Result<Option<HashMap<String, String>>, ApiError> {
+    let files = StaticFile::find_all(deployment.id)?;
+    if deployment.has_static && files.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(files.into_iter().map(|f| (f.path, f.hash)).collect()))
 }
 
 /// Custom response type to combine the standard deployment
@@ -259,6 +757,7 @@ struct ReadResponse {
     pub deployment: Deployment,
     pub routes: Vec<Route>,
     pub handlers: Vec<Handler>,
+    pub rollout: Option<Rollout>,
 }
 
 impl From<Deployment> for ReadResponse {
@@ -267,6 +766,7 @@ impl From<Deployment> for ReadResponse {
             deployment: d,
             routes: vec![],
             handlers: vec![],
+            rollout: None,
         }
     }
 }
@@ -274,7 +774,20 @@ impl From<Deployment> for ReadResponse {
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(list);
     cfg.service(create);
+    cfg.service(deploy);
+    cfg.service(import_openapi);
     cfg.service(add_static);
+    // Must be registered before `read` which would match it as an id
+    cfg.service(resolve);
     cfg.service(read);
+    cfg.service(static_manifest);
+    cfg.service(deliveries);
+    cfg.service(progress_events);
+    cfg.service(openapi_document);
+    cfg.service(typescript_sdk);
+    cfg.service(export_archive);
+    cfg.service(compare);
     cfg.service(delete);
+    cfg.service(pin);
+    cfg.service(unpin);
 }
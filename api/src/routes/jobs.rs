@@ -0,0 +1,36 @@
+use super::utils;
+use crate::{errors::ApiError, models::Job};
+use actix_session::Session;
+use actix_web::{get, web, HttpResponse};
+use uuid::Uuid;
+
+// Number of jobs shown in a user's history
+const HISTORY_LENGTH: i64 = 100;
+
+#[get("/jobs")]
+async fn list(session: Session) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let jobs = Job::find_all(user_id, HISTORY_LENGTH)?;
+    Ok(utils::success_with_data(json!(jobs)))
+}
+
+#[get("/jobs/{id}")]
+async fn read(id: web::Path<Uuid>, session: Session) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let job = Job::find(id.into_inner())?;
+    if job.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    Ok(utils::success_with_data(json!(job)))
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list);
+    cfg.service(read);
+}
@@ -1,10 +1,24 @@
+mod aliases;
 mod authentication;
 mod deployments;
+mod jobs;
 mod projects;
+mod runtimes;
+mod static_files;
+mod traffic;
+mod uploads;
 mod users;
 mod utils;
+mod webhooks;
 
+pub use aliases::init_routes as aliases;
 pub use authentication::init_routes as authentication;
 pub use deployments::init_routes as deployments;
+pub use jobs::init_routes as jobs;
 pub use projects::init_routes as projects;
+pub use runtimes::init_routes as runtimes;
+pub use static_files::init_routes as static_files;
+pub use traffic::init_routes as traffic;
+pub use uploads::init_routes as uploads;
 pub use users::init_routes as users;
+pub use webhooks::init_routes as webhooks;
@@ -1,13 +1,15 @@
 use super::utils;
 use crate::{
-    config::CFG,
     errors::ApiError,
+    jobs::{self, PurgeProject},
     models::{Project, ProjectMessage},
+    retention::{self, CollectGarbage},
+    webhooks,
 };
 use actix_session::Session;
-use actix_web::{delete, get, post, put, web, HttpResponse};
-use cloud_storage::Object;
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 use regex::Regex;
+use serde::Deserialize;
 use uuid::Uuid;
 
 lazy_static! {
@@ -66,7 +68,8 @@ async fn update(
 
     let p = Project::find(id.clone())?;
     if p.user_id == user_id {
-        Project::update(id.into_inner(), project.into_inner())?;
+        let project = Project::update(id.into_inner(), project.into_inner())?;
+        webhooks::enqueue(project.id, "project.updated", json!(project))?;
         Ok(utils::success())
     } else {
         Err(ApiError::new(
@@ -77,19 +80,36 @@ async fn update(
 }
 
 #[delete("/projects/{id}")]
-async fn delete(id: web::Path<Uuid>, session: Session) -> Result<HttpResponse, ApiError> {
+async fn delete(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
     let user_id = utils::is_authenticated(&session)?;
 
-    let project = Project::find(id.clone())?;
-    if project.user_id == user_id {
-        Project::delete(id.into_inner())?;
-
-        let files = web::block(move || Object::list_prefix(&CFG.gcp.bucket, &format!("{}/", &project.id))).await?;
-        for file in files {
-            web::block(move || Object::delete(&CFG.gcp.bucket, &file.name)).await?;
-        }
+    // Return the job queued by an earlier attempt at the same request, unless
+    // that attempt failed to delete the project after queueing it
+    let key = utils::idempotency_key(&req)?;
+    let existing = jobs::find_existing(user_id, jobs::PURGE_PROJECT, key.as_deref())?;
+    let project = match (Project::find(id.into_inner()), existing) {
+        (Err(e), Some(job)) if e.status_code == 404 => return Ok(utils::accepted(&job)),
+        (project, _) => project?,
+    };
 
-        Ok(utils::success())
+    if project.user_id == user_id {
+        // Remove the project's files in the background. The job is queued
+        // first so the files are never left behind, and waits for the
+        // project to be deleted before removing them.
+        let job = jobs::enqueue(
+            user_id,
+            jobs::PURGE_PROJECT,
+            &PurgeProject {
+                project_id: project.id,
+            },
+            key,
+        )?;
+        Project::delete(project.id)?;
+        Ok(utils::accepted(&job))
     } else {
         Err(ApiError::new(
             403,
@@ -98,10 +118,111 @@ async fn delete(id: web::Path<Uuid>, session: Session) -> Result<HttpResponse, A
     }
 }
 
+#[derive(Deserialize)]
+struct RetentionMessage {
+    count: Option<i32>,
+    days: Option<i32>,
+}
+
+#[get("/projects/{id}/retention")]
+async fn read_retention(id: web::Path<Uuid>, session: Session) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(id.into_inner())?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission to access resource".to_string(),
+        ));
+    }
+
+    // Deployments which would be removed by the next collection
+    let expired: Vec<_> = retention::expired(&project)?
+        .into_iter()
+        .map(|d| json!({ "id": d.id, "version": d.version, "published_at": d.published_at }))
+        .collect();
+
+    Ok(utils::success_with_data(json!({
+        "count": project.retention_count,
+        "days": project.retention_days,
+        "expired": expired,
+    })))
+}
+
+#[put("/projects/{id}/retention")]
+async fn update_retention(
+    id: web::Path<Uuid>,
+    retention: web::Json<RetentionMessage>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(id.into_inner())?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission to access resource".to_string(),
+        ));
+    }
+
+    if retention.count.map_or(false, |c| c < 1) {
+        return Err(ApiError::new(
+            400,
+            "field 'count' must be at least 1".to_string(),
+        ));
+    }
+    if retention.days.map_or(false, |d| d < 1) {
+        return Err(ApiError::new(
+            400,
+            "field 'days' must be at least 1".to_string(),
+        ));
+    }
+
+    Project::set_retention(project.id, retention.count, retention.days)?;
+    Ok(utils::success())
+}
+
+#[post("/projects/{id}/gc")]
+async fn collect_garbage(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    // Return the job queued by an earlier attempt at the same request
+    let key = utils::idempotency_key(&req)?;
+    if let Some(job) = jobs::find_existing(user_id, jobs::COLLECT_GARBAGE, key.as_deref())? {
+        return Ok(utils::accepted(&job));
+    }
+
+    let project = Project::find(id.into_inner())?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission to access resource".to_string(),
+        ));
+    }
+
+    let job = jobs::enqueue(
+        user_id,
+        jobs::COLLECT_GARBAGE,
+        &CollectGarbage {
+            project_id: project.id,
+            correlation_id: utils::correlation_id(&req),
+        },
+        key,
+    )?;
+    Ok(utils::accepted(&job))
+}
+
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(list);
     cfg.service(create);
     cfg.service(read);
     cfg.service(update);
     cfg.service(delete);
+    cfg.service(read_retention);
+    cfg.service(update_retention);
+    cfg.service(collect_garbage);
 }
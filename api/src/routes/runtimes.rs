@@ -0,0 +1,53 @@
+use super::utils;
+use crate::{errors::ApiError, runtimes::Runtime};
+use actix_web::{delete, get, put, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[get("/internal/runtimes")]
+async fn list(req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    utils::is_runtime(&req)?;
+
+    let runtimes = web::block(Runtime::find_all).await?;
+    Ok(utils::success_with_data(json!(runtimes)))
+}
+
+#[put("/internal/runtimes/{id}")]
+async fn heartbeat(
+    req: HttpRequest,
+    id: web::Path<String>,
+    body: web::Json<Heartbeat>,
+) -> Result<HttpResponse, ApiError> {
+    utils::is_runtime(&req)?;
+
+    let id = id.into_inner();
+    if id.is_empty() || id.len() > 128 {
+        return Err(ApiError::new(
+            400,
+            "runtime id must be between 1 and 128 characters".to_string(),
+        ));
+    }
+
+    let deployments = body.into_inner().deployments;
+    let runtime = web::block(move || Runtime::heartbeat(id, deployments)).await?;
+    Ok(utils::success_with_data(json!(runtime)))
+}
+
+#[delete("/internal/runtimes/{id}")]
+async fn deregister(req: HttpRequest, id: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    utils::is_runtime(&req)?;
+
+    web::block(move || Runtime::deregister(&id)).await?;
+    Ok(utils::success())
+}
+
+#[derive(Deserialize)]
+struct Heartbeat {
+    deployments: Vec<Uuid>,
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list);
+    cfg.service(heartbeat);
+    cfg.service(deregister);
+}
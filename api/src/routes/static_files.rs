@@ -0,0 +1,156 @@
+use super::utils;
+use crate::{
+    config::CFG,
+    errors::ApiError,
+    events::{Event, EventType},
+    models::{Deployment, Project, StaticFile},
+    static_files::{self, Manifest},
+};
+use actix_session::Session;
+use actix_web::{post, put, web, HttpRequest, HttpResponse};
+use uuid::Uuid;
+
+// Maximum size of a manifest
+const MAX_MANIFEST_SIZE: u64 = 4 * 1024 * 1024;
+
+#[post("/projects/{project_id}/deployments/{deployment_id}/static/manifest")]
+async fn negotiate(
+    payload: web::Payload,
+    ids: web::Path<(Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let deployment = Deployment::find(ids.1)?;
+    if deployment.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified deployment does not exist".to_string(),
+        ));
+    }
+
+    let body = utils::read_body(payload, MAX_MANIFEST_SIZE).await?;
+    let manifest: Manifest = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::new(400, format!("invalid manifest: {}", e)))?;
+    let manifest = static_files::validate(manifest)?;
+
+    // Deploying an identical configuration returns the existing deployment,
+    // which only accepts the same static files again
+    if deployment.has_static {
+        let existing: Manifest = StaticFile::find_all(deployment.id)?
+            .into_iter()
+            .map(|f| (f.path, f.hash))
+            .collect();
+        if existing != manifest {
+            return Err(ApiError::new(
+                403,
+                "static files already registered for deployment".to_string(),
+            ));
+        }
+
+        return Ok(utils::success_with_data(json!({ "missing": [] })));
+    }
+
+    let missing = static_files::missing(project.id, &manifest)?;
+    static_files::save_pending(deployment.id, &manifest)?;
+
+    Ok(utils::success_with_data(json!({ "missing": missing })))
+}
+
+#[put("/projects/{project_id}/deployments/{deployment_id}/static/blobs/{hash}")]
+async fn upload_blob(
+    payload: web::Payload,
+    ids: web::Path<(Uuid, Uuid, String)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let deployment = Deployment::find(ids.1)?;
+    if deployment.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified deployment does not exist".to_string(),
+        ));
+    }
+
+    // Only accept blobs which were requested by the deployment's manifest
+    let hash = ids.2.to_lowercase();
+    let manifest = static_files::find_pending(deployment.id)?;
+    let path = match manifest.iter().find(|(_, h)| **h == hash) {
+        Some((p, _)) => p,
+        None => {
+            return Err(ApiError::new(
+                404,
+                "hash is not part of the pending manifest".to_string(),
+            ))
+        }
+    };
+
+    let body = utils::read_body(payload, CFG.uploads.blob).await?;
+    static_files::upload(project.id, &hash, static_files::content_type(path), body).await?;
+
+    Ok(utils::success())
+}
+
+#[post("/projects/{project_id}/deployments/{deployment_id}/static/commit")]
+async fn commit(
+    req: HttpRequest,
+    ids: web::Path<(Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let deployment = Deployment::find(ids.1)?;
+    if deployment.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified deployment does not exist".to_string(),
+        ));
+    } else if deployment.has_static {
+        // Negotiation already checked that the static files are identical
+        return Ok(utils::success());
+    }
+
+    let manifest = static_files::find_pending(deployment.id)?;
+    static_files::commit(project.id, deployment.id, &manifest)?;
+    static_files::remove_pending(deployment.id)?;
+
+    Event::new(
+        EventType::DeploymentPublished,
+        &deployment,
+        utils::correlation_id(&req),
+    )
+    .publish()?;
+
+    Ok(utils::success())
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(negotiate);
+    cfg.service(upload_blob);
+    cfg.service(commit);
+}
@@ -0,0 +1,194 @@
+use super::utils;
+use crate::{
+    errors::ApiError,
+    events::{Event, EventType},
+    models::{Deployment, Project, Traffic, TrafficMessage},
+};
+use actix_session::Session;
+use actix_web::{get, http::HeaderName, post, web, HttpRequest, HttpResponse};
+use uuid::Uuid;
+
+#[get("/projects/{id}/traffic")]
+async fn read(id: web::Path<Uuid>, session: Session) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(id.into_inner())?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let traffic = Traffic::find(project.id)?;
+    Ok(utils::success_with_data(json!(traffic)))
+}
+
+#[post("/projects/{id}/traffic")]
+async fn update(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    traffic: web::Json<TrafficMessage>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(id.into_inner())?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+    let traffic = traffic.into_inner();
+    let current = Traffic::find(project.id)?;
+
+    let stable_id = match (traffic.stable, &current) {
+        (Some(id), _) => id,
+        (None, Some(current)) => current.stable_id,
+        (None, None) => {
+            return Err(ApiError::new(
+                400,
+                "field 'stable' is required when traffic is not yet split".to_string(),
+            ))
+        }
+    };
+    let stable = find_deployment(&project, stable_id)?;
+    if let Some(candidate_id) = traffic.candidate {
+        find_deployment(&project, candidate_id)?;
+        if candidate_id == stable_id {
+            return Err(ApiError::new(
+                400,
+                "candidate must differ from the stable deployment".to_string(),
+            ));
+        }
+    }
+
+    if traffic.weight < 0 || traffic.weight > 100 {
+        return Err(ApiError::new(
+            400,
+            "field 'weight' must be between 0 and 100".to_string(),
+        ));
+    }
+    if traffic.candidate.is_none() && traffic.weight != 0 {
+        return Err(ApiError::new(
+            400,
+            "field 'weight' requires a candidate".to_string(),
+        ));
+    }
+
+    // Headers are matched case insensitively by the runtimes
+    let sticky_header = match traffic.sticky_header {
+        Some(h) => match HeaderName::from_bytes(h.as_bytes()) {
+            Ok(name) if name.as_str().len() <= 64 => Some(name.as_str().to_string()),
+            _ => {
+                return Err(ApiError::new(
+                    400,
+                    "field 'sticky_header' must be a valid header name".to_string(),
+                ))
+            }
+        },
+        None => None,
+    };
+
+    let traffic = Traffic::set(
+        project.id,
+        stable_id,
+        traffic.candidate,
+        traffic.weight,
+        sticky_header,
+    )?;
+    Event::traffic(
+        EventType::TrafficUpdated,
+        &stable,
+        traffic.clone(),
+        utils::correlation_id(&req),
+    )
+    .publish()?;
+
+    Ok(utils::success_with_data(json!(traffic)))
+}
+
+#[post("/projects/{id}/traffic/promote")]
+async fn promote(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(id.into_inner())?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let traffic = find_traffic(&project)?.promote()?;
+    let deployment = Deployment::find(traffic.stable_id)?;
+    Event::traffic(
+        EventType::DeploymentPromoted,
+        &deployment,
+        traffic.clone(),
+        utils::correlation_id(&req),
+    )
+    .publish()?;
+
+    Ok(utils::success_with_data(json!(traffic)))
+}
+
+#[post("/projects/{id}/traffic/abort")]
+async fn abort(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(id.into_inner())?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let traffic = find_traffic(&project)?.abort()?;
+    let deployment = Deployment::find(traffic.stable_id)?;
+    Event::traffic(
+        EventType::TrafficUpdated,
+        &deployment,
+        traffic.clone(),
+        utils::correlation_id(&req),
+    )
+    .publish()?;
+
+    Ok(utils::success_with_data(json!(traffic)))
+}
+
+/// Find a deployment in a project
+fn find_deployment(project: &Project, id: Uuid) -> Result<Deployment, ApiError> {
+    let deployment = Deployment::find(id)?;
+    if deployment.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified deployment does not exist".to_string(),
+        ));
+    }
+
+    Ok(deployment)
+}
+
+/// Find how a project's traffic is split, which must have been configured
+fn find_traffic(project: &Project) -> Result<Traffic, ApiError> {
+    Traffic::find(project.id)?
+        .ok_or_else(|| ApiError::new(404, "project traffic has not been split".to_string()))
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(read);
+    cfg.service(update);
+    cfg.service(promote);
+    cfg.service(abort);
+}
@@ -0,0 +1,312 @@
+use super::utils;
+use crate::{
+    archive::{self, Format},
+    config::CFG,
+    errors::ApiError,
+    jobs::{self, IngestStatic},
+    models::{Deployment, Project, Upload},
+    static_files,
+};
+use actix_session::Session;
+use actix_web::{delete, head, patch, post, web, HttpRequest, HttpResponse};
+use mime::Mime;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, SeekFrom};
+use uuid::Uuid;
+
+// Version of the tus protocol implemented
+static TUS_VERSION: &str = "1.0.0";
+
+#[post("/projects/{project_id}/deployments/{deployment_id}/uploads")]
+async fn create(
+    req: HttpRequest,
+    ids: web::Path<(Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let deployment = Deployment::find(ids.1)?;
+    if deployment.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified deployment does not exist".to_string(),
+        ));
+    } else if deployment.has_static {
+        return Err(ApiError::new(
+            403,
+            "static files already registered for deployment".to_string(),
+        ));
+    }
+
+    let length: u64 = header(&req, "Upload-Length")?
+        .parse()
+        .map_err(|_| ApiError::new(400, "invalid header 'Upload-Length'".to_string()))?;
+    if length > CFG.uploads.archive {
+        return Err(ApiError::new(
+            413,
+            format!(
+                "upload exceeds maximum size of {} bytes",
+                CFG.uploads.archive
+            ),
+        ));
+    }
+
+    // The archive format is given by the `filetype` metadata
+    let content_type = metadata(header(&req, "Upload-Metadata")?, "filetype")
+        .ok_or_else(|| ApiError::new(400, "metadata 'filetype' is required".to_string()))?;
+    let valid = content_type
+        .parse::<Mime>()
+        .ok()
+        .and_then(|m| Format::from_mime(&m))
+        .is_some();
+    if !valid {
+        return Err(ApiError::new(
+            400,
+            "upload must be a zip, tar, tar.gz, or tar.zst archive".to_string(),
+        ));
+    }
+
+    let upload = Upload::create(deployment.id, content_type, length as i64)?;
+    let path = static_files::upload_path(upload.id);
+    web::block(move || {
+        fs::create_dir_all(&CFG.uploads.directory)?;
+        File::create(path)
+    })
+    .await?;
+
+    Ok(HttpResponse::Created()
+        .header("Tus-Resumable", TUS_VERSION)
+        .header(
+            "Location",
+            format!(
+                "/projects/{}/deployments/{}/uploads/{}",
+                project.id, deployment.id, upload.id
+            ),
+        )
+        .json(json!({ "success": true, "data": { "id": upload.id } })))
+}
+
+#[head("/projects/{project_id}/deployments/{deployment_id}/uploads/{upload_id}")]
+async fn progress(
+    ids: web::Path<(Uuid, Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let upload = Upload::find(ids.2)?;
+    if upload.deployment_id != ids.1 || Deployment::find(ids.1)?.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified upload does not exist".to_string(),
+        ));
+    }
+
+    Ok(HttpResponse::Ok()
+        .header("Tus-Resumable", TUS_VERSION)
+        .header("Upload-Offset", upload.received.to_string())
+        .header("Upload-Length", upload.length.to_string())
+        .header("Cache-Control", "no-store")
+        .finish())
+}
+
+#[patch("/projects/{project_id}/deployments/{deployment_id}/uploads/{upload_id}")]
+async fn append(
+    req: HttpRequest,
+    payload: web::Payload,
+    ids: web::Path<(Uuid, Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let upload = Upload::find(ids.2)?;
+    if upload.deployment_id != ids.1 || Deployment::find(ids.1)?.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified upload does not exist".to_string(),
+        ));
+    }
+
+    if header(&req, "Content-Type")? != "application/offset+octet-stream" {
+        return Err(ApiError::new(
+            415,
+            "content type must be 'application/offset+octet-stream'".to_string(),
+        ));
+    }
+    let offset: i64 = header(&req, "Upload-Offset")?
+        .parse()
+        .map_err(|_| ApiError::new(400, "invalid header 'Upload-Offset'".to_string()))?;
+    if offset < 0 || offset > upload.length {
+        return Err(ApiError::new(
+            400,
+            "invalid header 'Upload-Offset'".to_string(),
+        ));
+    }
+
+    // Receive the whole chunk before writing so an interrupted request
+    // leaves the upload at its previous offset
+    let mut chunk = archive::receive(payload, (upload.length - offset) as u64).await?;
+    let path = static_files::upload_path(upload.id);
+    let received = web::block(move || {
+        let size = chunk.metadata()?.len() as i64;
+        upload.append(offset, size, || {
+            let mut file = OpenOptions::new().write(true).open(&path)?;
+            file.set_len(offset as u64)?;
+            file.seek(SeekFrom::Start(offset as u64))?;
+            io::copy(&mut chunk, &mut file)?;
+            Ok(())
+        })
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent()
+        .header("Tus-Resumable", TUS_VERSION)
+        .header("Upload-Offset", received.to_string())
+        .finish())
+}
+
+#[post("/projects/{project_id}/deployments/{deployment_id}/uploads/{upload_id}/finalize")]
+async fn finalize(
+    req: HttpRequest,
+    ids: web::Path<(Uuid, Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let deployment = Deployment::find(ids.1)?;
+    if deployment.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified deployment does not exist".to_string(),
+        ));
+    }
+
+    // Return the job queued by an earlier attempt at the same request
+    let key = utils::idempotency_key(&req)?;
+    if let Some(job) = jobs::find_existing(user_id, jobs::INGEST_STATIC, key.as_deref())? {
+        return Ok(utils::accepted(&job));
+    }
+
+    if deployment.has_static {
+        return Err(ApiError::new(
+            403,
+            "static files already registered for deployment".to_string(),
+        ));
+    }
+
+    let upload = Upload::find(ids.2)?;
+    if upload.deployment_id != deployment.id {
+        return Err(ApiError::new(
+            404,
+            "specified upload does not exist".to_string(),
+        ));
+    } else if !upload.is_complete() {
+        return Err(ApiError::new(
+            409,
+            format!(
+                "upload is incomplete, received {} of {} bytes",
+                upload.received, upload.length
+            ),
+        ));
+    }
+
+    let job = jobs::enqueue_ingest(
+        user_id,
+        &IngestStatic {
+            upload_id: upload.id,
+            correlation_id: utils::correlation_id(&req),
+        },
+        key,
+    )?;
+    Ok(utils::accepted(&job))
+}
+
+#[delete("/projects/{project_id}/deployments/{deployment_id}/uploads/{upload_id}")]
+async fn terminate(
+    ids: web::Path<(Uuid, Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let upload = Upload::find(ids.2)?;
+    if upload.deployment_id != ids.1 || Deployment::find(ids.1)?.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified upload does not exist".to_string(),
+        ));
+    }
+
+    static_files::remove_upload(upload.id).await?;
+    Ok(HttpResponse::NoContent()
+        .header("Tus-Resumable", TUS_VERSION)
+        .finish())
+}
+
+/// Get the value of a required header
+fn header<'a>(req: &'a HttpRequest, name: &str) -> Result<&'a str, ApiError> {
+    req.headers()
+        .get(name)
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| ApiError::new(400, format!("header '{}' is required", name)))
+}
+
+/// Find a value in tus metadata, a comma separated list of keys and
+/// base64 encoded values
+fn metadata(header: &str, key: &str) -> Option<String> {
+    header.split(',').find_map(|pair| {
+        let mut parts = pair.trim().splitn(2, ' ');
+        if parts.next()? != key {
+            return None;
+        }
+
+        let value = base64::decode(parts.next()?.trim()).ok()?;
+        String::from_utf8(value).ok()
+    })
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(create);
+    cfg.service(progress);
+    cfg.service(append);
+    cfg.service(finalize);
+    cfg.service(terminate);
+}
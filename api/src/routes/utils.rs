@@ -1,6 +1,8 @@
-use crate::errors::ApiError;
+use crate::{config::CFG, errors::ApiError, models::Job};
 use actix_session::Session;
-use actix_web::HttpResponse;
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures::StreamExt;
+use ring::constant_time;
 use uuid::Uuid;
 
 /// Get the authenticated user's ID
@@ -13,6 +15,61 @@ pub fn is_authenticated(session: &Session) -> Result<Uuid, ApiError> {
     }
 }
 
+/// Ensure the request comes from a runtime using the configured bearer token
+pub fn is_runtime(req: &HttpRequest) -> Result<(), ApiError> {
+    let token = req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .filter(|h| h.starts_with("Bearer "))
+        .map(|h| &h["Bearer ".len()..]);
+
+    match token {
+        Some(t)
+            if !CFG.runtimes.token.is_empty()
+                && constant_time::verify_slices_are_equal(
+                    t.as_bytes(),
+                    CFG.runtimes.token.as_bytes(),
+                )
+                .is_ok() =>
+        {
+            Ok(())
+        }
+        _ => Err(ApiError::new(401, "unauthorized".to_string())),
+    }
+}
+
+/// Get the id used to correlate events with the request which caused them,
+/// taken from the `X-Request-Id` header if present
+pub fn correlation_id(req: &HttpRequest) -> String {
+    req.headers()
+        .get("X-Request-Id")
+        .and_then(|h| h.to_str().ok())
+        .filter(|h| !h.is_empty() && h.len() <= 128)
+        .map(|h| h.to_string())
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// Get the idempotency key for a request from the `Idempotency-Key` header
+pub fn idempotency_key(req: &HttpRequest) -> Result<Option<String>, ApiError> {
+    match req.headers().get("Idempotency-Key") {
+        Some(h) => h
+            .to_str()
+            .ok()
+            .filter(|k| !k.is_empty() && k.len() <= 255)
+            .map(|k| Some(k.to_string()))
+            .ok_or_else(|| ApiError::new(400, "invalid header 'Idempotency-Key'".to_string())),
+        None => Ok(None),
+    }
+}
+
+/// Response for a request whose work was queued as a background job
+pub fn accepted(job: &Job) -> HttpResponse {
+    HttpResponse::Accepted()
+        .header("Location", format!("/jobs/{}", job.id))
+        .json(json!({ "success": true, "data": { "job": job.id } }))
+}
+
 /// Generic success message
 pub fn success() -> HttpResponse {
     HttpResponse::Ok().json(json!({ "success": true }))
@@ -22,3 +79,36 @@ pub fn success() -> HttpResponse {
 pub fn success_with_data(data: serde_json::Value) -> HttpResponse {
     HttpResponse::Ok().json(json!({ "success": true, "data": data }))
 }
+
+/// Content disposition for a download, keeping only characters which are
+/// safe within a quoted file name
+pub fn attachment(file_name: &str) -> String {
+    let file_name: String = file_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "+-._".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("attachment; filename=\"{}\"", file_name)
+}
+
+/// Read a request body into memory, failing if it exceeds the limit
+pub async fn read_body(mut payload: web::Payload, limit: u64) -> Result<Vec<u8>, ApiError> {
+    let mut body = Vec::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk =
+            chunk.map_err(|e| ApiError::new(400, format!("failed to read request body: {}", e)))?;
+        if (body.len() + chunk.len()) as u64 > limit {
+            return Err(ApiError::new(
+                413,
+                format!("request body exceeds {} bytes", limit),
+            ));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
@@ -0,0 +1,169 @@
+use super::utils;
+use crate::{
+    errors::ApiError,
+    models::{Project, Webhook, WebhookDelivery, WebhookMessage},
+    webhooks,
+};
+use actix_session::Session;
+use actix_web::{delete, get, post, web, HttpResponse};
+use uuid::Uuid;
+
+// Number of deliveries shown in a webhook's history
+const HISTORY_LENGTH: i64 = 100;
+
+#[get("/projects/{id}/webhooks")]
+async fn list(id: web::Path<Uuid>, session: Session) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(id.into_inner())?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let hooks = Webhook::find_all(project.id)?;
+    Ok(utils::success_with_data(json!(hooks)))
+}
+
+#[post("/projects/{id}/webhooks")]
+async fn create(
+    id: web::Path<Uuid>,
+    webhook: web::Json<WebhookMessage>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(id.into_inner())?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let mut webhook = webhook.into_inner();
+    webhooks::validate(&webhook.url, &webhook.events)?;
+    let url = webhook.url.clone();
+    web::block(move || webhooks::check_destination(&url)).await?;
+    let secret = match webhook.secret.take() {
+        Some(s) if s.is_empty() => {
+            return Err(ApiError::new(
+                400,
+                "webhook secret must not be empty".to_string(),
+            ))
+        }
+        Some(s) => s,
+        None => webhooks::generate_secret(),
+    };
+
+    // The secret is only shown when the webhook is created
+    let webhook = Webhook::create(project.id, webhook, secret)?;
+    let mut data = json!(webhook);
+    data["secret"] = json!(webhook.secret);
+    Ok(utils::success_with_data(data))
+}
+
+#[get("/projects/{project_id}/webhooks/{webhook_id}")]
+async fn read(ids: web::Path<(Uuid, Uuid)>, session: Session) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let webhook = find_webhook(&project, ids.1)?;
+    Ok(utils::success_with_data(json!(webhook)))
+}
+
+#[delete("/projects/{project_id}/webhooks/{webhook_id}")]
+async fn delete(ids: web::Path<(Uuid, Uuid)>, session: Session) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let webhook = find_webhook(&project, ids.1)?;
+    Webhook::delete(webhook.id)?;
+    Ok(utils::success())
+}
+
+#[get("/projects/{project_id}/webhooks/{webhook_id}/deliveries")]
+async fn deliveries(
+    ids: web::Path<(Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let webhook = find_webhook(&project, ids.1)?;
+    let history = WebhookDelivery::find_all(webhook.id, HISTORY_LENGTH)?;
+    Ok(utils::success_with_data(json!(history)))
+}
+
+#[post("/projects/{project_id}/webhooks/{webhook_id}/deliveries/{delivery_id}/redeliver")]
+async fn redeliver(
+    ids: web::Path<(Uuid, Uuid, Uuid)>,
+    session: Session,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = utils::is_authenticated(&session)?;
+
+    let project = Project::find(ids.0)?;
+    if project.user_id != user_id {
+        return Err(ApiError::new(
+            403,
+            "user lacks permission for resource".to_string(),
+        ));
+    }
+
+    let webhook = find_webhook(&project, ids.1)?;
+    let delivery = WebhookDelivery::find(ids.2)?;
+    if delivery.webhook_id != webhook.id {
+        return Err(ApiError::new(
+            404,
+            "specified delivery does not exist".to_string(),
+        ));
+    }
+
+    // Send the same payload again as a new delivery, keeping the history of the original
+    let redelivery = WebhookDelivery::create(webhook.id, delivery.event, delivery.payload)?;
+    Ok(utils::success_with_data(json!(redelivery)))
+}
+
+/// Find a webhook belonging to a project
+fn find_webhook(project: &Project, id: Uuid) -> Result<Webhook, ApiError> {
+    let webhook = Webhook::find(id)?;
+    if webhook.project_id != project.id {
+        return Err(ApiError::new(
+            404,
+            "specified webhook does not exist".to_string(),
+        ));
+    }
+    Ok(webhook)
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list);
+    cfg.service(create);
+    cfg.service(read);
+    cfg.service(delete);
+    cfg.service(deliveries);
+    cfg.service(redeliver);
+}
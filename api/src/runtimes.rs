@@ -0,0 +1,154 @@
+use crate::{config::CFG, errors::ApiError, events};
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Sorted set of runtime ids scored by the time of their last heartbeat
+static REGISTRY: &str = "runtimes";
+
+// Remove a runtime and its consumer group only if it hasn't sent a heartbeat
+// since it was found to be stale, as runtimes keep their name across restarts
+static REMOVE_STALE: &str = r#"
+local seen = redis.call("zscore", KEYS[1], ARGV[1])
+if not seen or tonumber(seen) > tonumber(ARGV[2]) then
+    return 0
+end
+
+redis.call("zrem", KEYS[1], ARGV[1])
+if redis.call("exists", KEYS[2]) == 1 then
+    redis.call("xgroup", "destroy", KEYS[2], ARGV[1])
+end
+return 1
+"#;
+
+/// A runtime serving deployments, as reported by its last heartbeat
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Runtime {
+    pub id: String,
+    pub deployments: Vec<Uuid>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// How many of the live runtimes are serving a deployment
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Rollout {
+    pub serving: usize,
+    pub total: usize,
+    pub runtimes: Vec<String>,
+    pub status: String,
+}
+
+impl Runtime {
+    /// Register or refresh a runtime along with the deployments it has loaded
+    pub fn heartbeat(id: String, deployments: Vec<Uuid>) -> Result<Self, ApiError> {
+        let runtime = Runtime {
+            id,
+            deployments,
+            last_seen: Utc::now(),
+        };
+        let payload = serde_json::to_string(&runtime)
+            .map_err(|e| ApiError::new(500, format!("failed to encode runtime: {}", e)))?;
+
+        let mut connection = crate::redis::connection()?;
+        connection.set_ex::<_, _, ()>(key(&runtime.id), payload, CFG.runtimes.expiry as usize)?;
+        connection.zadd::<_, _, _, ()>(REGISTRY, &runtime.id, runtime.last_seen.timestamp())?;
+        Ok(runtime)
+    }
+
+    /// Retrieve all runtimes which have sent a heartbeat recently
+    pub fn find_all() -> Result<Vec<Self>, ApiError> {
+        let mut connection = crate::redis::connection()?;
+
+        let ids: Vec<String> =
+            connection.zrangebyscore(REGISTRY, format!("({}", expiry_cutoff()), "+inf")?;
+        let mut runtimes = Vec::with_capacity(ids.len());
+        for id in ids {
+            let payload: Option<String> = connection.get(key(&id))?;
+            if let Some(runtime) = payload.and_then(|p| serde_json::from_str(&p).ok()) {
+                runtimes.push(runtime);
+            }
+        }
+
+        Ok(runtimes)
+    }
+
+    /// Forget runtimes which stopped without deregistering, along with their
+    /// consumer groups. Returns the number of runtimes removed.
+    pub fn remove_expired() -> Result<usize, ApiError> {
+        let mut connection = crate::redis::connection()?;
+
+        let cutoff = expiry_cutoff();
+        let stale: Vec<String> = connection.zrangebyscore(REGISTRY, "-inf", cutoff)?;
+
+        let script = redis::Script::new(REMOVE_STALE);
+        let mut removed = 0;
+        for id in stale {
+            let was_removed: bool = script
+                .key(REGISTRY)
+                .key(events::STREAM)
+                .arg(&id)
+                .arg(cutoff)
+                .invoke(&mut connection)?;
+            if was_removed {
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Remove a runtime from the registry along with its consumer group
+    pub fn deregister(id: &str) -> Result<(), ApiError> {
+        let mut connection = crate::redis::connection()?;
+        events::remove_group(&mut connection, id)?;
+        connection.del::<_, ()>(key(id))?;
+        connection.zrem::<_, _, ()>(REGISTRY, id)?;
+        Ok(())
+    }
+}
+
+impl Rollout {
+    /// Get the rollout status of a deployment across the live runtimes
+    pub fn find(deployment_id: Uuid) -> Result<Self, ApiError> {
+        let runtimes = Runtime::find_all()?;
+        let total = runtimes.len();
+        let serving: Vec<String> = runtimes
+            .into_iter()
+            .filter(|r| r.deployments.contains(&deployment_id))
+            .map(|r| r.id)
+            .collect();
+
+        Ok(Rollout {
+            serving: serving.len(),
+            total,
+            status: format!("{}/{} runtimes serving", serving.len(), total),
+            runtimes: serving,
+        })
+    }
+}
+
+/// Remove runtimes which stopped sending heartbeats at a regular interval,
+/// should be spawned when the server starts. Runtimes are only removed if
+/// they are still stale, so every instance of the API can run this.
+pub async fn schedule_expiry() {
+    loop {
+        let removed = web::block(Runtime::remove_expired).await;
+        if let Err(e) = removed.map_err(ApiError::from) {
+            error!("failed to remove expired runtimes: {}", e);
+        }
+
+        actix_rt::time::delay_for(std::time::Duration::from_secs(CFG.runtimes.expiry.max(1))).await;
+    }
+}
+
+/// Latest time a runtime's last heartbeat can be at before it is considered gone
+fn expiry_cutoff() -> i64 {
+    Utc::now().timestamp() - CFG.runtimes.expiry as i64
+}
+
+/// The key a runtime's heartbeat is stored at
+fn key(id: &str) -> String {
+    format!("runtime:{}", id)
+}
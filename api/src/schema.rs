@@ -1,3 +1,14 @@
+table! {
+    aliases (id) {
+        id -> Uuid,
+        project_id -> Uuid,
+        name -> Varchar,
+        deployment_id -> Uuid,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 table! {
     deployments (id) {
         id -> Uuid,
@@ -6,6 +17,9 @@ table! {
         hash -> Bpchar,
         has_static -> Bool,
         published_at -> Timestamp,
+        source -> Nullable<Jsonb>,
+        pinned -> Bool,
+        normalized_version -> Nullable<Varchar>,
     }
 }
 
@@ -22,6 +36,26 @@ table! {
     }
 }
 
+table! {
+    jobs (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        kind -> Varchar,
+        payload -> Jsonb,
+        status -> Varchar,
+        attempts -> Int4,
+        max_attempts -> Int4,
+        idempotency_key -> Nullable<Varchar>,
+        result -> Nullable<Jsonb>,
+        error -> Nullable<Text>,
+        run_at -> Timestamp,
+        locked_until -> Timestamp,
+        created_at -> Timestamp,
+        started_at -> Nullable<Timestamp>,
+        finished_at -> Nullable<Timestamp>,
+    }
+}
+
 table! {
     projects (id) {
         id -> Uuid,
@@ -30,6 +64,8 @@ table! {
         description -> Text,
         created_at -> Timestamp,
         updated_at -> Nullable<Timestamp>,
+        retention_count -> Nullable<Int4>,
+        retention_days -> Nullable<Int4>,
     }
 }
 
@@ -43,6 +79,52 @@ table! {
     }
 }
 
+table! {
+    static_blobs (project_id, hash) {
+        project_id -> Uuid,
+        hash -> Bpchar,
+        size -> Int8,
+        content_type -> Text,
+        reference_count -> Int4,
+        created_at -> Timestamp,
+        uploaded -> Bool,
+        leased_until -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    static_files (id) {
+        id -> Uuid,
+        deployment_id -> Uuid,
+        path -> Text,
+        hash -> Bpchar,
+        size -> Int8,
+        content_type -> Text,
+    }
+}
+
+table! {
+    traffic (project_id) {
+        project_id -> Uuid,
+        stable_id -> Uuid,
+        candidate_id -> Nullable<Uuid>,
+        weight -> Int4,
+        sticky_header -> Nullable<Varchar>,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    uploads (id) {
+        id -> Uuid,
+        deployment_id -> Uuid,
+        content_type -> Text,
+        length -> Int8,
+        received -> Int8,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     users (id) {
         id -> Uuid,
@@ -53,15 +135,60 @@ table! {
     }
 }
 
+table! {
+    webhook_deliveries (id) {
+        id -> Uuid,
+        webhook_id -> Uuid,
+        event -> Varchar,
+        payload -> Jsonb,
+        status -> Varchar,
+        attempts -> Int4,
+        next_attempt_at -> Nullable<Timestamp>,
+        response_status -> Nullable<Int4>,
+        response_body -> Nullable<Text>,
+        error -> Nullable<Text>,
+        created_at -> Timestamp,
+        completed_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    webhooks (id) {
+        id -> Uuid,
+        project_id -> Uuid,
+        url -> Text,
+        events -> Array<Text>,
+        secret -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+joinable!(aliases -> deployments (deployment_id));
+joinable!(aliases -> projects (project_id));
 joinable!(deployments -> projects (project_id));
 joinable!(handlers -> deployments (deployment_id));
+joinable!(jobs -> users (user_id));
 joinable!(projects -> users (user_id));
 joinable!(routes -> deployments (deployment_id));
+joinable!(static_blobs -> projects (project_id));
+joinable!(static_files -> deployments (deployment_id));
+joinable!(traffic -> projects (project_id));
+joinable!(uploads -> deployments (deployment_id));
+joinable!(webhook_deliveries -> webhooks (webhook_id));
+joinable!(webhooks -> projects (project_id));
 
 allow_tables_to_appear_in_same_query!(
+    aliases,
     deployments,
     handlers,
+    jobs,
     projects,
     routes,
+    static_blobs,
+    static_files,
+    traffic,
+    uploads,
     users,
+    webhook_deliveries,
+    webhooks,
 );
@@ -0,0 +1,656 @@
+use crate::{
+    archive::{self, Format},
+    config::CFG,
+    errors::ApiError,
+    jobs,
+    models::{Deployment, StaticBlob, StaticFile, Upload},
+    progress::{self, Progress, State, Validation},
+    redis, storage,
+};
+use ::redis::Commands;
+use actix_web::web;
+use chrono::{Duration, Utc};
+use ring::digest;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+// How long a resumable upload can take before being discarded
+const UPLOAD_EXPIRY_HOURS: i64 = 24;
+
+// Seconds between checks for expired uploads
+const UPLOAD_EXPIRY_INTERVAL: u64 = 60 * 60;
+
+// How long a stored blob can be without a record before it is deleted
+const ORPHAN_GRACE_HOURS: i64 = 24;
+
+// How long a negotiated manifest waits for its blobs before being discarded
+const PENDING_MANIFEST_TTL: usize = 24 * 60 * 60;
+
+/// A deployment's static files as a mapping of path to SHA-256 hash
+pub type Manifest = BTreeMap<String, String>;
+
+/// Add a file to a deployment's manifest, only uploading its contents if
+/// no other deployment in the project already references them
+pub async fn store(
+    project_id: Uuid,
+    deployment_id: Uuid,
+    path: String,
+    data: Vec<u8>,
+) -> Result<(), ApiError> {
+    let hash = hash(&data);
+    let mime = content_type(&path);
+
+    let blob = StaticBlob::acquire(project_id, hash.clone(), data.len() as i64, mime.clone())?;
+
+    // Another writer may still be uploading the same contents, so every
+    // writer which finds them missing uploads them. Uploads of the same
+    // contents to the same key are idempotent.
+    if !blob.uploaded {
+        let key = storage::blob_key(project_id, &hash);
+        let result = web::block(move || storage::backend().put(&key, &data, &mime))
+            .await
+            .map_err(ApiError::from)
+            .and_then(|_| StaticBlob::mark_uploaded(project_id, &hash));
+        if let Err(e) = result {
+            StaticBlob::release(project_id, &hash)?;
+            return Err(e);
+        }
+    }
+
+    let created = StaticFile::create(
+        deployment_id,
+        path,
+        hash.clone(),
+        blob.size,
+        blob.content_type,
+    );
+    if let Err(e) = created {
+        StaticBlob::release(project_id, &hash)?;
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+/// Extract an uploaded archive and add its contents to a deployment's manifest
+pub async fn ingest(
+    project_id: Uuid,
+    deployment_id: Uuid,
+    format: Format,
+    file: File,
+) -> Result<(), ApiError> {
+    progress::emit(
+        deployment_id,
+        Progress::Validation {
+            status: Validation::Started,
+            message: None,
+        },
+    );
+    let extracted = match web::block(move || archive::extract(format, file)).await {
+        Ok(e) => e,
+        Err(e) => {
+            let e = ApiError::from(e);
+            progress::emit(
+                deployment_id,
+                Progress::Validation {
+                    status: Validation::Failed,
+                    message: Some(e.message.clone()),
+                },
+            );
+            return Err(e);
+        }
+    };
+    progress::emit(
+        deployment_id,
+        Progress::Validation {
+            status: Validation::Passed,
+            message: None,
+        },
+    );
+
+    let entries = extracted
+        .entries
+        .iter()
+        .map(|e| (e.path.clone(), e.file.clone()))
+        .collect();
+
+    store_extracted(project_id, deployment_id, entries).await
+}
+
+/// Add files extracted from an archive to a deployment's manifest, given
+/// their paths within the static directory and locations on disk
+pub async fn store_extracted(
+    project_id: Uuid,
+    deployment_id: Uuid,
+    entries: Vec<(String, PathBuf)>,
+) -> Result<(), ApiError> {
+    progress::emit(deployment_id, Progress::state(State::Uploading));
+
+    let total = entries.len();
+    for (i, (path, file)) in entries.into_iter().enumerate() {
+        let result = match web::block(move || std::fs::read(file)).await {
+            Ok(data) => store(project_id, deployment_id, path.clone(), data).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            progress::emit(
+                deployment_id,
+                Progress::State {
+                    state: State::Failed,
+                    message: Some(e.message.clone()),
+                },
+            );
+            return Err(e);
+        }
+
+        progress::emit(
+            deployment_id,
+            Progress::Upload {
+                path,
+                uploaded: i + 1,
+                total,
+            },
+        );
+    }
+
+    Ok(())
+}
+
+/// Upload files extracted from an archive without adding them to a
+/// deployment, given their paths within the static directory and locations
+/// on disk. Returns the manifest to commit once the deployment exists.
+pub async fn upload_extracted(
+    project_id: Uuid,
+    entries: Vec<(String, PathBuf)>,
+) -> Result<Manifest, ApiError> {
+    let mut manifest = Manifest::new();
+    for (path, file) in entries {
+        let data = web::block(move || std::fs::read(file)).await?;
+        let hash = hash(&data);
+        upload(project_id, &hash, content_type(&path), data).await?;
+        manifest.insert(path, hash);
+    }
+
+    Ok(manifest)
+}
+
+/// Upload the contents of a blob without referencing it from a deployment.
+/// The blob is referenced once a manifest containing it is committed.
+pub async fn upload(
+    project_id: Uuid,
+    expected: &str,
+    content_type: String,
+    data: Vec<u8>,
+) -> Result<(), ApiError> {
+    if hash(&data) != expected {
+        return Err(ApiError::new(
+            400,
+            "uploaded contents do not match hash".to_string(),
+        ));
+    }
+
+    // Lease the blob before uploading so it can't be removed before the
+    // manifest waiting for it is committed
+    let leased_until = Utc::now().naive_utc() + Duration::seconds(PENDING_MANIFEST_TTL as i64);
+    let blob = StaticBlob::register(
+        project_id,
+        expected.to_string(),
+        data.len() as i64,
+        content_type.clone(),
+        leased_until,
+    )?;
+    if blob.uploaded {
+        return Ok(());
+    }
+
+    let key = storage::blob_key(project_id, expected);
+    web::block(move || storage::backend().put(&key, &data, &content_type)).await?;
+    StaticBlob::mark_uploaded(project_id, expected)?;
+    Ok(())
+}
+
+/// Create a deployment's manifest from blobs which have already been
+/// uploaded, marking it as having static files
+pub fn commit(project_id: Uuid, deployment_id: Uuid, manifest: &Manifest) -> Result<(), ApiError> {
+    let missing = missing(project_id, manifest)?;
+    if !missing.is_empty() {
+        return Err(ApiError::new(
+            409,
+            format!("{} blob(s) have not been uploaded", missing.len()),
+        ));
+    }
+
+    StaticFile::create_all(project_id, deployment_id, manifest)
+}
+
+/// Move a deployment's static files from where they were stored before
+/// being addressed by content into its manifest. Files were stored under
+/// `{project}/{deployment}/{path}`, and under `{project}/{path}` before
+/// deployments had their own namespace. Returns the number of files moved.
+pub async fn migrate(deployment: &Deployment) -> Result<usize, ApiError> {
+    if !StaticFile::find_all(deployment.id)?.is_empty() {
+        return Ok(0);
+    }
+
+    let project_prefix = storage::project_prefix(deployment.project_id);
+    let mut prefix = format!("{}{}/", project_prefix, deployment.id);
+    let list_prefix = prefix.clone();
+    let mut objects = web::block(move || storage::backend().list(&list_prefix)).await?;
+    let namespaced = !objects.is_empty();
+
+    // Files shared by all of the project's deployments are left in place for the others
+    if !namespaced {
+        let deployments: HashSet<Uuid> = Deployment::find_all(deployment.project_id)?
+            .into_iter()
+            .map(|d| d.id)
+            .collect();
+        prefix = project_prefix.clone();
+        objects = web::block(move || storage::backend().list(&project_prefix)).await?;
+        objects.retain(|o| is_legacy_path(&o.key[prefix.len()..], &deployments));
+    }
+
+    let mut manifest = Manifest::new();
+    for object in &objects {
+        let path = object.key[prefix.len()..].to_string();
+        let key = object.key.clone();
+        let data = web::block(move || storage::backend().get(&key)).await?;
+
+        let hash = hash(&data);
+        upload(deployment.project_id, &hash, content_type(&path), data).await?;
+        manifest.insert(path, hash);
+    }
+    if manifest.is_empty() {
+        return Ok(0);
+    }
+    commit(deployment.project_id, deployment.id, &manifest)?;
+
+    if namespaced {
+        for object in objects {
+            web::block(move || storage::backend().delete(&object.key)).await?;
+        }
+    }
+
+    Ok(manifest.len())
+}
+
+/// Find the hashes in a manifest whose contents are not yet stored
+pub fn missing(project_id: Uuid, manifest: &Manifest) -> Result<Vec<String>, ApiError> {
+    let hashes: BTreeSet<&String> = manifest.values().collect();
+
+    let mut missing = Vec::new();
+    for hash in hashes {
+        if !StaticBlob::find(project_id, hash)?.map_or(false, |b| b.uploaded) {
+            missing.push(hash.clone());
+        }
+    }
+    Ok(missing)
+}
+
+/// Check that a manifest only contains safe paths and valid hashes,
+/// normalizing each path relative to the static directory
+pub fn validate(manifest: Manifest) -> Result<Manifest, ApiError> {
+    let mut normalized = Manifest::new();
+    for (path, hash) in manifest {
+        let path = normalize_path(&path)
+            .ok_or_else(|| ApiError::new(400, format!("invalid static file path '{}'", path)))?;
+        if !is_hash(&hash) {
+            return Err(ApiError::new(
+                400,
+                format!("invalid hash for static file '{}'", path),
+            ));
+        }
+
+        if normalized
+            .insert(path.clone(), hash.to_lowercase())
+            .is_some()
+        {
+            return Err(ApiError::new(
+                400,
+                format!("duplicate static file path '{}'", path),
+            ));
+        }
+    }
+    Ok(normalized)
+}
+
+/// Save a manifest until its blobs have been uploaded
+pub fn save_pending(deployment_id: Uuid, manifest: &Manifest) -> Result<(), ApiError> {
+    let encoded = serde_json::to_string(manifest)
+        .map_err(|e| ApiError::new(500, format!("failed to encode manifest: {}", e)))?;
+
+    let mut connection = redis::connection()?;
+    connection.set_ex::<_, _, ()>(pending_key(deployment_id), encoded, PENDING_MANIFEST_TTL)?;
+    Ok(())
+}
+
+/// Retrieve a deployment's pending manifest
+pub fn find_pending(deployment_id: Uuid) -> Result<Manifest, ApiError> {
+    let mut connection = redis::connection()?;
+    let encoded: Option<String> = connection.get(pending_key(deployment_id))?;
+
+    match encoded {
+        Some(e) => serde_json::from_str(&e)
+            .map_err(|e| ApiError::new(500, format!("failed to decode manifest: {}", e))),
+        None => Err(ApiError::new(
+            404,
+            "no pending manifest for deployment".to_string(),
+        )),
+    }
+}
+
+/// Discard a deployment's pending manifest
+pub fn remove_pending(deployment_id: Uuid) -> Result<(), ApiError> {
+    let mut connection = redis::connection()?;
+    connection.del::<_, ()>(pending_key(deployment_id))?;
+    Ok(())
+}
+
+/// Delete the contents of any static files no longer used by a deployment
+pub async fn remove_unreferenced(project_id: Uuid) -> Result<usize, ApiError> {
+    let mut removed = 0;
+    for blob in StaticBlob::find_unreferenced(project_id)? {
+        let deleted = web::block(move || {
+            let key = storage::blob_key(project_id, &blob.hash);
+            StaticBlob::delete_unreferenced(project_id, &blob.hash, || {
+                Ok(storage::backend().delete(&key)?)
+            })
+        })
+        .await?;
+        if deleted {
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Delete stored blobs which have no record, such as those left behind by
+/// an upload which failed after storing its contents. Blobs are only
+/// deleted once they have been found without a record for a grace period,
+/// so uploads which are still being registered are not removed.
+pub async fn remove_orphaned(project_id: Uuid) -> Result<usize, ApiError> {
+    let prefix = storage::blob_key(project_id, "");
+    let list_prefix = prefix.clone();
+    let objects = web::block(move || storage::backend().list(&list_prefix)).await?;
+
+    let key = format!("static-orphans:{}", project_id);
+    let mut connection = redis::connection()?;
+    let seen: HashMap<String, i64> = connection.hgetall(&key)?;
+    let now = Utc::now().timestamp();
+
+    let mut orphans = HashMap::new();
+    let mut removed = 0;
+    for object in objects {
+        let hash = object.key[prefix.len()..].to_string();
+        if StaticBlob::find(project_id, &hash)?.is_some() {
+            continue;
+        }
+
+        let first_seen = seen.get(&hash).copied().unwrap_or(now);
+        if now - first_seen >= ORPHAN_GRACE_HOURS * 60 * 60 {
+            web::block(move || storage::backend().delete(&object.key)).await?;
+            removed += 1;
+        } else {
+            orphans.insert(hash, first_seen);
+        }
+    }
+
+    // Only remember blobs which are still orphaned
+    connection.del::<_, ()>(&key)?;
+    if !orphans.is_empty() {
+        let orphans: Vec<(String, i64)> = orphans.into_iter().collect();
+        connection.hset_multiple::<_, _, _, ()>(&key, &orphans)?;
+    }
+
+    Ok(removed)
+}
+
+/// Location of a resumable upload's data
+pub fn upload_path(id: Uuid) -> PathBuf {
+    Path::new(&CFG.uploads.directory).join(id.to_string())
+}
+
+/// Delete a resumable upload and its data
+pub async fn remove_upload(id: Uuid) -> Result<(), ApiError> {
+    remove_upload_data(id).await?;
+    Upload::delete(id)?;
+    Ok(())
+}
+
+async fn remove_upload_data(id: Uuid) -> Result<(), ApiError> {
+    web::block(move || match std::fs::remove_file(upload_path(id)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    })
+    .await?;
+    Ok(())
+}
+
+/// Keep a received archive as a finished upload so it can be ingested later
+pub async fn save_upload(
+    deployment_id: Uuid,
+    content_type: String,
+    mut file: File,
+) -> Result<Upload, ApiError> {
+    let size = file.metadata()?.len() as i64;
+    let upload = Upload::create(deployment_id, content_type, size)?;
+
+    let path = upload_path(upload.id);
+    web::block(move || {
+        upload.append(0, size, || {
+            std::fs::create_dir_all(&CFG.uploads.directory)?;
+            io::copy(&mut file, &mut File::create(&path)?)?;
+            Ok(())
+        })?;
+        Ok::<_, ApiError>(upload)
+    })
+    .await
+    .map_err(ApiError::from)
+}
+
+/// Remove expired uploads at a regular interval, should be spawned when
+/// the server starts. Removing an upload twice is harmless, so every
+/// instance of the API can run this.
+pub async fn schedule_upload_expiry() {
+    loop {
+        if let Err(e) = remove_expired_uploads().await {
+            error!("failed to remove expired uploads: {}", e);
+        }
+
+        actix_rt::time::delay_for(std::time::Duration::from_secs(UPLOAD_EXPIRY_INTERVAL)).await;
+    }
+}
+
+/// Delete resumable uploads which were never finished or ingested, along
+/// with the data of uploads removed by the database with their deployment
+pub async fn remove_expired_uploads() -> Result<(), ApiError> {
+    // Uploads waiting for a job are kept until it has ingested them
+    let pending = jobs::pending_uploads()?;
+
+    let cutoff = Utc::now().naive_utc() - Duration::hours(UPLOAD_EXPIRY_HOURS);
+    for upload in Upload::find_expired(cutoff)? {
+        if !pending.contains(&upload.id) {
+            remove_upload(upload.id).await?;
+        }
+    }
+
+    // Records are always created before their data, so data without a
+    // record belongs to an upload which was removed
+    let stored = web::block(stored_uploads).await?;
+    let existing: HashSet<Uuid> = Upload::find_existing(stored.clone())?.into_iter().collect();
+    for id in stored {
+        if !existing.contains(&id) {
+            remove_upload_data(id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// List the ids of all uploads with stored data
+fn stored_uploads() -> Result<Vec<Uuid>, io::Error> {
+    let entries = match std::fs::read_dir(&CFG.uploads.directory) {
+        Ok(e) => e,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+
+    let mut ids = Vec::new();
+    for entry in entries {
+        let name = entry?.file_name();
+        if let Some(id) = name.to_str().and_then(|n| Uuid::parse_str(n).ok()) {
+            ids.push(id);
+        }
+    }
+    Ok(ids)
+}
+
+/// Normalize a path within the static directory, rejecting any which
+/// would escape it
+pub fn normalize_path(path: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    for part in path.split(|c| c == '/' || c == '\\') {
+        match part {
+            "" | "." => {}
+            ".." => return None,
+            p if p.contains('\0') => return None,
+            p => parts.push(p),
+        }
+    }
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("/"))
+    }
+}
+
+/// Guess the content type of a file from its path
+pub fn content_type(path: &str) -> String {
+    mime_guess::from_path(path)
+        .first_or(mime::APPLICATION_OCTET_STREAM)
+        .to_string()
+}
+
+/// Hash the contents of a file
+pub fn hash(data: &[u8]) -> String {
+    hex::encode(digest::digest(&digest::SHA256, data))
+}
+
+/// Check whether a key within a project was a static file stored before
+/// deployments had their own namespace. Legacy directories may share a name
+/// with a namespace, so only content addressed blobs and the namespaces of
+/// the project's deployments are excluded.
+fn is_legacy_path(path: &str, deployments: &HashSet<Uuid>) -> bool {
+    let mut parts = path.splitn(2, '/');
+    let first = parts.next().unwrap_or_default();
+    let rest = match parts.next() {
+        Some(r) => r,
+        None => return true,
+    };
+
+    if first == "blobs" {
+        return !is_hash(rest);
+    }
+    Uuid::parse_str(first).map_or(true, |id| !deployments.contains(&id))
+}
+
+fn is_hash(value: &str) -> bool {
+    value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn pending_key(deployment_id: Uuid) -> String {
+    format!("static-manifest:{}", deployment_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(normalize_path("index.html").unwrap(), "index.html");
+        assert_eq!(normalize_path("/css//site.css").unwrap(), "css/site.css");
+        assert_eq!(normalize_path("./js/./app.js").unwrap(), "js/app.js");
+        assert_eq!(normalize_path("img\\logo.png").unwrap(), "img/logo.png");
+    }
+
+    #[test]
+    fn rejects_escaping_paths() {
+        for path in &["", "/", "./", "../secret", "a/../../b", "a\\..\\b", "a\0b"] {
+            assert!(
+                normalize_path(path).is_none(),
+                "{:?} should be rejected",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn detects_legacy_paths() {
+        let deployment = Uuid::new_v4();
+        let deployments: HashSet<Uuid> = vec![deployment].into_iter().collect();
+
+        assert!(is_legacy_path("index.html", &deployments));
+        assert!(is_legacy_path("css/site.css", &deployments));
+        assert!(!is_legacy_path(&format!("blobs/{}", HASH), &deployments));
+        assert!(!is_legacy_path(
+            &format!("{}/index.html", deployment),
+            &deployments
+        ));
+
+        // Directories which only look like namespaces are still legacy files
+        assert!(is_legacy_path("blobs/logo.png", &deployments));
+        assert!(is_legacy_path(&format!("blobs/{}/a", HASH), &deployments));
+        assert!(is_legacy_path(
+            &format!("{}/index.html", Uuid::new_v4()),
+            &deployments
+        ));
+        assert!(is_legacy_path("blobs", &deployments));
+    }
+
+    #[test]
+    fn validates_manifests() {
+        let mut manifest = Manifest::new();
+        manifest.insert("/index.html".to_string(), HASH.to_uppercase());
+        manifest.insert("css/./site.css".to_string(), HASH.to_string());
+
+        let validated = validate(manifest).unwrap();
+        assert_eq!(
+            validated.keys().collect::<Vec<_>>(),
+            vec!["css/site.css", "index.html"]
+        );
+        assert!(validated.values().all(|h| h == HASH));
+    }
+
+    #[test]
+    fn rejects_invalid_manifests() {
+        let mut manifest = Manifest::new();
+        manifest.insert("../index.html".to_string(), HASH.to_string());
+        assert_eq!(validate(manifest).unwrap_err().status_code, 400);
+
+        let mut manifest = Manifest::new();
+        manifest.insert("index.html".to_string(), "abc".to_string());
+        assert_eq!(validate(manifest).unwrap_err().status_code, 400);
+
+        let mut manifest = Manifest::new();
+        manifest.insert("index.html".to_string(), HASH.to_string());
+        manifest.insert("./index.html".to_string(), HASH.to_string());
+        let error = validate(manifest).unwrap_err();
+        assert_eq!(error.status_code, 400);
+        assert!(error.message.contains("duplicate"));
+    }
+
+    #[test]
+    fn hashes_contents() {
+        assert_eq!(hash(b"test"), HASH);
+        assert!(is_hash(HASH));
+        assert!(!is_hash(&HASH[1..]));
+        assert!(!is_hash(&HASH.replace('9', "g")));
+    }
+}
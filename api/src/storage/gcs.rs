@@ -0,0 +1,72 @@
+use super::{Storage, StorageError, StorageObject};
+use crate::config;
+use cloud_storage::Object;
+
+/// Google Cloud Storage backend
+pub struct Gcs {
+    bucket: String,
+}
+
+impl Gcs {
+    pub fn new(config: config::Gcs) -> Self {
+        Gcs {
+            bucket: config.bucket,
+        }
+    }
+}
+
+impl Storage for Gcs {
+    fn put(&self, key: &str, data: &[u8], content_type: &str) -> Result<(), StorageError> {
+        Object::create(&self.bucket, data, key, content_type).map_err(error)?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        Object::download(&self.bucket, key).map_err(|e| {
+            if is_not_found(&e) {
+                StorageError::NotFound(key.to_string())
+            } else {
+                error(e)
+            }
+        })
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<StorageObject>, StorageError> {
+        let objects = Object::list_prefix(&self.bucket, prefix).map_err(error)?;
+        Ok(objects
+            .into_iter()
+            .map(|o| StorageObject {
+                key: o.name,
+                size: o.size,
+                hash: o.crc32c,
+            })
+            .collect())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), StorageError> {
+        // Deleting a missing object succeeds like the other backends
+        match Object::delete(&self.bucket, key) {
+            Ok(()) => Ok(()),
+            Err(ref e) if is_not_found(e) => Ok(()),
+            Err(e) => Err(error(e)),
+        }
+    }
+
+    fn copy(&self, from: &str, to: &str, _content_type: &str) -> Result<(), StorageError> {
+        // Copies are made server side and keep the object's content type
+        let object = Object::read(&self.bucket, from).map_err(error)?;
+        object.copy(&self.bucket, to).map_err(error)?;
+        Ok(())
+    }
+}
+
+fn is_not_found(error: &cloud_storage::Error) -> bool {
+    match error {
+        cloud_storage::Error::Google(response) => response.error.code == 404,
+        _ => false,
+    }
+}
+
+fn error(error: cloud_storage::Error) -> StorageError {
+    StorageError::Backend(format!("google cloud storage error: {}", error))
+}
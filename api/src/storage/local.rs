@@ -0,0 +1,224 @@
+use super::{Storage, StorageError, StorageObject};
+use crate::config;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Storage backend using a directory on the local filesystem
+pub struct Local {
+    directory: PathBuf,
+}
+
+impl Local {
+    pub fn new(config: config::Local) -> Self {
+        Local {
+            directory: PathBuf::from(config.directory),
+        }
+    }
+
+    /// Get the path to an object, preventing keys from escaping the directory
+    fn path(&self, key: &str) -> Result<PathBuf, StorageError> {
+        // Components are checked before parsing as paths silently drop `.` and empty segments
+        let relative = Path::new(key);
+        let is_safe = key
+            .split('/')
+            .all(|s| !s.is_empty() && s != "." && s != "..")
+            && relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)));
+        if !is_safe {
+            return Err(StorageError::InvalidKey(key.to_string()));
+        }
+
+        Ok(self.directory.join(relative))
+    }
+
+    /// Recursively collect all files within a directory
+    fn walk(&self, directory: &Path, objects: &mut Vec<StorageObject>) -> Result<(), StorageError> {
+        let entries = match fs::read_dir(directory) {
+            Ok(e) => e,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(error(e)),
+        };
+
+        for entry in entries {
+            let entry = entry.map_err(error)?;
+            let path = entry.path();
+            let metadata = entry.metadata().map_err(error)?;
+            if metadata.is_dir() {
+                self.walk(&path, objects)?;
+                continue;
+            }
+
+            // Hashing every file's contents is too slow for large directories, so
+            // the size and modification time identify a version of the file instead
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_nanos());
+            let key = path
+                .strip_prefix(&self.directory)
+                .unwrap()
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            objects.push(StorageObject {
+                key,
+                size: metadata.len(),
+                hash: format!("{:x}-{:x}", metadata.len(), modified),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Storage for Local {
+    fn put(&self, key: &str, data: &[u8], _content_type: &str) -> Result<(), StorageError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(error)?;
+        }
+        fs::write(path, data).map_err(error)
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        fs::read(self.path(key)?).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => StorageError::NotFound(key.to_string()),
+            _ => error(e),
+        })
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<StorageObject>, StorageError> {
+        // Only walk the deepest directory containing every key with the prefix
+        let directory = match prefix.rfind('/') {
+            Some(i) => self.path(&prefix[..i])?,
+            None => self.directory.clone(),
+        };
+
+        let mut objects = Vec::new();
+        self.walk(&directory, &mut objects)?;
+        objects.retain(|o| o.key.starts_with(prefix));
+        Ok(objects)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match fs::remove_file(self.path(key)?) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(error(e)),
+        }
+    }
+
+    fn copy(&self, from: &str, to: &str, content_type: &str) -> Result<(), StorageError> {
+        let data = self.get(from)?;
+        self.put(to, &data, content_type)
+    }
+}
+
+fn error(error: std::io::Error) -> StorageError {
+    StorageError::Backend(format!("local storage error: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn local() -> (TempDir, Local) {
+        let directory = tempfile::tempdir().unwrap();
+        let local = Local::new(config::Local {
+            directory: directory.path().to_string_lossy().to_string(),
+        });
+        (directory, local)
+    }
+
+    fn keys(mut objects: Vec<StorageObject>) -> Vec<String> {
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        objects.into_iter().map(|o| o.key).collect()
+    }
+
+    #[test]
+    fn rejects_keys_outside_directory() {
+        let (_directory, local) = local();
+        for key in &[
+            "",
+            "../a",
+            "a/../../b",
+            "/etc/passwd",
+            "./a",
+            "a/./b",
+            "a//b",
+            "a/",
+        ] {
+            assert!(
+                matches!(local.path(key), Err(StorageError::InvalidKey(_))),
+                "{} should be rejected",
+                key
+            );
+            assert!(local.put(key, b"", "text/plain").is_err());
+        }
+        assert!(local.path("a/b/c.txt").is_ok());
+    }
+
+    #[test]
+    fn stores_and_copies_objects() {
+        let (_directory, local) = local();
+        local.put("a/b.txt", b"hello", "text/plain").unwrap();
+        assert_eq!(local.get("a/b.txt").unwrap(), b"hello");
+
+        local.copy("a/b.txt", "c/d.txt", "text/plain").unwrap();
+        assert_eq!(local.get("c/d.txt").unwrap(), b"hello");
+
+        local.delete("a/b.txt").unwrap();
+        assert!(matches!(
+            local.get("a/b.txt"),
+            Err(StorageError::NotFound(_))
+        ));
+        assert!(local.delete("a/b.txt").is_ok());
+        assert!(matches!(
+            local.copy("a/b.txt", "e.txt", "text/plain"),
+            Err(StorageError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn lists_objects_by_prefix() {
+        let (_directory, local) = local();
+        for key in &[
+            "p/blobs/1",
+            "p/blobs/2",
+            "p/blobsy/3",
+            "p/other",
+            "q/blobs/4",
+        ] {
+            local.put(key, b"data", "text/plain").unwrap();
+        }
+
+        assert_eq!(
+            keys(local.list("p/blobs/").unwrap()),
+            vec!["p/blobs/1", "p/blobs/2"]
+        );
+        assert_eq!(
+            keys(local.list("p/blobs").unwrap()),
+            vec!["p/blobs/1", "p/blobs/2", "p/blobsy/3"]
+        );
+        assert_eq!(keys(local.list("p/").unwrap()).len(), 4);
+        assert_eq!(keys(local.list("").unwrap()).len(), 5);
+        assert!(local.list("missing/").unwrap().is_empty());
+    }
+
+    #[test]
+    fn identifies_versions_by_size_and_time() {
+        let (_directory, local) = local();
+        local.put("a", b"data", "text/plain").unwrap();
+
+        let objects = local.list("a").unwrap();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].size, 4);
+        assert!(objects[0].hash.starts_with("4-"));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::config::CFG;
+use std::fmt;
+use uuid::Uuid;
+
+mod gcs;
+mod local;
+mod s3;
+
+lazy_static! {
+    static ref BACKEND: Box<dyn Storage> = {
+        let storage = &CFG.storage;
+        let backend: Option<Box<dyn Storage>> = match storage.backend.as_str() {
+            "gcs" => storage
+                .gcs
+                .clone()
+                .map(|c| Box::new(gcs::Gcs::new(c)) as Box<dyn Storage>),
+            "s3" => storage.s3.clone().map(|c| {
+                s3::S3::new(c)
+                    .map(|s| Box::new(s) as Box<dyn Storage>)
+                    .unwrap_or_else(|e| {
+                        println!("Failed to configure s3 storage: {}", e);
+                        std::process::exit(1);
+                    })
+            }),
+            "local" => storage
+                .local
+                .clone()
+                .map(|c| Box::new(local::Local::new(c)) as Box<dyn Storage>),
+            other => {
+                println!(
+                    "Unknown storage backend '{}', expected one of gcs, s3, or local",
+                    other
+                );
+                std::process::exit(1);
+            }
+        };
+
+        backend.unwrap_or_else(|| {
+            println!(
+                "Missing configuration for '{}' storage backend",
+                storage.backend
+            );
+            std::process::exit(1);
+        })
+    };
+}
+
+/// An object stored in a storage backend
+#[derive(Clone, Debug)]
+pub struct StorageObject {
+    pub key: String,
+    pub size: u64,
+    /// Backend specific hash of the object's contents
+    pub hash: String,
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    NotFound(String),
+    InvalidKey(String),
+    Backend(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageError::NotFound(key) => write!(f, "object '{}' does not exist", key),
+            StorageError::InvalidKey(key) => write!(f, "invalid object key '{}'", key),
+            StorageError::Backend(msg) => f.write_str(msg),
+        }
+    }
+}
+
+/// Operations supported by all storage backends. These are blocking and
+/// should be run using `web::block`.
+pub trait Storage: Send + Sync {
+    /// Create or replace an object
+    fn put(&self, key: &str, data: &[u8], content_type: &str) -> Result<(), StorageError>;
+
+    /// Retrieve the contents of an object
+    fn get(&self, key: &str) -> Result<Vec<u8>, StorageError>;
+
+    /// List all objects whose key starts with the prefix
+    fn list(&self, prefix: &str) -> Result<Vec<StorageObject>, StorageError>;
+
+    /// Delete an object
+    fn delete(&self, key: &str) -> Result<(), StorageError>;
+
+    /// Copy an object to a new key. Not every backend can read back the
+    /// content type an object was stored with, so it is passed again.
+    fn copy(&self, from: &str, to: &str, content_type: &str) -> Result<(), StorageError>;
+}
+
+/// Configure the storage backend
+pub fn connect() {
+    lazy_static::initialize(&BACKEND);
+}
+
+/// Retrieve the configured storage backend
+pub fn backend() -> &'static dyn Storage {
+    BACKEND.as_ref()
+}
+
+/// Get the prefix all of a project's files are stored under
+pub fn project_prefix(project_id: Uuid) -> String {
+    format!("{}/", project_id)
+}
+
+/// Get the key a static file's contents are stored under
+pub fn blob_key(project_id: Uuid, hash: &str) -> String {
+    format!("{}/blobs/{}", project_id, hash)
+}
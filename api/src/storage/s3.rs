@@ -0,0 +1,143 @@
+use super::{Storage, StorageError, StorageObject};
+use crate::config;
+use ::s3::{bucket::Bucket, creds::Credentials, region::Region};
+
+/// S3 compatible storage backend, such as AWS S3 or MinIO
+pub struct S3 {
+    bucket: Bucket,
+}
+
+impl S3 {
+    pub fn new(config: config::S3) -> Result<Self, StorageError> {
+        let credentials =
+            Credentials::new(Some(&config.key), Some(&config.secret), None, None, None)
+                .map_err(|e| StorageError::Backend(format!("invalid s3 credentials: {}", e)))?;
+
+        // Custom endpoints are generally self-hosted and only support path-style requests
+        let bucket = match config.endpoint {
+            Some(endpoint) => {
+                let region = Region::Custom {
+                    region: config.region,
+                    endpoint,
+                };
+                Bucket::new_with_path_style(&config.bucket, region, credentials)
+            }
+            None => {
+                let region = config
+                    .region
+                    .parse()
+                    .map_err(|e| StorageError::Backend(format!("invalid s3 region: {}", e)))?;
+                Bucket::new(&config.bucket, region, credentials)
+            }
+        }
+        .map_err(error)?;
+
+        Ok(S3 { bucket })
+    }
+}
+
+impl Storage for S3 {
+    fn put(&self, key: &str, data: &[u8], content_type: &str) -> Result<(), StorageError> {
+        let (_, code) = self
+            .bucket
+            .put_object_with_content_type_blocking(key, data, content_type)
+            .map_err(error)?;
+        check(key, code)
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        let (data, code) = self.bucket.get_object_blocking(key).map_err(error)?;
+        check(key, code)?;
+        Ok(data)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<StorageObject>, StorageError> {
+        let pages = self
+            .bucket
+            .list_blocking(prefix.to_string(), None)
+            .map_err(error)?;
+
+        let mut objects = Vec::new();
+        for (page, code) in pages {
+            check(prefix, code)?;
+            objects.extend(page.contents.into_iter().map(|o| StorageObject {
+                key: o.key,
+                size: o.size,
+                hash: o.e_tag.trim_matches('"').to_string(),
+            }));
+        }
+        Ok(objects)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let (_, code) = self.bucket.delete_object_blocking(key).map_err(error)?;
+        check(key, code)
+    }
+
+    fn copy(&self, from: &str, to: &str, content_type: &str) -> Result<(), StorageError> {
+        // Objects are copied by the server rather than downloaded and uploaded again
+        let mut bucket = self.bucket.clone();
+        bucket.add_header("x-amz-copy-source", &copy_source(&self.bucket.name, from));
+        bucket.add_header("x-amz-metadata-directive", "REPLACE");
+
+        let (_, code) = bucket
+            .put_object_with_content_type_blocking(to, &[], content_type)
+            .map_err(error)?;
+        check(from, code)
+    }
+}
+
+/// Get the source of a copy, percent encoding the key as it is sent in a header
+fn copy_source(bucket: &str, key: &str) -> String {
+    let mut source = format!("/{}/", bucket);
+    for byte in key.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                source.push(byte as char)
+            }
+            _ => source.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    source
+}
+
+/// Convert an unsuccessful status code into an error
+fn check(key: &str, code: u16) -> Result<(), StorageError> {
+    match code {
+        200..=299 => Ok(()),
+        404 => Err(StorageError::NotFound(key.to_string())),
+        code => Err(StorageError::Backend(format!(
+            "s3 request for '{}' failed with status {}",
+            key, code
+        ))),
+    }
+}
+
+fn error(error: ::s3::S3Error) -> StorageError {
+    StorageError::Backend(format!("s3 error: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_copy_sources() {
+        assert_eq!(
+            copy_source("files", "abc/blobs/0123"),
+            "/files/abc/blobs/0123"
+        );
+        assert_eq!(
+            copy_source("files", "abc/my file+1é.txt"),
+            "/files/abc/my%20file%2B1%C3%A9.txt"
+        );
+    }
+
+    #[test]
+    fn maps_status_codes() {
+        assert!(check("a", 200).is_ok());
+        assert!(check("a", 204).is_ok());
+        assert!(matches!(check("a", 404), Err(StorageError::NotFound(k)) if k == "a"));
+        assert!(matches!(check("a", 403), Err(StorageError::Backend(_))));
+    }
+}
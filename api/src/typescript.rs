@@ -0,0 +1,555 @@
+use crate::{
+    errors::ApiError,
+    models::{Deployment, Handler, Project, Route},
+    openapi::{self, PathParameter},
+};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as FmtWrite;
+use std::io::{Cursor, Write};
+use zip::{write::FileOptions, ZipWriter};
+
+// Words which cannot be used as function names in TypeScript, along with
+// the globals and exports the generated client relies on
+static RESERVED: &[&str] = &[
+    "ApiError",
+    "Config",
+    "String",
+    "URL",
+    "encodeURIComponent",
+    "fetch",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "import",
+    "in",
+    "instanceof",
+    "new",
+    "null",
+    "return",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "request",
+];
+
+// Shared request logic included at the top of every client
+static RUNTIME: &str = r#"export interface Config {
+  baseUrl: string;
+  headers?: Record<string, string>;
+  fetch?: typeof fetch;
+}
+
+export class ApiError extends Error {
+  constructor(public status: number, public body: unknown) {
+    super(`request failed with status ${status}`);
+  }
+}
+
+async function request<T>(
+  config: Config,
+  method: string,
+  path: string,
+  query?: Record<string, string | undefined>,
+  headers?: Record<string, string | undefined>,
+  body?: unknown,
+): Promise<T> {
+  const url = new URL(config.baseUrl.replace(/\/$/, "") + path);
+  for (const [key, value] of Object.entries(query || {})) {
+    if (value !== undefined) url.searchParams.set(key, value);
+  }
+
+  const init: RequestInit = { method, headers: { ...config.headers } };
+  for (const [key, value] of Object.entries(headers || {})) {
+    if (value !== undefined) (init.headers as Record<string, string>)[key] = value;
+  }
+  if (body !== undefined) {
+    (init.headers as Record<string, string>)["Content-Type"] = "application/json";
+    init.body = JSON.stringify(body);
+  }
+
+  const response = await (config.fetch || fetch)(url.toString(), init);
+  const isJson = (response.headers.get("Content-Type") || "").includes("application/json");
+  const data = isJson ? await response.json() : await response.text();
+  if (!response.ok) throw new ApiError(response.status, data);
+  return data as T;
+}
+"#;
+
+/// Generate a zip archive containing a TypeScript client for a deployment
+pub fn generate_archive(
+    project: &Project,
+    deployment: &Deployment,
+    routes: &[Route],
+    handlers: &[Handler],
+) -> Result<Vec<u8>, ApiError> {
+    let package = json!({
+        "name": package_name(&project.name),
+        "version": package_version(&deployment.version),
+        "description": format!("Generated client for {} {}", project.name, deployment.version),
+        "main": "index.ts",
+        "types": "index.ts",
+    });
+    let package = serde_json::to_string_pretty(&package)
+        .map_err(|e| ApiError::new(500, format!("failed to encode package manifest: {}", e)))?;
+    let client = generate(project, deployment, routes, handlers);
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    writer.start_file("package.json", FileOptions::default())?;
+    writer.write_all(package.as_bytes())?;
+    writer.start_file("index.ts", FileOptions::default())?;
+    writer.write_all(client.as_bytes())?;
+
+    Ok(writer.finish()?.into_inner())
+}
+
+/// Generate the source of a TypeScript client module for a deployment
+pub fn generate(
+    project: &Project,
+    deployment: &Deployment,
+    routes: &[Route],
+    handlers: &[Handler],
+) -> String {
+    let handlers_by_name: HashMap<&str, &Handler> =
+        handlers.iter().map(|h| (h.name.as_str(), h)).collect();
+
+    let mut source = String::new();
+    writeln!(
+        source,
+        "// Client for {} {}, generated by Backendless. Do not edit.\n",
+        project.name, deployment.version
+    )
+    .unwrap();
+    source.push_str(RUNTIME);
+
+    let mut used = HashSet::new();
+    for route in routes {
+        let handler = match handlers_by_name.get(route.handler.as_str()) {
+            Some(h) => h,
+            None => continue,
+        };
+
+        let (path, parameters) = openapi::parse_path(&route.path);
+        for method in &route.methods {
+            let mut name = if route.methods.len() > 1 {
+                camel_case(&format!("{}_{}", handler.name, method.to_lowercase()))
+            } else {
+                camel_case(&handler.name)
+            };
+            if RESERVED.contains(&name.as_str()) {
+                name.push('_');
+            }
+
+            // Disambiguate multiple routes sharing a single handler, skipping
+            // suffixes which would collide with another handler's name
+            if used.contains(&name) {
+                let mut count = 2;
+                while used.contains(&format!("{}{}", name, count)) {
+                    count += 1;
+                }
+                name = format!("{}{}", name, count);
+            }
+            used.insert(name.clone());
+
+            source.push('\n');
+            source.push_str(&function(&name, method, &path, &parameters, handler));
+        }
+    }
+
+    source
+}
+
+/// Generate the parameter interface and function for a single route method
+fn function(
+    name: &str,
+    method: &str,
+    path: &str,
+    path_parameters: &[PathParameter],
+    handler: &Handler,
+) -> String {
+    let mut fields = Vec::new();
+    for parameter in path_parameters {
+        let kind = match parameter.convertor.as_str() {
+            "int" | "float" => "number",
+            _ => "string",
+        };
+        fields.push(format!("  {}: {};", property(&parameter.name), kind));
+    }
+    if let Some(query) = &handler.query_parameters {
+        let entries: Vec<String> = query
+            .iter()
+            .map(|q| format!("{}?: string", property(q)))
+            .collect();
+        fields.push(format!("  query?: {{ {} }};", entries.join("; ")));
+    }
+    if let Some(headers) = &handler.headers {
+        let entries: Vec<String> = headers
+            .iter()
+            .map(|h| format!("{}?: string", property(h)))
+            .collect();
+        fields.push(format!("  headers?: {{ {} }};", entries.join("; ")));
+    }
+    let body = openapi::request_body(method, &handler.body);
+    if let Some(body) = body {
+        fields.push(format!(
+            "  body: {};",
+            schema_type(&openapi::body_schema(body))
+        ));
+    }
+
+    // Substitute path parameters into a template literal. Parameters using
+    // the `path` convertor may span segments, so each is encoded separately.
+    let mut url = path.to_string();
+    for parameter in path_parameters {
+        let value = format!("String(params[{}])", quote(&parameter.name));
+        let encoded = if parameter.convertor == "path" {
+            format!("{}.split(\"/\").map(encodeURIComponent).join(\"/\")", value)
+        } else {
+            format!("encodeURIComponent({})", value)
+        };
+        url = url.replace(
+            &format!("{{{}}}", parameter.name),
+            &format!("${{{}}}", encoded),
+        );
+    }
+
+    let mut source = String::new();
+    let interface = format!("{}Params", pascal_case(name));
+    if fields.is_empty() {
+        writeln!(source, "export async function {}<T = unknown>(", name).unwrap();
+        writeln!(source, "  config: Config,").unwrap();
+    } else {
+        writeln!(source, "export interface {} {{", interface).unwrap();
+        for field in &fields {
+            writeln!(source, "{}", field).unwrap();
+        }
+        writeln!(source, "}}\n").unwrap();
+        writeln!(source, "export async function {}<T = unknown>(", name).unwrap();
+        writeln!(source, "  config: Config,").unwrap();
+        writeln!(source, "  params: {},", interface).unwrap();
+    }
+    writeln!(source, "): Promise<T> {{").unwrap();
+    writeln!(
+        source,
+        "  return request<T>(config, {}, `{}`, {}, {}, {});",
+        quote(&method.to_uppercase()),
+        url,
+        if handler.query_parameters.is_some() {
+            "params.query"
+        } else {
+            "undefined"
+        },
+        if handler.headers.is_some() {
+            "params.headers"
+        } else {
+            "undefined"
+        },
+        if body.is_some() {
+            "params.body"
+        } else {
+            "undefined"
+        },
+    )
+    .unwrap();
+    writeln!(source, "}}").unwrap();
+
+    source
+}
+
+/// Convert a JSON schema into an equivalent TypeScript type
+fn schema_type(schema: &Value) -> String {
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        return values.join(" | ");
+    }
+
+    match schema.get("type").and_then(Value::as_str) {
+        Some("string") => "string".to_string(),
+        Some("integer") | Some("number") => "number".to_string(),
+        Some("boolean") => "boolean".to_string(),
+        Some("null") => "null".to_string(),
+        Some("array") => match schema.get("items") {
+            Some(items) => format!("Array<{}>", schema_type(items)),
+            None => "unknown[]".to_string(),
+        },
+        Some("object") => {
+            let properties = match schema.get("properties").and_then(Value::as_object) {
+                Some(p) => p,
+                None => return "Record<string, unknown>".to_string(),
+            };
+            let required: Vec<&str> = schema
+                .get("required")
+                .and_then(Value::as_array)
+                .map(|r| r.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+
+            let fields: Vec<String> = properties
+                .iter()
+                .map(|(key, value)| {
+                    let optional = if required.contains(&key.as_str()) {
+                        ""
+                    } else {
+                        "?"
+                    };
+                    format!("{}{}: {}", property(key), optional, schema_type(value))
+                })
+                .collect();
+            format!("{{ {} }}", fields.join("; "))
+        }
+        _ => "unknown".to_string(),
+    }
+}
+
+/// Quote a property name if it is not a valid identifier
+fn property(name: &str) -> String {
+    let valid = name.chars().enumerate().all(|(i, c)| {
+        c == '_' || c == '$' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit())
+    });
+    if valid && !name.is_empty() {
+        name.to_string()
+    } else {
+        quote(name)
+    }
+}
+
+fn quote(value: &str) -> String {
+    Value::String(value.to_string()).to_string()
+}
+
+fn camel_case(name: &str) -> String {
+    let pascal = pascal_case(name);
+    let mut chars = pascal.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => "handler".to_string(),
+    }
+}
+
+fn pascal_case(name: &str) -> String {
+    let mut result: String = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    if result.starts_with(|c: char| c.is_ascii_digit()) {
+        result.insert(0, '_');
+    }
+    result
+}
+
+/// Convert a project name into a valid npm package name, which may only
+/// contain lowercase url-safe characters and can't start with `.` or `_`
+fn package_name(name: &str) -> String {
+    let name: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-._~".contains(c) {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let name = name.trim_start_matches(|c| c == '.' || c == '_');
+
+    let mut name = format!("{}-client", name);
+    name.truncate(214);
+    name
+}
+
+/// Strip the leading `v` from a version so it is valid for npm
+fn package_version(version: &str) -> String {
+    let version = version.trim_start_matches('v');
+    if version.is_empty() {
+        "0.0.0".to_string()
+    } else {
+        version.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn project() -> Project {
+        Project {
+            id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            name: "shop".to_string(),
+            description: String::new(),
+            created_at: Utc::now().naive_utc(),
+            updated_at: None,
+            retention_count: None,
+            retention_days: None,
+        }
+    }
+
+    fn deployment() -> Deployment {
+        Deployment {
+            id: Uuid::nil(),
+            project_id: Uuid::nil(),
+            version: "v1.0.0".to_string(),
+            hash: String::new(),
+            has_static: false,
+            published_at: Utc::now().naive_utc(),
+            source: None,
+            pinned: false,
+            normalized_version: None,
+        }
+    }
+
+    fn route(path: &str, methods: &[&str], handler: &str) -> Route {
+        Route {
+            id: Uuid::nil(),
+            deployment_id: Uuid::nil(),
+            path: path.to_string(),
+            methods: methods.iter().map(|m| m.to_string()).collect(),
+            handler: handler.to_string(),
+        }
+    }
+
+    fn handler(name: &str, body: Option<Value>) -> Handler {
+        Handler {
+            id: Uuid::nil(),
+            deployment_id: Uuid::nil(),
+            name: name.to_string(),
+            query_parameters: None,
+            headers: None,
+            path_parameters: None,
+            body,
+            logic: json!([]),
+        }
+    }
+
+    #[test]
+    fn omits_empty_bodies() {
+        let handler = handler("create_item", Some(json!({ "fields": {} })));
+        let source = function("createItem", "POST", "/items", &[], &handler);
+        assert!(!source.contains("body:"));
+        assert!(source.contains("`/items`, undefined, undefined, undefined);"));
+    }
+
+    #[test]
+    fn types_bodies_and_path_parameters() {
+        let handler = handler(
+            "update_item",
+            Some(json!({
+                "fields": { "name": { "type": "string" }, "count": { "type": "integer" } },
+                "required": ["name"],
+            })),
+        );
+        let (path, parameters) = openapi::parse_path("/items/{int:id}");
+        let source = function("updateItem", "PUT", &path, &parameters, &handler);
+
+        assert!(source.contains("export interface UpdateItemParams {"));
+        assert!(source.contains("  id: number;"));
+        assert!(source.contains("  body: { count?: number; name: string };"));
+        assert!(source.contains("`/items/${encodeURIComponent(String(params[\"id\"]))}`"));
+        assert!(source.contains("params.body);"));
+    }
+
+    #[test]
+    fn encodes_path_segments_separately() {
+        let handler = handler("read_file", None);
+        let (path, parameters) = openapi::parse_path("/files/{path:path}");
+        let source = function("readFile", "GET", &path, &parameters, &handler);
+
+        assert!(source.contains(
+            "`/files/${String(params[\"path\"]).split(\"/\").map(encodeURIComponent).join(\"/\")}`"
+        ));
+    }
+
+    #[test]
+    fn disambiguates_function_names() {
+        let routes = vec![
+            route("/a", &["GET"], "list"),
+            route("/b", &["GET"], "list"),
+            route("/c", &["GET"], "list2"),
+            route("/d", &["GET"], "delete"),
+            route("/e", &["GET"], "fetch"),
+        ];
+        let handlers = vec![
+            handler("list", None),
+            handler("list2", None),
+            handler("delete", None),
+            handler("fetch", None),
+        ];
+        let source = generate(&project(), &deployment(), &routes, &handlers);
+
+        assert!(source.contains("export async function list<"));
+        assert!(source.contains("export async function list2<"));
+        assert!(source.contains("export async function list22<"));
+        assert!(source.contains("export async function delete_<"));
+        assert!(source.contains("export async function fetch_<"));
+    }
+
+    #[test]
+    fn skips_routes_without_handlers() {
+        let routes = vec![route("/a", &["GET"], "missing")];
+        let source = generate(&project(), &deployment(), &routes, &[]);
+        assert!(!source.contains("export async function missing"));
+    }
+
+    #[test]
+    fn converts_schemas() {
+        let schema = json!({ "type": "array", "items": { "enum": ["a", 1] } });
+        assert_eq!(schema_type(&schema), "Array<\"a\" | 1>");
+        assert_eq!(
+            schema_type(&json!({ "type": "object" })),
+            "Record<string, unknown>"
+        );
+        assert_eq!(schema_type(&json!({})), "unknown");
+    }
+
+    #[test]
+    fn quotes_invalid_properties() {
+        assert_eq!(property("name"), "name");
+        assert_eq!(property("content-type"), "\"content-type\"");
+        assert_eq!(property("1st"), "\"1st\"");
+        assert_eq!(property(""), "\"\"");
+    }
+
+    #[test]
+    fn converts_names() {
+        assert_eq!(camel_case("get_user-by id"), "getUserById");
+        assert_eq!(camel_case("__"), "handler");
+        assert_eq!(pascal_case("1st_item"), "_1stItem");
+        assert_eq!(package_name("My Shop"), "my-shop-client");
+        assert_eq!(package_name("_shop.io"), "shop.io-client");
+        assert_eq!(package_version("v1.2.3"), "1.2.3");
+        assert_eq!(package_version(""), "0.0.0");
+    }
+}
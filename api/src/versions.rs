@@ -0,0 +1,235 @@
+//! Semantic versions of deployments.
+//!
+//! Versions follow SemVer 2.0.0 with an optional leading `v`, so `1.2.3`,
+//! `v1.0.0-beta.1`, and `2.0.0+build.5` are all valid. The version is kept
+//! as it was written since it forms part of the deployment's URL, but two
+//! versions which differ only by the `v` or build metadata are the same.
+
+use crate::{errors::ApiError, models::Deployment};
+use semver::{Version, VersionReq};
+use std::cmp::Ordering;
+
+/// Longest version which can be stored
+pub const MAX_LENGTH: usize = 128;
+
+/// Parse a deployment's version
+pub fn parse(version: &str) -> Result<Version, ApiError> {
+    if version.is_empty() {
+        return Err(ApiError::new(
+            400,
+            "field 'version' is required".to_string(),
+        ));
+    }
+    if version.len() > MAX_LENGTH {
+        return Err(ApiError::new(
+            400,
+            format!("field 'version' must be at most {} characters", MAX_LENGTH),
+        ));
+    }
+
+    // Versions are part of the url, so anything that isn't SemVer is rejected
+    let unprefixed = if version.starts_with('v') {
+        &version[1..]
+    } else {
+        version
+    };
+    Version::parse(unprefixed).map_err(|e| {
+        ApiError::new(
+            400,
+            format!(
+                "field 'version' must be a semantic version such as '1.2.3', got '{}': {}",
+                version, e
+            ),
+        )
+    })
+}
+
+/// The form of a version deployments are kept unique by, without a leading
+/// `v` or build metadata
+pub fn normalize(version: &Version) -> String {
+    let mut version = version.clone();
+    version.build.clear();
+    version.to_string()
+}
+
+/// Check that a version is valid and not used by any of a project's deployments
+pub fn validate(version: &str, deployments: &[Deployment]) -> Result<Version, ApiError> {
+    let parsed = parse(version)?;
+
+    let existing = deployments
+        .iter()
+        .find(|d| parse(&d.version).ok().as_ref() == Some(&parsed));
+    if let Some(existing) = existing {
+        return Err(ApiError::new(
+            409,
+            format!(
+                "version '{}' is already used by deployment {} ('{}')",
+                version, existing.id, existing.version
+            ),
+        ));
+    }
+
+    Ok(parsed)
+}
+
+/// Sort deployments by version precedence, highest first. Deployments from
+/// before versions were enforced come last, newest first.
+pub fn sort(deployments: &mut Vec<Deployment>) {
+    deployments.sort_by(|a, b| {
+        match (parse(&a.version).ok(), parse(&b.version).ok()) {
+            (Some(a), Some(b)) => b.cmp(&a),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+        .then_with(|| b.published_at.cmp(&a.published_at))
+    });
+}
+
+/// Find the ready deployment with the highest version matching a range
+/// such as `^1.2`, `~1.2.3`, or `>=1.0.0, <2.0.0`. Pre-releases only match
+/// ranges which name a pre-release of the same version.
+pub fn resolve(range: &str, deployments: Vec<Deployment>) -> Result<Option<Deployment>, ApiError> {
+    let requirement = VersionReq::parse(range)
+        .map_err(|e| ApiError::new(400, format!("invalid version range '{}': {}", range, e)))?;
+
+    let resolved = deployments
+        .into_iter()
+        .filter(|d| d.has_static)
+        .filter_map(|d| parse(&d.version).ok().map(|v| (v, d)))
+        .filter(|(v, _)| requirement.matches(v))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, d)| d);
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use uuid::Uuid;
+
+    fn deployment(version: &str, published_at: i64, has_static: bool) -> Deployment {
+        Deployment {
+            id: Uuid::new_v4(),
+            project_id: Uuid::nil(),
+            version: version.to_string(),
+            hash: String::new(),
+            has_static,
+            published_at: NaiveDateTime::from_timestamp(published_at, 0),
+            source: None,
+            pinned: false,
+            normalized_version: parse(version).ok().map(|v| normalize(&v)),
+        }
+    }
+
+    fn versions(deployments: &[Deployment]) -> Vec<&str> {
+        deployments.iter().map(|d| d.version.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_prefixed_versions() {
+        assert_eq!(parse("v1.2.3").unwrap(), Version::parse("1.2.3").unwrap());
+        assert_eq!(parse("1.0.0-beta.1").unwrap().pre.len(), 2);
+        assert_eq!(parse("").unwrap_err().status_code, 400);
+        assert_eq!(parse("1.2").unwrap_err().status_code, 400);
+        assert_eq!(parse("vv1.2.3").unwrap_err().status_code, 400);
+        assert_eq!(
+            parse(&format!("1.0.0-{}", "a".repeat(MAX_LENGTH)))
+                .unwrap_err()
+                .status_code,
+            400
+        );
+    }
+
+    #[test]
+    fn normalizes_prefix_and_build_metadata() {
+        assert_eq!(normalize(&parse("v1.2.3+build.5").unwrap()), "1.2.3");
+        assert_eq!(normalize(&parse("1.2.3-rc.1").unwrap()), "1.2.3-rc.1");
+    }
+
+    #[test]
+    fn rejects_duplicate_versions() {
+        let existing = vec![deployment("1.0.0", 0, true), deployment("legacy", 0, true)];
+
+        for version in &["1.0.0", "v1.0.0", "1.0.0+build.2"] {
+            let error = validate(version, &existing).unwrap_err();
+            assert_eq!(error.status_code, 409);
+            assert_eq!(
+                error.message,
+                format!(
+                    "version '{}' is already used by deployment {} ('1.0.0')",
+                    version, existing[0].id
+                )
+            );
+        }
+
+        assert!(validate("1.0.1", &existing).is_ok());
+        assert!(validate("1.0.0-rc.1", &existing).is_ok());
+    }
+
+    #[test]
+    fn sorts_by_precedence() {
+        let mut deployments = vec![
+            deployment("legacy", 1, true),
+            deployment("1.0.0", 0, true),
+            deployment("1.0.0-rc.1", 0, true),
+            deployment("v1.10.0", 0, true),
+            deployment("1.2.0", 0, true),
+            deployment("newer-legacy", 2, true),
+        ];
+        sort(&mut deployments);
+        assert_eq!(
+            versions(&deployments),
+            vec![
+                "v1.10.0",
+                "1.2.0",
+                "1.0.0",
+                "1.0.0-rc.1",
+                "newer-legacy",
+                "legacy"
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_highest_matching_version() {
+        let deployments = || {
+            vec![
+                deployment("1.0.0", 0, true),
+                deployment("1.2.0", 0, true),
+                deployment("1.3.0", 0, false),
+                deployment("2.0.0-beta.1", 0, true),
+                deployment("legacy", 0, true),
+            ]
+        };
+
+        let resolved = resolve("^1", deployments()).unwrap().unwrap();
+        assert_eq!(resolved.version, "1.2.0");
+        let resolved = resolve("~1.0", deployments()).unwrap().unwrap();
+        assert_eq!(resolved.version, "1.0.0");
+        assert!(resolve(">=3", deployments()).unwrap().is_none());
+        assert_eq!(
+            resolve("not a range", deployments())
+                .unwrap_err()
+                .status_code,
+            400
+        );
+    }
+
+    #[test]
+    fn resolves_pre_releases_only_when_named() {
+        let deployments = || {
+            vec![
+                deployment("1.0.0", 0, true),
+                deployment("2.0.0-beta.1", 0, true),
+                deployment("2.0.0-beta.2", 0, true),
+            ]
+        };
+
+        let resolved = resolve(">=1", deployments()).unwrap().unwrap();
+        assert_eq!(resolved.version, "1.0.0");
+        let resolved = resolve(">=2.0.0-beta.1", deployments()).unwrap().unwrap();
+        assert_eq!(resolved.version, "2.0.0-beta.2");
+    }
+}
@@ -0,0 +1,458 @@
+//! Outgoing webhooks notifying other services about project events.
+//!
+//! Each delivery is a POST request with a JSON body of the form:
+//!
+//! ```json
+//! {
+//!   "id": "c1a0f6f4-8a8e-4c0e-9d0c-7f3a3b2a1e90",
+//!   "type": "deployment.ready",
+//!   "project_id": "0b5cc8a4-0b4e-4d3a-9a43-3f5c4f2f3c7e",
+//!   "timestamp": "2026-10-19T12:00:00.000000Z",
+//!   "data": { "deployment_id": "5e0f1c3a-7d0c-4a55-8f0a-bd0c2ad0f0a1", "version": "v1.0.0" }
+//! }
+//! ```
+//!
+//! Requests carry the following headers:
+//!
+//! | Header                    | Value                                              |
+//! |---------------------------|----------------------------------------------------|
+//! | `X-Backendless-Event`     | The event type                                     |
+//! | `X-Backendless-Delivery`  | The id of the delivery, unique for each redelivery |
+//! | `X-Backendless-Timestamp` | Unix time the request was signed at                |
+//! | `X-Backendless-Signature` | `sha256=` followed by the hex encoded signature    |
+//!
+//! The signature is the HMAC-SHA256 of `{timestamp}.{body}` keyed with the
+//! webhook's secret. Any 2xx response acknowledges the delivery, anything
+//! else is retried with exponential backoff until it is abandoned.
+//!
+//! Webhooks can only be sent to hosts which resolve to public addresses,
+//! checked both when the webhook is created and before each delivery. The
+//! delivery is sent to the address which was checked rather than resolving
+//! the host again. Private addresses can be allowed for development with
+//! the `webhooks.allow_private_destinations` setting.
+
+use crate::{
+    config::CFG,
+    errors::ApiError,
+    models::{Webhook, WebhookDelivery},
+};
+use actix_web::{client::Client, http::Uri, web};
+use chrono::{Duration, NaiveDateTime, Utc};
+use futures::future;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use ring::hmac;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use uuid::Uuid;
+
+/// Events which can be subscribed to
+pub static EVENTS: &[&str] = &[
+    "deployment.created",
+    "deployment.ready",
+    "deployment.promoted",
+    "deployment.deleted",
+    "project.updated",
+];
+
+// Attempts made before a delivery is abandoned
+const MAX_ATTEMPTS: i32 = 8;
+
+// Delay before the first retry in seconds, doubled after each attempt
+const RETRY_DELAY: i64 = 30;
+
+// Longest delay between attempts in seconds
+const MAX_RETRY_DELAY: i64 = 6 * 60 * 60;
+
+// How long a worker may take to send a delivery before another can claim it
+const LEASE: i64 = 2 * 60;
+
+// Seconds to wait for a receiver to respond
+const TIMEOUT: u64 = 10;
+
+// Seconds between checks for due deliveries
+const POLL_INTERVAL: u64 = 5;
+
+// Deliveries sent at once
+const BATCH_SIZE: i64 = 20;
+
+// Bytes of a successful response's body kept in the delivery history
+const MAX_RESPONSE_SIZE: usize = 4096;
+
+/// Queue an event for every webhook in the project subscribed to it
+pub fn enqueue(project_id: Uuid, event: &str, data: serde_json::Value) -> Result<(), ApiError> {
+    let payload = json!({
+        "id": Uuid::new_v4(),
+        "type": event,
+        "project_id": project_id,
+        "timestamp": Utc::now(),
+        "data": data,
+    });
+
+    for webhook in Webhook::find_all(project_id)? {
+        if webhook.subscribes_to(event) {
+            WebhookDelivery::create(webhook.id, event.to_string(), payload.clone())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that a webhook subscription is valid
+pub fn validate(url: &str, events: &[String]) -> Result<(), ApiError> {
+    let valid_url = url
+        .parse::<actix_web::http::Uri>()
+        .ok()
+        .filter(|u| u.host().is_some())
+        .and_then(|u| u.scheme_str().map(|s| s == "http" || s == "https"))
+        .unwrap_or(false);
+    if !valid_url {
+        return Err(ApiError::new(
+            400,
+            "webhook url must be an absolute http or https url".to_string(),
+        ));
+    }
+
+    if let Some(event) = events.iter().find(|e| !EVENTS.contains(&e.as_str())) {
+        return Err(ApiError::new(
+            400,
+            format!(
+                "unknown event '{}', must be one of: {}",
+                event,
+                EVENTS.join(", ")
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check that a webhook url's host only resolves to public addresses, so
+/// webhooks cannot reach services on the API's own network. Returns the
+/// address to send to. This blocks while the host is resolved and should
+/// be run using `web::block`.
+pub fn check_destination(url: &str) -> Result<SocketAddr, ApiError> {
+    let uri = url
+        .parse::<Uri>()
+        .map_err(|e| ApiError::new(400, format!("invalid webhook url: {}", e)))?;
+    let host = uri
+        .host()
+        .unwrap_or_default()
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = uri.port_u16().unwrap_or(match uri.scheme_str() {
+        Some("https") => 443,
+        _ => 80,
+    });
+
+    let addresses: Vec<SocketAddr> = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| ApiError::new(400, format!("failed to resolve '{}': {}", host, e)))?
+        .collect();
+
+    select_address(host, addresses, CFG.webhooks.allow_private_destinations)
+}
+
+/// Choose the address to send to from those a host resolves to, failing if
+/// any of them are not public unless private addresses are allowed
+fn select_address(
+    host: &str,
+    addresses: Vec<SocketAddr>,
+    allow_private: bool,
+) -> Result<SocketAddr, ApiError> {
+    let first = match addresses.first() {
+        Some(a) => *a,
+        None => {
+            return Err(ApiError::new(
+                400,
+                format!("'{}' does not resolve to any address", host),
+            ))
+        }
+    };
+
+    match addresses.into_iter().find(|a| !is_public(a.ip())) {
+        Some(address) if !allow_private => Err(ApiError::new(
+            400,
+            format!(
+                "webhook host '{}' resolves to non-public address {}",
+                host,
+                address.ip()
+            ),
+        )),
+        _ => Ok(first),
+    }
+}
+
+/// Whether an address is reachable on the public internet
+fn is_public(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || octets[0] == 0
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (octets[0] == 100 && octets[1] & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            if ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() {
+                false
+            } else if first & 0xfe00 == 0xfc00 || first & 0xffc0 == 0xfe80 {
+                // Unique local, fc00::/7, and link-local, fe80::/10
+                false
+            } else if let Some(ip) = ip.to_ipv4() {
+                // IPv4-mapped and compatible addresses reach the IPv4 address
+                is_public(IpAddr::V4(ip))
+            } else {
+                true
+            }
+        }
+    }
+}
+
+/// Generate a random secret to sign deliveries with
+pub fn generate_secret() -> String {
+    thread_rng().sample_iter(&Alphanumeric).take(32).collect()
+}
+
+/// Send due deliveries forever, should be spawned when the server starts
+pub async fn run() {
+    loop {
+        let claimed =
+            web::block(|| WebhookDelivery::claim_due(BATCH_SIZE, Duration::seconds(LEASE))).await;
+
+        match claimed {
+            Ok(deliveries) => {
+                future::join_all(deliveries.into_iter().map(deliver)).await;
+            }
+            Err(e) => error!("failed to claim webhook deliveries: {}", ApiError::from(e)),
+        }
+
+        actix_rt::time::delay_for(std::time::Duration::from_secs(POLL_INTERVAL)).await;
+    }
+}
+
+/// Make a single attempt at sending a delivery and record the result
+async fn deliver(delivery: WebhookDelivery) {
+    let id = delivery.id;
+    if let Err(e) = attempt(delivery).await {
+        error!("failed to send webhook delivery {}: {}", id, e);
+    }
+}
+
+async fn attempt(delivery: WebhookDelivery) -> Result<(), ApiError> {
+    let webhook_id = delivery.webhook_id;
+    let webhook = web::block(move || Webhook::find(webhook_id)).await?;
+
+    // Where the host resolves to may have changed since the webhook was created
+    let url = webhook.url.clone();
+    let (succeeded, status, response, error) =
+        match web::block(move || check_destination(&url)).await {
+            Ok(address) => send(&webhook, &delivery, address).await?,
+            Err(e) => (false, None, None, Some(ApiError::from(e).message)),
+        };
+
+    let retry_at = if succeeded {
+        None
+    } else {
+        retry_at(delivery.attempts + 1)
+    };
+    web::block(move || delivery.record_attempt(succeeded, status, response, error, retry_at))
+        .await?;
+    Ok(())
+}
+
+/// Send a delivery to a checked address, returning whether it succeeded
+/// along with the status, body, and error of the response
+async fn send(
+    webhook: &Webhook,
+    delivery: &WebhookDelivery,
+    address: SocketAddr,
+) -> Result<(bool, Option<i32>, Option<String>, Option<String>), ApiError> {
+    let body = serde_json::to_vec(&delivery.payload)
+        .map_err(|e| ApiError::new(500, format!("failed to encode delivery: {}", e)))?;
+    let timestamp = Utc::now().timestamp();
+
+    let result = Client::new()
+        .post(&webhook.url)
+        // Connect to the checked address so the host can't resolve elsewhere in between
+        .address(address)
+        .timeout(std::time::Duration::from_secs(TIMEOUT))
+        .header("Content-Type", "application/json")
+        .header("X-Backendless-Event", delivery.event.as_str())
+        .header("X-Backendless-Delivery", delivery.id.to_string())
+        .header("X-Backendless-Timestamp", timestamp.to_string())
+        .header(
+            "X-Backendless-Signature",
+            format!("sha256={}", sign(&webhook.secret, timestamp, &body)),
+        )
+        .send_body(body)
+        .await;
+
+    match result {
+        Ok(mut response) => {
+            let status = response.status();
+
+            // Error responses may expose details of the receiver, so their body isn't kept
+            let text = if status.is_success() {
+                match response.body().limit(MAX_RESPONSE_SIZE).await {
+                    Ok(b) => Some(String::from_utf8_lossy(&b[..]).into_owned()),
+                    Err(_) => None,
+                }
+            } else {
+                None
+            };
+            Ok((
+                status.is_success(),
+                Some(status.as_u16() as i32),
+                text,
+                None,
+            ))
+        }
+        Err(e) => Ok((false, None, None, Some(e.to_string()))),
+    }
+}
+
+/// When to retry a delivery after a number of failed attempts, if at all
+fn retry_at(attempts: i32) -> Option<NaiveDateTime> {
+    if attempts >= MAX_ATTEMPTS {
+        return None;
+    }
+
+    let delay = (RETRY_DELAY << (attempts - 1)).min(MAX_RETRY_DELAY);
+    Some(Utc::now().naive_utc() + Duration::seconds(delay))
+}
+
+/// Sign a delivery body with the webhook's secret
+fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let mut context = hmac::Context::with_key(&key);
+    context.update(timestamp.to_string().as_bytes());
+    context.update(b".");
+    context.update(body);
+    hex::encode(context.sign().as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    fn socket(address: &str) -> SocketAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn backs_off_exponentially() {
+        for (attempts, delay) in &[(1, 30), (2, 60), (3, 120), (7, 1920)] {
+            let before = Utc::now().naive_utc();
+            let at = retry_at(*attempts).unwrap();
+            let after = Utc::now().naive_utc();
+
+            assert!(at >= before + Duration::seconds(*delay));
+            assert!(at <= after + Duration::seconds(*delay));
+        }
+    }
+
+    #[test]
+    fn abandons_after_max_attempts() {
+        assert!(retry_at(MAX_ATTEMPTS - 1).is_some());
+        assert!(retry_at(MAX_ATTEMPTS).is_none());
+        assert!(retry_at(MAX_ATTEMPTS + 1).is_none());
+    }
+
+    #[test]
+    fn signs_timestamp_and_body() {
+        let body = br#"{"type":"deployment.created"}"#;
+        assert_eq!(
+            sign("whsec", 1_600_000_000, body),
+            "fe42365adf87aaa1cd71fabd72560db5e1880006822b95514dfeeb319fb0ae1d"
+        );
+        assert_ne!(
+            sign("whsec", 1_600_000_001, body),
+            sign("whsec", 1_600_000_000, body)
+        );
+        assert_ne!(
+            sign("other", 1_600_000_000, body),
+            sign("whsec", 1_600_000_000, body)
+        );
+    }
+
+    #[test]
+    fn rejects_non_public_addresses() {
+        for address in &[
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip(address)), "{} should not be public", address);
+        }
+
+        for address in &[
+            "8.8.8.8",
+            "100.128.0.1",
+            "2606:4700::1111",
+            "::ffff:1.1.1.1",
+        ] {
+            assert!(is_public(ip(address)), "{} should be public", address);
+        }
+    }
+
+    #[test]
+    fn selects_checked_addresses() {
+        let public = vec![socket("8.8.8.8:443"), socket("[2606:4700::1111]:443")];
+        assert_eq!(
+            select_address("example.com", public, false).unwrap(),
+            socket("8.8.8.8:443")
+        );
+
+        let mixed = vec![socket("8.8.8.8:80"), socket("10.0.0.1:80")];
+        assert!(select_address("example.com", mixed.clone(), false).is_err());
+        assert_eq!(
+            select_address("example.com", mixed, true).unwrap(),
+            socket("8.8.8.8:80")
+        );
+
+        let error = select_address("example.com", vec![], true).unwrap_err();
+        assert_eq!(
+            error.message,
+            "'example.com' does not resolve to any address"
+        );
+    }
+
+    #[test]
+    fn checks_literal_destinations() {
+        assert_eq!(
+            check_destination("https://8.8.8.8/hook").unwrap(),
+            socket("8.8.8.8:443")
+        );
+        assert_eq!(
+            check_destination("http://[2606:4700::1111]:8080/hook").unwrap(),
+            socket("[2606:4700::1111]:8080")
+        );
+        assert_eq!(
+            check_destination("http://127.0.0.1:8080/hook")
+                .unwrap_err()
+                .message,
+            "webhook host '127.0.0.1' resolves to non-public address 127.0.0.1"
+        );
+        assert!(check_destination("http://[::1]/hook").is_err());
+        assert!(check_destination("not a url").is_err());
+    }
+}
@@ -1,10 +1,10 @@
 import click
 import fastjsonschema
+import hashlib
 import json
 from pathlib import Path
 import pickle
 from requests import get, post, put, Session
-import shutil
 import sys
 import yaml
 
@@ -173,20 +173,42 @@ def deploy(ctx, project_id, project_file):
         click.echo("Specified static directory does not exist")
         sys.exit(1)
 
-    # Create zip archive of static files
-    shutil.make_archive(full_path.parent.joinpath("static"), "zip", p)
+    # Hash each static file so only new contents are uploaded
+    files = {}
+    for path in p.rglob("*"):
+        if path.is_file():
+            with open(path, "rb") as f:
+                files[path.relative_to(p).as_posix()] = hashlib.sha256(f.read()).hexdigest()
 
     # Upload schema to server
-    schema = post(f"{ctx.obj['SERVER']}/projects/{project_id}/deployments", data=validated)
+    schema = session.post(f"{ctx.obj['SERVER']}/projects/{project_id}/deployments", json=validated)
     if schema.status_code != 200:
-        click.echo(f"Failed to upload schema: {schema.json().reason}")
+        click.echo(f"Failed to upload schema: {schema.json()['reason']}")
         sys.exit(1)
+    static_url = f"{ctx.obj['SERVER']}/projects/{project_id}/deployments/{schema.json()['data']['id']}/static"
 
-    # Upload static files to server
-    upload = put(f"{ctx.obj['SERVER']}/projects/{project_id}/deployments",
-                 files={"zip": open(full_path.parent.joinpath("static.zip"), "rb")})
-    if upload.status_code != 200:
-        click.echo(f"Failed to upload project: {upload.json().reason}")
+    # Find which static files the server does not already have
+    manifest = session.post(f"{static_url}/manifest", json=files)
+    if manifest.status_code != 200:
+        click.echo(f"Failed to upload static manifest: {manifest.json()['reason']}")
+        sys.exit(1)
+    missing = set(manifest.json()["data"]["missing"])
+
+    # Upload the contents of each missing file once
+    for name, digest in files.items():
+        if digest not in missing:
+            continue
+        missing.remove(digest)
+
+        with open(p.joinpath(name), "rb") as f:
+            upload = session.put(f"{static_url}/blobs/{digest}", data=f)
+        if upload.status_code != 200:
+            click.echo(f"Failed to upload {name}: {upload.json()['reason']}")
+            sys.exit(1)
+
+    commit = session.post(f"{static_url}/commit")
+    if commit.status_code != 200:
+        click.echo(f"Failed to upload static files: {commit.json()['reason']}")
         sys.exit(1)
     click.echo("Successfully uploaded project")
 
@@ -0,0 +1,66 @@
+from db import Alias, Project
+from util import matches_route
+
+
+class Aliases(object):
+    """
+    The versions a project's aliases point at
+
+    :param project_id: the id of the project
+    :param versions: dictionary of alias names to versions
+    """
+    def __init__(self, project_id, versions):
+        self.project_id = project_id
+        self.versions = versions
+
+    def __str__(self):
+        return f"<Aliases project_id={self.project_id} versions={self.versions}>"
+
+    def __repr__(self):
+        return self.__str__()
+
+
+async def apply_aliases(app, project_id, db):
+    """
+    Update where a project's aliases point from the database
+
+    :param app: app instance to be modified
+    :param project_id: the id of the project
+    :param db: a database connection object
+    """
+    # Projects are routed by name, so remove the aliases under any previous name
+    for name, aliases in list(app.state.aliases.items()):
+        if str(aliases.project_id) == str(project_id):
+            del app.state.aliases[name]
+
+    project = await Project.find(project_id, db)
+    if project is None:
+        return
+
+    # Replace all of the project's aliases at once so requests never see a partial update
+    versions = await Alias.versions(project_id, db)
+    if versions:
+        app.state.aliases[project.name] = Aliases(project_id, versions)
+
+
+class AliasMiddleware(object):
+    """
+    Route requests to an alias, `/{project}/{alias}/{path}`, to the version
+    the alias points at. Requests which match a route as-is are left untouched.
+
+    :param app: the ASGI app to wrap
+    """
+    def __init__(self, app):
+        self.app = app
+
+    async def __call__(self, scope, receive, send):
+        if scope["type"] == "http":
+            app = scope["app"]
+            segments = scope["path"].split("/", 3)
+            aliases = app.state.aliases.get(segments[1])
+            version = aliases.versions.get(segments[2]) if aliases is not None and len(segments) > 2 else None
+
+            if version is not None and not matches_route(app, scope):
+                scope = dict(scope, path="/".join(segments[:2] + [version] + segments[3:]))
+
+        await self.app(scope, receive, send)
@@ -1,7 +1,19 @@
 import os
+import socket
 import toml
 import util
 
+# Storage configuration values and their defaults, matching the API's settings
+STORAGE_DEFAULTS = {
+    "backend": "gcs",
+    "s3_bucket": "backendless-user-files",
+    "s3_region": "us-east-1",
+    "s3_endpoint": None,
+    "s3_key": None,
+    "s3_secret": None,
+    "local_directory": "./data",
+}
+
 
 def read_from_environment():
     """
@@ -14,21 +26,30 @@ def read_from_environment():
     server_host = os.environ.get("RUNTIME_SERVER_HOST")
     server_port = os.environ.get("RUNTIME_SERVER_PORT")
     server_workers = os.environ.get("RUNTIME_SERVER_WORKERS")
+    server_id = os.environ.get("RUNTIME_SERVER_ID")
     redis_host = os.environ.get("RUNTIME_REDIS_HOST")
     redis_port = os.environ.get("RUNTIME_REDIS_PORT")
     redis_database = os.environ.get("RUNTIME_REDIS_DATABASE")
     database_url = os.environ.get("RUNTIME_DATABASE_URL")
     gcp_bucket = os.environ.get("RUNTIME_GCP_BUCKET")
+    api_url = os.environ.get("RUNTIME_API_URL")
+    api_token = os.environ.get("RUNTIME_API_TOKEN")
 
     # Mark as non existent if blank
     if server_host == "":
         server_host = None
+    if server_id == "":
+        server_id = None
     if redis_host == "":
         redis_host = None
     if database_url == "":
         database_url = None
     if gcp_bucket == "":
         gcp_bucket = None
+    if api_url == "":
+        api_url = None
+    if api_token == "":
+        api_token = None
 
     # Try converting to integer
     server_port = util.convert_to(server_port, int)
@@ -36,7 +57,21 @@ def read_from_environment():
     redis_port = util.convert_to(redis_port, int)
     redis_database = util.convert_to(redis_database, int)
 
-    return server_host, server_port, server_workers, redis_host, redis_port, redis_database, database_url, gcp_bucket
+    return server_host, server_port, server_workers, server_id, \
+        redis_host, redis_port, redis_database, database_url, gcp_bucket, api_url, api_token
+
+
+def read_storage_from_environment():
+    """
+    Read the storage configuration from environment variables, such as
+    ``RUNTIME_STORAGE_BACKEND`` or ``RUNTIME_STORAGE_S3_BUCKET``
+
+    :return: storage configuration values
+    """
+    values = {key: os.environ.get(f"RUNTIME_STORAGE_{key.upper()}") for key in STORAGE_DEFAULTS}
+
+    # Mark as non existent if blank
+    return {key: value if value != "" else None for key, value in values.items()}
 
 
 def determine_config_file():
@@ -59,9 +94,9 @@ def parse_from_file(raw_config: dict):
     :return: parsed configuration
     """
 
-    server_host, server_port, server_workers, \
+    server_host, server_port, server_workers, server_id, \
         redis_host, redis_port, redis_database, \
-        database_url, gcp_bucket = [None] * 8
+        database_url, gcp_bucket, api_url, api_token = [None] * 11
 
     if type(raw_config.get("server")) is dict:
         raw_server = raw_config.get("server")  # type: dict
@@ -75,6 +110,9 @@ def parse_from_file(raw_config: dict):
         if type(raw_server.get("workers")) is int:
             server_workers = raw_server.get("workers")
 
+        if type(raw_server.get("id")) is str:
+            server_id = raw_server.get("id")
+
     if type(raw_config.get("redis")) is dict:
         raw_redis = raw_config.get("redis")  # type: dict
 
@@ -99,7 +137,46 @@ def parse_from_file(raw_config: dict):
         if type(raw_gcs.get("bucket")) is str:
             gcp_bucket = raw_gcs.get("bucket")
 
-    return server_host, server_port, server_workers, redis_host, redis_port, redis_database, database_url, gcp_bucket
+    if type(raw_config.get("api")) is dict:
+        raw_api = raw_config.get("api")  # type: dict
+
+        if type(raw_api.get("url")) is str:
+            api_url = raw_api.get("url")
+
+        if type(raw_api.get("token")) is str:
+            api_token = raw_api.get("token")
+
+    return server_host, server_port, server_workers, server_id, \
+        redis_host, redis_port, redis_database, database_url, gcp_bucket, api_url, api_token
+
+
+def parse_storage_from_file(raw_config: dict):
+    """
+    Parse the storage configuration from the raw dictionary, laid out the
+    same as the API's ``[storage]``, ``[storage.s3]`` and ``[storage.local]`` tables
+
+    :param raw_config: raw parsed configuration
+    :return: storage configuration values
+    """
+    values = dict.fromkeys(STORAGE_DEFAULTS)
+
+    raw_storage = raw_config.get("storage")
+    if type(raw_storage) is not dict:
+        return values
+
+    if type(raw_storage.get("backend")) is str:
+        values["backend"] = raw_storage.get("backend")
+
+    for backend in ["s3", "local"]:
+        raw_backend = raw_storage.get(backend)
+        if type(raw_backend) is not dict:
+            continue
+
+        for key, value in raw_backend.items():
+            if f"{backend}_{key}" in values and type(value) is str:
+                values[f"{backend}_{key}"] = value
+
+    return values
 
 
 class Config(object):
@@ -115,29 +192,41 @@ class Config(object):
             raw_file = toml.load(open(file, "r"))
 
         # Parse the file configuration
-        file_server_host, file_server_port, file_server_workers, \
+        file_server_host, file_server_port, file_server_workers, file_server_id, \
             file_redis_host, file_redis_port, file_redis_database, \
-            file_database_url, file_gcp_bucket = parse_from_file(raw_file)
+            file_database_url, file_gcp_bucket, file_api_url, file_api_token = parse_from_file(raw_file)
 
         # Read from environment
-        env_server_host, env_server_port, env_server_workers, \
+        env_server_host, env_server_port, env_server_workers, env_server_id, \
             env_redis_host, env_redis_port, env_redis_database, \
-            env_database_url, env_gcp_bucket = read_from_environment()
+            env_database_url, env_gcp_bucket, env_api_url, env_api_token = read_from_environment()
 
         # Set configuration
         self._host = util.set_config_var(file_server_host, env_server_host, "127.0.0.1")
         self._port = util.set_config_var(file_server_port, env_server_port, 9090)
         self._workers = util.set_config_var(file_server_workers, env_server_workers, 1)
+        # Each worker process claims a numbered slot on the server to name its consumer group
+        self.server_id = util.set_config_var(file_server_id, env_server_id, socket.gethostname())
         self._redis_host = util.set_config_var(file_redis_host, env_redis_host, "127.0.0.1")
         self._redis_port = util.set_config_var(file_redis_port, env_redis_port, 6379)
         self._redis_database = util.set_config_var(file_redis_database, env_redis_database, 0)
         self.database_url = util.set_config_var(file_database_url, env_database_url, "postgres://127.0.0.1:5432")
         self.gcp_bucket = util.set_config_var(file_gcp_bucket, env_gcp_bucket, "backendless-user-files")
+        file_storage = parse_storage_from_file(raw_file)
+        env_storage = read_storage_from_environment()
+        self.storage = {key: util.set_config_var(file_storage[key], env_storage[key], default)
+                        for key, default in STORAGE_DEFAULTS.items()}
+        self._api_url = util.set_config_var(file_api_url, env_api_url, "http://127.0.0.1:8080")
+        self._api_token = util.set_config_var(file_api_token, env_api_token, "")
 
     @property
     def app(self):
         return {"host": self._host, "port": self._port, "workers": self._workers}
 
+    @property
+    def api(self):
+        return {"url": self._api_url.rstrip("/"), "token": self._api_token}
+
     @property
     def redis(self):
         return {"address": f"redis://{self._redis_host}:{self._redis_port}", "db": self._redis_database}
@@ -1,2 +1,2 @@
 from . import models, tables
-from .models import Deployment, Handler, Route
+from .models import Alias, Deployment, Handler, Project, Route, Traffic
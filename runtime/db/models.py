@@ -1,4 +1,6 @@
-from .tables import deployments, handlers, projects, routes
+import sqlalchemy
+
+from .tables import aliases, deployments, handlers, projects, routes, static_files, traffic
 
 
 class Deployment(object):
@@ -45,6 +47,17 @@ class Deployment(object):
         records = await self.__db.fetch_all(query=query)
         return [Handler(record) for record in records]
 
+    @property
+    async def static_files(self):
+        """
+        Retrieve the static file manifest of the deployment
+
+        :return: dictionary of file paths to content hashes
+        """
+        query = static_files.select().where(static_files.c.deployment_id == self.id)
+        records = await self.__db.fetch_all(query=query)
+        return {record.get("path"): record.get("hash") for record in records}
+
     @property
     async def project(self):
         """
@@ -63,10 +76,11 @@ class Deployment(object):
 
         :param deployment_id: the uuid of the deployment
         :param db: a database connection object
+        :return: the deployment or `None`
         """
         query = deployments.select().where(deployments.c.id == deployment_id)
         record = await db.fetch_one(query=query)
-        return cls(record, db)
+        return cls(record, db) if record is not None else None
 
     @classmethod
     async def all(cls, db):
@@ -142,3 +156,142 @@ class Project(object):
 
     def __repr__(self):
         return self.__str__()
+
+    @classmethod
+    async def find(cls, project_id, db):
+        """
+        Find a project by its id
+
+        :param project_id: the uuid of the project
+        :param db: a database connection object
+        :return: the project or `None`
+        """
+        query = projects.select().where(projects.c.id == project_id)
+        record = await db.fetch_one(query=query)
+        return cls(record) if record is not None else None
+
+
+class Traffic(object):
+    """
+    Representation of how a project's unversioned traffic is split in the database
+
+    :param record: a record found in the database
+    :param db: a database connection object
+    """
+    def __init__(self, record, db):
+        self.__db = db
+        self.project_id = record.get("project_id")
+        self.stable_id = record.get("stable_id")
+        self.candidate_id = record.get("candidate_id")
+        self.weight = record.get("weight")
+        self.sticky_header = record.get("sticky_header")
+        self.updated_at = record.get("updated_at")
+
+    def __str__(self):
+        return f"<Traffic project_id={self.project_id} stable_id={self.stable_id} candidate_id={self.candidate_id} " \
+               f"weight={self.weight}>"
+
+    def __repr__(self):
+        return self.__str__()
+
+    @property
+    async def project(self):
+        """
+        Retrieve the project whose traffic is split
+
+        :return: associated project
+        """
+        query = projects.select().where(projects.c.id == self.project_id)
+        record = await self.__db.fetch_one(query=query)
+        return Project(record)
+
+    @property
+    async def stable(self):
+        """
+        Retrieve the deployment receiving the remaining traffic
+
+        :return: the stable deployment
+        """
+        return await Deployment.find(self.stable_id, self.__db)
+
+    @property
+    async def candidate(self):
+        """
+        Retrieve the deployment receiving the weighted traffic
+
+        :return: the candidate deployment or `None`
+        """
+        if self.candidate_id is None:
+            return None
+        return await Deployment.find(self.candidate_id, self.__db)
+
+    @classmethod
+    async def find(cls, project_id, db):
+        """
+        Find how a project's traffic is split
+
+        :param project_id: the uuid of the project
+        :param db: a database connection object
+        :return: the traffic split or `None`
+        """
+        query = traffic.select().where(traffic.c.project_id == project_id)
+        record = await db.fetch_one(query=query)
+        return cls(record, db) if record is not None else None
+
+    @classmethod
+    async def all(cls, db):
+        """
+        Find all traffic splits in the database
+
+        :param db: a database connection object
+        """
+        query = traffic.select()
+        records = await db.fetch_all(query=query)
+        return [cls(record, db) for record in records]
+
+
+class Alias(object):
+    """
+    Representation of an alias in the database
+
+    :param record: a record found in the database
+    """
+    def __init__(self, record):
+        self.id = record.get("id")
+        self.project_id = record.get("project_id")
+        self.name = record.get("name")
+        self.deployment_id = record.get("deployment_id")
+        self.created_at = record.get("created_at")
+        self.updated_at = record.get("updated_at")
+
+    def __str__(self):
+        return f"<Alias id={self.id} project_id={self.project_id} name={self.name} deployment_id={self.deployment_id}>"
+
+    def __repr__(self):
+        return self.__str__()
+
+    @classmethod
+    async def versions(cls, project_id, db):
+        """
+        Find the version each of a project's aliases points at
+
+        :param project_id: the uuid of the project
+        :param db: a database connection object
+        :return: dictionary of alias names to versions
+        """
+        query = sqlalchemy.select([aliases.c.name, deployments.c.version]) \
+            .select_from(aliases.join(deployments, aliases.c.deployment_id == deployments.c.id)) \
+            .where(aliases.c.project_id == project_id)
+        records = await db.fetch_all(query=query)
+        return {record.get("name"): record.get("version") for record in records}
+
+    @classmethod
+    async def all(cls, db):
+        """
+        Find all aliases in the database
+
+        :param db: a database connection object
+        """
+        query = aliases.select()
+        records = await db.fetch_all(query=query)
+        return [cls(record) for record in records]
@@ -52,3 +52,39 @@ handlers = sqlalchemy.Table(
     sqlalchemy.Column("body", postgresql.JSONB(none_as_null=True)),
     sqlalchemy.Column("logic", postgresql.JSONB(none_as_null=True), nullable=False)
 )
+
+# Static files table
+static_files = sqlalchemy.Table(
+    "static_files",
+    metadata,
+    sqlalchemy.Column("id", postgresql.UUID, primary_key=True, default=uuid.uuid4, unique=True, nullable=False),
+    sqlalchemy.Column("deployment_id", postgresql.UUID, nullable=False),
+    sqlalchemy.Column("path", sqlalchemy.Text, nullable=False),
+    sqlalchemy.Column("hash", sqlalchemy.String, nullable=False),
+    sqlalchemy.Column("size", sqlalchemy.BigInteger, nullable=False),
+    sqlalchemy.Column("content_type", sqlalchemy.Text, nullable=False)
+)
+
+# Traffic splits table
+traffic = sqlalchemy.Table(
+    "traffic",
+    metadata,
+    sqlalchemy.Column("project_id", postgresql.UUID, primary_key=True, unique=True, nullable=False),
+    sqlalchemy.Column("stable_id", postgresql.UUID, nullable=False),
+    sqlalchemy.Column("candidate_id", postgresql.UUID),
+    sqlalchemy.Column("weight", sqlalchemy.Integer, nullable=False),
+    sqlalchemy.Column("sticky_header", sqlalchemy.String),
+    sqlalchemy.Column("updated_at", sqlalchemy.DateTime, nullable=False)
+)
+
+# Aliases table
+aliases = sqlalchemy.Table(
+    "aliases",
+    metadata,
+    sqlalchemy.Column("id", postgresql.UUID, primary_key=True, default=uuid.uuid4, unique=True, nullable=False),
+    sqlalchemy.Column("project_id", postgresql.UUID, nullable=False),
+    sqlalchemy.Column("name", sqlalchemy.String, nullable=False),
+    sqlalchemy.Column("deployment_id", postgresql.UUID, nullable=False),
+    sqlalchemy.Column("created_at", sqlalchemy.DateTime, nullable=False),
+    sqlalchemy.Column("updated_at", sqlalchemy.DateTime, nullable=False)
+)
@@ -7,12 +7,13 @@ from db import Handler
 from .operations import OPERATIONS
 
 
-def generate_handler(handler: Handler, project_id):
+def generate_handler(handler: Handler, project_id, manifest):
     """
     Generate a handler based on the specified configuration
 
     :param handler: handler configuration
     :param project_id: the id of the associated project
+    :param manifest: the deployment's static files mapped to their content hashes
     :return: runnable handler
     """
     async def runner(request: Request):
@@ -34,7 +35,8 @@ def generate_handler(handler: Handler, project_id):
         # Run through the specified logic
         for statement in handler.logic:
             action = statement.get("action")
-            result = OPERATIONS[action](state, **statement, bucket=request.app.state.bucket, project_id=str(project_id))
+            result = OPERATIONS[action](state, **statement, storage=request.app.state.storage, project_id=str(project_id),
+                                        manifest=manifest)
 
             # Return result if exists
             if result is not None:
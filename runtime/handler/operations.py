@@ -204,28 +204,28 @@ def db(state, *, operation, collection, project_id, data={}, query=None, store=N
         state[store] = store_value
 
 
-def static(state, *, file, bucket, project_id, **_):
+def static(state, *, file, storage, project_id, manifest, **_):
     """
     Serve a static file to the requester
 
     :param state: the request status
     :param file: the file to retrieve
-    :param bucket: the GCP bucket reference
+    :param storage: the storage backend static files are kept in
     :param project_id: the id of the project
+    :param manifest: the deployment's static files mapped to their content hashes
     """
     file = substitute(file, state)
-    if file.startswith("/"):
-        blob = bucket.blob(f"{project_id}{file}")
-    else:
-        blob = bucket.blob(f"{project_id}/{file}")
+    content_hash = manifest.get(file.lstrip("/"))
 
-    # Ensure exists
-    if not blob.exists():
-        return JSONResponse({"success": False, "reason": f"specified file ({file}) was not found"}, status_code=404)
+    not_found = JSONResponse({"success": False, "reason": f"specified file ({file}) was not found"}, status_code=404)
+    if content_hash is None:
+        return not_found
 
     # Create and write to temporary file
     with NamedTemporaryFile() as file:
-        blob.download_to_file(file)
+        if not storage.download(f"{project_id}/blobs/{content_hash}", file):
+            return not_found
+        file.flush()
 
         # Return the file
         return FileResponse(file.name)
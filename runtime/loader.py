@@ -1,8 +1,10 @@
 from starlette.routing import Route
 
-from db import Deployment
+from aliases import apply_aliases
+from db import Alias, Deployment, Traffic
 from handler import generate_handler
-from util import generate_name
+from traffic import apply_split
+from util import generate_name, remove_deployment_routes
 
 
 # This is the same as /runtime/pubsub/modifiers.py#publish_deployment
@@ -14,10 +16,14 @@ def load_routes(app, db):
             handlers = await deployment.handlers
             routes = await deployment.routes
             project = await deployment.project
+            manifest = await deployment.static_files
+
+            # Events replayed during startup may have already published the deployment
+            remove_deployment_routes(deployment.id, app)
 
             handlers_by_name = {}
             for handler in handlers:
-                handlers_by_name[handler.name] = generate_handler(handler, deployment.project_id)
+                handlers_by_name[handler.name] = generate_handler(handler, deployment.project_id, manifest)
 
             for route in routes:
                 h = handlers_by_name.get(route.handler)
@@ -33,4 +39,12 @@ def load_routes(app, db):
                 )
 
                 app.router.routes.append(r)
+
+            app.state.deployments.add(deployment.id)
+
+        for traffic in await Traffic.all(db):
+            await apply_split(app, traffic, traffic.project_id)
+
+        for project_id in {alias.project_id for alias in await Alias.all(db)}:
+            await apply_aliases(app, project_id, db)
     return inner
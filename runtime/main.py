@@ -1,13 +1,17 @@
 from databases import Database
 from dotenv import load_dotenv
-from google.cloud import storage
 from starlette.applications import Starlette
+from starlette.middleware import Middleware
 from starlette.responses import JSONResponse
 import uvicorn
 
 from config import Config
+from aliases import AliasMiddleware
 import loader
 import pubsub
+import registry
+import storage
+from traffic import TrafficMiddleware
 
 load_dotenv()
 
@@ -18,9 +22,8 @@ cfg = Config()
 # Connect to database
 database = Database(cfg.database_url)
 
-# Connect to Google Cloud services
-storage_client = storage.Client()
-bucket = storage_client.bucket(cfg.gcp_bucket)
+# Connect to where static files are stored
+static_storage = storage.connect(cfg)
 
 
 # Create API server
@@ -31,20 +34,30 @@ app = Starlette(
         405: lambda req, exc: JSONResponse({"success": False, "reason": "method not allowed"}),
         500: lambda req, exc: JSONResponse({"success": False, "reason": "internal server error"})
     },
+    middleware=[Middleware(AliasMiddleware), Middleware(TrafficMiddleware)],
     on_startup=[database.connect],
     on_shutdown=[database.disconnect]
 )
 
-# Add redis configuration to startup and shutdown
-app.router.on_startup.append(pubsub.configure(app, cfg.redis))
-app.router.on_shutdown.append(pubsub.shutdown(app))
+# Add redis configuration to startup
+app.router.on_startup.append(pubsub.configure(app, cfg.redis, cfg.server_id, cfg.app["workers"]))
 
 # Load initial routes
 app.router.on_startup.append(loader.load_routes(app, database))
 
+# Report loaded deployments to the API
+app.router.on_startup.append(registry.configure(app, cfg.api))
+
+# Deregister before releasing the worker slot so the next worker's consumer group isn't removed
+app.router.on_shutdown.append(registry.shutdown(app, cfg.api))
+app.router.on_shutdown.append(pubsub.shutdown(app))
+
 # Attach services to state
 app.state.database = database
-app.state.bucket = bucket
+app.state.storage = static_storage
+app.state.deployments = set()
+app.state.traffic = {}
+app.state.aliases = {}
 
 if __name__ == "__main__":
     uvicorn.run("main:app", **cfg.app)
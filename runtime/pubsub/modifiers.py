@@ -1,28 +1,38 @@
 from starlette.routing import Route
 
-from db import Deployment
+from aliases import apply_aliases
+from db import Deployment, Traffic
 from handler import generate_handler
-from util import find_route_by_name, generate_name
+from traffic import apply_split
+from util import generate_name, remove_deployment_routes
 
 
-async def publish_deployment(app, uuid):
+async def publish_deployment(app, event):
     """
     Publish a deployment's routes
 
     :param app: app instance to be modified
-    :param uuid: the id of the deployment
+    :param event: the `deployment.published` event
     """
     # Fetch deployment and its associated handlers and routes
     db = app.state.database
-    deployment = await Deployment.find(uuid, db)
+    deployment = await Deployment.find(event["deployment_id"], db)
+    if deployment is None:
+        # The deployment was deleted before the event was processed
+        return
+
     handlers = await deployment.handlers
     routes = await deployment.routes
     project = await deployment.project
+    manifest = await deployment.static_files
+
+    # Events may be replayed, so replace any routes already published
+    remove_deployment_routes(deployment.id, app)
 
     # Generate handlers
     handlers_by_name = {}
     for handler in handlers:
-        handlers_by_name[handler.name] = generate_handler(handler, deployment.project_id)
+        handlers_by_name[handler.name] = generate_handler(handler, deployment.project_id, manifest)
 
     # Add routes
     for route in routes:
@@ -43,24 +53,39 @@ async def publish_deployment(app, uuid):
         # Add route to app
         app.router.routes.append(r)
 
+    app.state.deployments.add(deployment.id)
+
 
-async def delete_deployment(app, uuid):
+async def delete_deployment(app, event):
     """
     Delete a deployment's routes
 
     :param app: app instance to be modified
-    :param uuid: the id of the deployment
+    :param event: the `deployment.deleted` event
     """
-    # Fetch deployment and its associated routes
-    db = app.state.database
-    deployment = await Deployment.find(uuid, db)
-    routes = await deployment.routes
+    # The deployment no longer exists, so find its routes by their names
+    remove_deployment_routes(event["deployment_id"], app)
+    app.state.deployments.discard(event["deployment_id"])
 
-    # Remove routes from app
-    for route in routes:
-        # Retrieve the route
-        _, i = find_route_by_name(route.id, deployment.id, app)
-        if i == -1:
-            continue
 
-        del app.router.routes[i]
+async def update_traffic(app, event):
+    """
+    Update how a project's unversioned traffic is split
+
+    :param app: app instance to be modified
+    :param event: the `traffic.updated` or `deployment.promoted` event
+    """
+    # The split may have changed again since, so use the current one
+    traffic = await Traffic.find(event["project_id"], app.state.database)
+    await apply_split(app, traffic, event["project_id"])
+
+
+async def update_aliases(app, event):
+    """
+    Update where a project's aliases point
+
+    :param app: app instance to be modified
+    :param event: the `alias.updated` or `alias.deleted` event
+    """
+    # Aliases may have changed again since, so use the current ones
+    await apply_aliases(app, event["project_id"], app.state.database)
@@ -1,28 +1,46 @@
 import aioredis
 import asyncio
 
-from .channel import reader
-from .modifiers import delete_deployment, publish_deployment
+from . import slots
+from .stream import create_group, reader
+from .modifiers import delete_deployment, publish_deployment, update_aliases, update_traffic
 
+# Workers for each type of deployment event
+WORKERS = {
+    "deployment.published": publish_deployment,
+    "deployment.deleted": delete_deployment,
+    "deployment.promoted": update_traffic,
+    "traffic.updated": update_traffic,
+    "alias.updated": update_aliases,
+    "alias.deleted": update_aliases,
+}
 
-def configure(app, config):
+
+def configure(app, config, server_id, workers):
     """
-    Configure redis pub/pub for an app
+    Configure the redis event stream for an app
 
     :param app: app instance to be modified
     :param config: redis configuration to use
+    :param server_id: unique name of the server
+    :param workers: number of worker processes on the server
     """
     async def internal_configure():
         # Connect to redis and add to app state
         redis = await aioredis.create_redis(**config)
         app.state.redis = redis
 
-        # Subscribe to the `publish` and `delete` channels
-        delete, publish = await redis.subscribe("delete", "publish")
+        # Name the runtime after its worker slot, which is also used as its consumer group
+        slot = await slots.claim(redis, server_id, workers)
+        app.state.slot = slot
+        app.state.runtime_id = slot.runtime_id
+        app.state.slot_renewal = asyncio.ensure_future(slots.renew(redis, slot))
+
+        # Create the group before routes are loaded so no events are missed
+        await create_group(redis, slot.runtime_id)
 
-        # Run readers
-        asyncio.ensure_future(reader(delete, app, delete_deployment))
-        asyncio.ensure_future(reader(publish, app, publish_deployment))
+        # Run reader
+        app.state.reader = asyncio.ensure_future(reader(redis, app, slot.runtime_id, WORKERS))
 
     return internal_configure
 
@@ -34,9 +52,16 @@ def shutdown(app):
     :param app: app instance to retrieve redis connection from
     """
     async def internal_shutdown():
+        # Stop reading events, unacknowledged events are replayed on startup
+        app.state.reader.cancel()
+
         # Retrieve redis
         redis = app.state.redis
 
+        # Let the next worker take over the slot
+        app.state.slot_renewal.cancel()
+        await slots.release(redis, app.state.slot)
+
         # Close the connection and wait
         redis.close()
         await redis.wait_closed()
@@ -0,0 +1,100 @@
+import asyncio
+import logging
+import uuid
+
+# Seconds a worker slot is held without being renewed
+SLOT_TTL = 30
+
+# Seconds between renewals of a worker slot
+SLOT_RENEW_INTERVAL = 10
+
+# Extend a slot's lease if it is still held by the worker
+RENEW_SCRIPT = """
+if redis.call("get", KEYS[1]) == ARGV[1] then
+    return redis.call("expire", KEYS[1], ARGV[2])
+end
+return 0
+"""
+
+# Release a slot if it is still held by the worker
+RELEASE_SCRIPT = """
+if redis.call("get", KEYS[1]) == ARGV[1] then
+    return redis.call("del", KEYS[1])
+end
+return 0
+"""
+
+
+class Slot(object):
+    """
+    A numbered worker slot on a server. Each worker process needs every event,
+    so each reads through its own consumer group. Naming the group after a
+    slot rather than the process lets a restarted worker continue from the
+    events its predecessor had not acknowledged.
+
+    :param server_id: unique name of the server
+    :param number: the slot's number, less than the number of workers
+    :param token: value identifying the worker holding the slot
+    """
+    def __init__(self, server_id, number, token):
+        self.server_id = server_id
+        self.number = number
+        self.token = token
+
+    @property
+    def key(self):
+        return f"runtime-slots:{self.server_id}:{self.number}"
+
+    @property
+    def runtime_id(self):
+        return f"{self.server_id}-{self.number}"
+
+
+async def claim(redis, server_id, workers):
+    """
+    Claim the first free worker slot, waiting for one to be released or
+    expire if a previous worker stopped without releasing its slot
+
+    :param redis: the redis connection
+    :param server_id: unique name of the server
+    :param workers: number of worker processes on the server
+    :return: the claimed slot
+    """
+    token = uuid.uuid4().hex
+    while True:
+        for number in range(workers):
+            slot = Slot(server_id, number, token)
+            if await redis.set(slot.key, token, expire=SLOT_TTL, exist=redis.SET_IF_NOT_EXIST):
+                return slot
+
+        logging.warning(f"All {workers} worker slots are taken, waiting for one to be released")
+        await asyncio.sleep(SLOT_RENEW_INTERVAL)
+
+
+async def renew(redis, slot):
+    """
+    Keep holding a worker slot until cancelled
+
+    :param redis: the redis connection
+    :param slot: the slot to hold
+    """
+    while True:
+        await asyncio.sleep(SLOT_RENEW_INTERVAL)
+
+        try:
+            renewed = await redis.eval(RENEW_SCRIPT, keys=[slot.key], args=[slot.token, SLOT_TTL])
+            if not renewed and not await redis.set(slot.key, slot.token, expire=SLOT_TTL,
+                                                   exist=redis.SET_IF_NOT_EXIST):
+                logging.error(f"Worker slot {slot.number} was claimed by another worker")
+        except Exception:
+            logging.exception(f"Failed to renew worker slot {slot.number}")
+
+
+async def release(redis, slot):
+    """
+    Release a worker slot so the next worker can claim it immediately
+
+    :param redis: the redis connection
+    :param slot: the slot to release
+    """
+    await redis.eval(RELEASE_SCRIPT, keys=[slot.key], args=[slot.token])
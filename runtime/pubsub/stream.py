@@ -0,0 +1,173 @@
+import asyncio
+import json
+import logging
+
+from aioredis.errors import ReplyError
+
+from util import parse_uuid
+
+# Stream deployment events are appended to, see /api/src/events.rs
+STREAM = "deployments:events"
+
+# Newest version of the event schema which can be consumed
+SCHEMA_VERSION = 1
+
+# How long to wait for new events before reading again
+BLOCK_TIME = 5 * 1000
+
+# Number of events to read at once
+BATCH_SIZE = 100
+
+# Seconds between retries of events which failed to process
+RECLAIM_INTERVAL = 30
+
+# How long an event must have been pending before it is retried, in milliseconds
+RECLAIM_IDLE_TIME = 30 * 1000
+
+# Number of times an event is delivered before it is given up on
+MAX_DELIVERIES = 5
+
+
+async def create_group(redis, group):
+    """
+    Create the consumer group for a runtime. New groups only receive events
+    published after they are created as the initial routes are loaded from
+    the database. The API removes the group once the runtime deregisters or
+    stops sending heartbeats.
+
+    :param redis: the redis connection
+    :param group: name of the consumer group
+    """
+    try:
+        await redis.xgroup_create(STREAM, group, latest_id="$", mkstream=True)
+    except ReplyError as e:
+        # The group already exists, so continue from its last acknowledged event
+        if not str(e).startswith("BUSYGROUP"):
+            raise
+
+
+async def reader(redis, app, runtime_id, workers):
+    """
+    Accept incoming events from the deployment event stream. Each runtime is
+    the only consumer in its group, so events which were delivered but never
+    acknowledged are replayed before new events are read, and each event is
+    only acknowledged once it is processed. Events which failed to process
+    are periodically retried until they have been delivered too many times.
+
+    :param redis: the redis connection to read with
+    :param app: starlette app to modify routes on
+    :param runtime_id: the consumer group and consumer name for the runtime
+    :param workers: functions accepting an app instance and event by event type
+    """
+    # Start with the events pending for this consumer
+    latest_id = "0"
+    loop = asyncio.get_event_loop()
+    next_reclaim = loop.time() + RECLAIM_INTERVAL
+    while True:
+        if latest_id == ">" and loop.time() >= next_reclaim:
+            await reclaim(redis, app, runtime_id, workers)
+            next_reclaim = loop.time() + RECLAIM_INTERVAL
+
+        messages = await redis.xread_group(
+            runtime_id, runtime_id, [STREAM],
+            timeout=BLOCK_TIME, count=BATCH_SIZE, latest_ids=[latest_id]
+        )
+
+        if len(messages) == 0:
+            # All pending events were replayed, so switch to new events
+            latest_id = ">"
+            continue
+
+        for _, message_id, fields in messages:
+            if latest_id != ">":
+                latest_id = message_id
+
+            await process(redis, app, runtime_id, workers, message_id, fields)
+
+
+async def reclaim(redis, app, runtime_id, workers):
+    """
+    Retry events which were delivered but not acknowledged for a while,
+    acknowledging those which have been delivered too many times so a
+    failing event isn't retried forever
+
+    :param redis: the redis connection
+    :param app: starlette app to modify routes on
+    :param runtime_id: the consumer group and consumer name for the runtime
+    :param workers: functions accepting an app instance and event by event type
+    """
+    pending = await redis.xpending(STREAM, runtime_id, "-", "+", BATCH_SIZE)
+    for message_id, _, idle, deliveries in pending:
+        if idle < RECLAIM_IDLE_TIME:
+            continue
+        elif deliveries >= MAX_DELIVERIES:
+            logging.error(f"Giving up on event {message_id.decode()} after {deliveries} deliveries")
+            await redis.xack(STREAM, runtime_id, message_id)
+            continue
+
+        # Claiming the event counts as another delivery
+        claimed = await redis.xclaim(STREAM, runtime_id, runtime_id, RECLAIM_IDLE_TIME, message_id)
+        if len(claimed) == 0:
+            # The event was trimmed from the stream, so there is nothing to retry
+            await redis.xack(STREAM, runtime_id, message_id)
+            continue
+
+        for claimed_id, fields in claimed:
+            await process(redis, app, runtime_id, workers, claimed_id, fields)
+
+
+async def process(redis, app, runtime_id, workers, message_id, fields):
+    """
+    Run the worker for an event and acknowledge it
+
+    :param redis: the redis connection
+    :param app: starlette app to modify routes on
+    :param runtime_id: the consumer group for the runtime
+    :param workers: functions accepting an app instance and event by event type
+    :param message_id: id of the event in the stream
+    :param fields: fields of the stream entry
+    """
+    # Entries trimmed from the stream have no fields
+    event = parse_event((fields or {}).get(b"event", b"").decode())
+    worker = workers.get(event.get("type")) if event is not None else None
+
+    if worker is None:
+        if event is not None:
+            logging.warning(f"Skipping event with unknown type {event.get('type')}")
+    else:
+        try:
+            await worker(app, event)
+        except Exception:
+            # Leave the event pending so it is retried once it has been idle for a while
+            logging.exception(f"Failed to process event {message_id.decode()}")
+            return
+
+    await redis.xack(STREAM, runtime_id, message_id)
+
+
+def parse_event(message):
+    """
+    Parse and validate a deployment event, see /api/src/events.rs for the format
+
+    :param message: the raw message from redis
+    :return: the event with its ids parsed or `None`
+    """
+    try:
+        event = json.loads(message)
+    except ValueError:
+        logging.error(f"Failed to parse event: {message}")
+        return None
+
+    if not isinstance(event, dict) or not isinstance(event.get("schema_version"), int):
+        logging.error(f"Received malformed event: {message}")
+        return None
+    elif event["schema_version"] > SCHEMA_VERSION:
+        logging.warning(f"Skipping event with unsupported schema version {event['schema_version']}")
+        return None
+
+    for key in ("project_id", "deployment_id"):
+        event[key] = parse_uuid(str(event.get(key)))
+        if event[key] is None:
+            return None
+
+    return event
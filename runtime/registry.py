@@ -0,0 +1,81 @@
+import asyncio
+import logging
+
+import requests
+
+# Seconds between heartbeats, well within the API's expiry for runtimes
+HEARTBEAT_INTERVAL = 10
+
+# Seconds to wait for the API to respond
+REQUEST_TIMEOUT = 5
+
+
+def configure(app, config):
+    """
+    Register the runtime with the API and periodically report the deployments it has loaded
+
+    :param app: app instance to retrieve loaded deployments and the runtime's name from
+    :param config: API configuration to use
+    """
+    async def internal_configure():
+        app.state.heartbeat = asyncio.ensure_future(heartbeat(app, config, app.state.runtime_id))
+
+    return internal_configure
+
+
+def shutdown(app, config):
+    """
+    Stop sending heartbeats and deregister the runtime
+
+    :param app: app instance to retrieve the heartbeat task and the runtime's name from
+    :param config: API configuration to use
+    """
+    async def internal_shutdown():
+        app.state.heartbeat.cancel()
+
+        try:
+            await send("DELETE", config, app.state.runtime_id)
+        except requests.RequestException as e:
+            logging.warning(f"Failed to deregister runtime: {e}")
+
+    return internal_shutdown
+
+
+async def heartbeat(app, config, runtime_id):
+    """
+    Send the loaded deployments to the API until cancelled
+
+    :param app: app instance to retrieve loaded deployments from
+    :param config: API configuration to use
+    :param runtime_id: unique name of the runtime
+    """
+    while True:
+        deployments = [str(deployment) for deployment in app.state.deployments]
+        try:
+            await send("PUT", config, runtime_id, {"deployments": deployments})
+        except requests.RequestException as e:
+            logging.warning(f"Failed to send heartbeat: {e}")
+
+        await asyncio.sleep(HEARTBEAT_INTERVAL)
+
+
+async def send(method, config, runtime_id, body=None):
+    """
+    Make an authenticated request for the runtime to the API
+
+    :param method: the HTTP method to use
+    :param config: API configuration to use
+    :param runtime_id: unique name of the runtime
+    :param body: optional JSON body
+    """
+    def request():
+        response = requests.request(
+            method,
+            f"{config['url']}/internal/runtimes/{runtime_id}",
+            json=body,
+            headers={"Authorization": f"Bearer {config['token']}"},
+            timeout=REQUEST_TIMEOUT
+        )
+        response.raise_for_status()
+
+    await asyncio.get_event_loop().run_in_executor(None, request)
@@ -0,0 +1,136 @@
+import os
+import shutil
+
+
+class GCSStorage(object):
+    """
+    Read files from a Google Cloud Storage bucket
+
+    :param bucket: the name of the bucket
+    """
+    def __init__(self, bucket):
+        from google.cloud import storage
+
+        self.bucket = storage.Client().bucket(bucket)
+
+    def download(self, key, file):
+        """
+        Write the contents of an object to a file
+
+        :param key: the key of the object
+        :param file: the file object to write to
+        :return: whether the object exists
+        """
+        blob = self.bucket.blob(key)
+        if not blob.exists():
+            return False
+
+        blob.download_to_file(file)
+        return True
+
+
+class S3Storage(object):
+    """
+    Read files from an S3 compatible bucket, such as AWS S3 or MinIO
+
+    :param bucket: the name of the bucket
+    :param region: the region the bucket is in
+    :param endpoint: custom endpoint for self-hosted services or `None`
+    :param key: the access key id
+    :param secret: the secret access key
+    """
+    def __init__(self, bucket, region, endpoint, key, secret):
+        import boto3
+        from botocore.config import Config
+
+        # Custom endpoints are generally self-hosted and only support path-style requests
+        addressing_style = "path" if endpoint is not None else "auto"
+        self.bucket = bucket
+        self.client = boto3.client(
+            "s3",
+            region_name=region,
+            endpoint_url=endpoint,
+            aws_access_key_id=key,
+            aws_secret_access_key=secret,
+            config=Config(s3={"addressing_style": addressing_style})
+        )
+
+    def download(self, key, file):
+        """
+        Write the contents of an object to a file
+
+        :param key: the key of the object
+        :param file: the file object to write to
+        :return: whether the object exists
+        """
+        from botocore.exceptions import ClientError
+
+        try:
+            self.client.download_fileobj(self.bucket, key, file)
+        except ClientError as e:
+            if e.response.get("Error", {}).get("Code") in ("404", "NoSuchKey"):
+                return False
+            raise
+
+        return True
+
+
+class LocalStorage(object):
+    """
+    Read files from a directory on the local filesystem
+
+    :param directory: the directory files are stored in
+    """
+    def __init__(self, directory):
+        self.directory = directory
+
+    def path(self, key):
+        """
+        Get the path to an object, preventing keys from escaping the directory
+
+        :param key: the key of the object
+        :return: the path to the object or `None` if the key is invalid
+        """
+        parts = key.split("/")
+        if any(part in ("", ".", "..") for part in parts):
+            return None
+
+        return os.path.join(self.directory, *parts)
+
+    def download(self, key, file):
+        """
+        Write the contents of an object to a file
+
+        :param key: the key of the object
+        :param file: the file object to write to
+        :return: whether the object exists
+        """
+        path = self.path(key)
+        if path is None or not os.path.isfile(path):
+            return False
+
+        with open(path, "rb") as source:
+            shutil.copyfileobj(source, file)
+        return True
+
+
+def connect(cfg):
+    """
+    Connect to the storage backend static files are served from, which must
+    be the same one the API stores them in
+
+    :param cfg: the runtime configuration
+    :return: the storage backend
+    """
+    storage = cfg.storage
+    backend = storage["backend"]
+
+    if backend == "gcs":
+        return GCSStorage(cfg.gcp_bucket)
+    elif backend == "s3":
+        return S3Storage(storage["s3_bucket"], storage["s3_region"], storage["s3_endpoint"], storage["s3_key"],
+                         storage["s3_secret"])
+    elif backend == "local":
+        return LocalStorage(storage["local_directory"])
+
+    raise ValueError(f"unknown storage backend '{backend}', expected one of gcs, s3, or local")
@@ -0,0 +1,130 @@
+import hashlib
+import uuid
+
+from starlette.datastructures import MutableHeaders
+from starlette.requests import HTTPConnection
+
+from util import matches_route
+
+# Cookie assigning clients to a deployment when no sticky header is configured
+COOKIE = "backendless-traffic"
+
+# Seconds the assignment cookie is kept for
+COOKIE_MAX_AGE = 30 * 24 * 60 * 60
+
+
+class Split(object):
+    """
+    How a project's unversioned traffic is split between its deployments
+
+    :param project_id: the id of the project
+    :param stable: the version receiving the remaining traffic
+    :param candidate: the version receiving the weighted traffic or `None`
+    :param weight: percentage of clients sent to the candidate
+    :param sticky_header: header identifying clients or `None` to use a cookie
+    """
+    def __init__(self, project_id, stable, candidate, weight, sticky_header):
+        self.project_id = project_id
+        self.stable = stable
+        self.candidate = candidate
+        self.weight = weight
+        self.sticky_header = sticky_header
+
+    def __str__(self):
+        return f"<Split project_id={self.project_id} stable={self.stable} candidate={self.candidate} " \
+               f"weight={self.weight}>"
+
+    def __repr__(self):
+        return self.__str__()
+
+    def version_for(self, key):
+        """
+        Choose the version a client is sent to. Clients are assigned by a hash
+        of their key, so they keep their version as the weight is increased.
+
+        :param key: the value identifying the client
+        :return: the version to serve
+        """
+        if self.candidate is None:
+            return self.stable
+
+        digest = hashlib.sha256(f"{self.project_id}:{key}".encode()).hexdigest()
+        bucket = int(digest, 16) % 100
+        return self.candidate if bucket < self.weight else self.stable
+
+
+async def apply_split(app, traffic, project_id):
+    """
+    Update how a project's traffic is routed from its split in the database
+
+    :param app: app instance to be modified
+    :param traffic: the project's traffic split or `None` if it was removed
+    :param project_id: the id of the project
+    """
+    # Projects are routed by name, so remove the split under any previous name
+    for name, split in list(app.state.traffic.items()):
+        if str(split.project_id) == str(project_id):
+            del app.state.traffic[name]
+
+    if traffic is None:
+        return
+
+    project = await traffic.project
+    stable = await traffic.stable
+    candidate = await traffic.candidate
+    if stable is None:
+        return
+
+    app.state.traffic[project.name] = Split(
+        traffic.project_id,
+        stable.version,
+        candidate.version if candidate is not None else None,
+        traffic.weight,
+        traffic.sticky_header
+    )
+
+
+class TrafficMiddleware(object):
+    """
+    Route requests without a version, `/{project}/{path}`, to the version
+    chosen by the project's traffic split. Requests which match a route
+    as-is are left untouched.
+
+    :param app: the ASGI app to wrap
+    """
+    def __init__(self, app):
+        self.app = app
+
+    async def __call__(self, scope, receive, send):
+        if scope["type"] != "http":
+            await self.app(scope, receive, send)
+            return
+
+        app = scope["app"]
+        segments = scope["path"].split("/", 2)
+        name = segments[1]
+        split = app.state.traffic.get(name)
+        if split is None or matches_route(app, scope):
+            await self.app(scope, receive, send)
+            return
+
+        # Identify the client by the configured header, falling back to a cookie
+        connection = HTTPConnection(scope)
+        key = connection.headers.get(split.sticky_header) if split.sticky_header else None
+        assign = False
+        if not key:
+            key = connection.cookies.get(COOKIE)
+        if not key:
+            key = uuid.uuid4().hex
+            assign = split.sticky_header is None
+
+        version = split.version_for(key)
+        scope = dict(scope, path="/".join(["", name, version] + segments[2:]))
+
+        async def send_with_cookie(message):
+            if assign and message["type"] == "http.response.start":
+                headers = MutableHeaders(scope=message)
+                headers.append("Set-Cookie", f"{COOKIE}={key}; Path=/{name}; Max-Age={COOKIE_MAX_AGE}; HttpOnly")
+            await send(message)
+
+        await self.app(scope, receive, send_with_cookie)
@@ -1,6 +1,8 @@
 import logging
 import uuid
 
+from starlette.routing import Match
+
 
 def convert_to(value: str, cls):
     """
@@ -62,6 +64,35 @@ def find_route_by_name(route, deployment, app):
     return None, -1
 
 
+def remove_deployment_routes(deployment, app):
+    """
+    Remove all of the routes in a deployment
+
+    :param deployment: the id of the deployment
+    :param app: starlette instance to remove routes from
+    """
+    suffix = generate_name("", deployment)
+    app.router.routes[:] = [
+        route for route in app.router.routes
+        if not (getattr(route, "name", None) or "").endswith(suffix)
+    ]
+
+
+def matches_route(app, scope):
+    """
+    Check whether a request matches any of an app's routes as-is
+
+    :param app: starlette instance to check the routes of
+    :param scope: the request scope
+    :return: whether any route matched
+    """
+    for route in app.router.routes:
+        match, _ = route.matches(scope)
+        if match != Match.NONE:
+            return True
+    return False
+
+
 def parse_uuid(s):
     """
     Attempt to parse a string as a UUID